@@ -7,12 +7,22 @@ pub enum Error {
     // Access
     Unauthorized,
     NotKeeper,
+    NotPrimaryKeeper,
     NotLiquidator,
     NotAdmin,
+    NotGuardian,
+    AdminStillActive,
 
     // Market
     MarketNotFound,
     MarketAlreadyExists,
+    TemplateNotFound,
+    LpNotWhitelisted,
+    MarketReduceOnly,
+    MarketDelisted,
+    MarketHasOpenPositions,
+    SettlementCloseQueued,
+    SettlementPriceNotFixed,
 
     // Requests
     RequestNotFound,
@@ -24,6 +34,8 @@ pub enum Error {
     PositionNotLiquidatable,
     PositionTooSmall,
     InsufficientPositionSize,
+    TradingDisabled,
+    AccountHasOpenPositions,
 
     // Orders
     OrderNotFound,
@@ -31,6 +43,14 @@ pub enum Error {
     OrderCannotBeExecutedYet,
     InvalidOrderSize,
     OrderFrozen,
+    OrderBookDisabled,
+    OrderWouldCross,
+    OrderNotExecuted,
+    OrderExpired,
+    FillOrKillNotFilled,
+    DeadManSwitchNotEnabled,
+    TooManyConditionalCloses,
+    DuplicateClientOrderId,
 
     // Risk
     InsufficientCollateral,
@@ -38,26 +58,65 @@ pub enum Error {
     MaxLeverageExceeded,
     OICapReached,
     MaxOpenInterestExceeded,
+    MaxPositionSizeExceeded,
+    LeverageBandExceeded,
     InsufficientLiquidity,
     InsufficientPoolLiquidity,
+    LiquidationAuditDisabled,
 
     // Execution
     SlippageExceeded,
     PriceNotAcceptable,
+    ExecutionPriceOutOfBounds,
     InvalidPrice,
     InvalidCollateralAmount,
     PriceStale,
+    MarketNotInOutage,
     InvalidTriggerPrice,
     UnsupportedOrderType,
 
     // Balance
     InsufficientBalance,
     InsufficientMarketTokens,
+    InsufficientBackstopShares,
+    UnknownCollateralToken,
+    DuplicateTransferNotification,
 
     // Oracle
     PriceNotAvailable,
     InvalidOracleSignature,
 
+    // Solvency
+    EpochNotElapsed,
+    SnapshotNotPublished,
+
+    // Checkpoints
+    CheckpointNotFound,
+    AlreadySigned,
+
+    // Scheduler
+    GasReservationFailed,
+    NoGasReservation,
+
+    // Fee discounts
+    FeeDiscountDisabled,
+    ExternalQueryFailed,
+
+    // Referrals
+    ReferralCodeTaken,
+    ReferralCodeNotFound,
+    TooManyReferralCodes,
+
+    // Fee routing
+    InvalidFeeSplit,
+
+    // Capacity
+    CapacityReached,
+
+    // Withdraw-only mode
+    WithdrawOnlyMode,
+    NotInWithdrawOnlyMode,
+
     // Other
     InsufficientOpenInterest,
     InvalidParameter,
@@ -9,12 +9,13 @@ mod services;
 mod modules;
 
 use sails_rs::prelude::*;
-use sails_rs::collections::HashMap;
-use sails_rs::gstd::msg;
+use sails_rs::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use sails_rs::gstd::{exec, msg};
 use sails_rs::cell::RefCell;
 use core::cell::{Ref, RefMut};
 
 use types::*;
+use errors::Error;
 
 struct SyncRefCell<T>(RefCell<T>);
 unsafe impl<T> Sync for SyncRefCell<T> {}
@@ -25,9 +26,15 @@ static STATE: SyncRefCell<Option<PerpetualDEXState>> = SyncRefCell(RefCell::new(
 pub struct PerpetualDEXState {
     pub markets: HashMap<String, Market>,
     pub market_configs: HashMap<String, MarketConfig>,
+    /// Named config presets for listing new markets
+    pub market_templates: HashMap<String, MarketConfig>,
+    pub market_metadata: HashMap<String, MarketMetadata>,
     pub pool_amounts: HashMap<String, PoolAmounts>,
     pub market_tokens: HashMap<String, MarketTokenInfo>,
-    pub positions: HashMap<PositionKey, Position>,
+    pub backstop_pools: HashMap<String, BackstopPool>,
+    pub backstop_stakes: HashMap<(String, ActorId), BackstopStake>,
+    /// Ordered so keeper passes can resume from a cursor
+    pub positions: BTreeMap<PositionKey, Position>,
     pub account_positions: HashMap<ActorId, Vec<PositionKey>>,
     pub deposit_requests: HashMap<RequestKey, DepositRequest>,
     pub withdrawal_requests: HashMap<RequestKey, WithdrawalRequest>,
@@ -36,10 +43,169 @@ pub struct PerpetualDEXState {
     pub order_counter: u64,
     pub oracle: OracleState,
     pub admin: ActorId,
+    /// Timestamp of the admin's last successful admin action, for guardian recovery
+    pub admin_last_active: u64,
+    pub recovery_config: RecoveryConfig,
+    /// Guardian votes per proposed admin for the current inactivity round
+    pub recovery_votes: HashMap<ActorId, HashSet<ActorId>>,
+    /// `admin_last_active` value the current votes were cast against
+    pub recovery_round: u64,
+    pub fee_discount_config: FeeDiscountConfig,
+    pub fee_discounts: HashMap<ActorId, CachedDiscount>,
+    /// Referral code -> owner
+    pub referral_codes: HashMap<String, ActorId>,
+    /// Referral codes registered per owner
+    pub referral_code_counts: HashMap<ActorId, u32>,
+    /// Trader -> referrer, fixed by the first successful order carrying a code
+    pub referrers: HashMap<ActorId, ActorId>,
+    pub referral_stats: HashMap<ActorId, ReferralStats>,
+    /// Share of trading fees paid to the payer's referrer (bps)
+    pub referral_fee_share_bps: u16,
+    pub correlation_groups: HashMap<String, CorrelationGroup>,
+    pub block_flows: HashMap<String, BlockFlow>,
+    /// Funding spread revenue; backstops liquidation shortfalls
+    pub insurance_fund_usd: Usd,
+    /// Treasury part of split trading fees, kept apart from any trading balance
+    pub treasury_usd: Usd,
+    /// Saved orders still in `Created` status
+    pub open_orders: u64,
+    pub scheduled_market_configs: HashMap<String, ScheduledMarketConfig>,
+    pub market_bootstraps: HashMap<String, MarketBootstrap>,
+    /// Markets past `Listed`; absent markets are listed
+    pub market_stages: HashMap<String, MarketStage>,
+    /// Price every position of a settling market closes at, fixed from the settlement-window TWAP
+    /// or attested by the admin when no sample was taken in the window
+    pub settlement_prices: HashMap<String, u128>,
+    /// Oracle mid samples of settling markets whose settlement price is not fixed yet
+    pub settlement_twaps: HashMap<String, SettlementTwap>,
+    /// Pending settlement-close requests per settling market, executed in FIFO order
+    pub settlement_queues: HashMap<String, VecDeque<SettlementClose>>,
+    /// Positions already notified of a margin call; cleared once they leave the band
+    pub margin_called: HashSet<PositionKey>,
+    /// Last position checked by the margin-call scan
+    pub margin_call_cursor: Option<PositionKey>,
+    /// Last position checked by the scheduled liquidation scan
+    pub liquidation_scan_cursor: Option<PositionKey>,
+    pub auto_topups: HashMap<PositionKey, AutoTopUpRule>,
+    /// Conditional closes per position, ordered so keeper passes can resume from a cursor
+    pub conditional_closes: BTreeMap<PositionKey, Vec<ConditionalClose>>,
+    /// Last position whose conditional closes were evaluated
+    pub conditional_close_cursor: Option<PositionKey>,
+    pub dead_man_switches: HashMap<ActorId, DeadManSwitch>,
+    /// Last position settled by `settle_market_fees`, per market
+    pub fee_settlement_cursors: HashMap<String, PositionKey>,
+    /// (account, client order id) -> request key claimed by the order, for idempotent order
+    /// placement; an order executed on creation keeps its claim without a saved order
+    pub client_order_ids: HashMap<(ActorId, u64), RequestKey>,
+    /// Accounts that paused their own trading -> time opening resumes (`u64::MAX` = until re-enabled)
+    pub trading_disabled_until: HashMap<ActorId, u64>,
+    pub capacity_limits: CapacityLimits,
+    pub withdraw_only: Option<WithdrawOnlyState>,
+    /// Details of the most recent saved-order executions, at most `MAX_EXECUTION_DETAILS`
+    pub execution_details: HashMap<RequestKey, ExecutionDetails>,
+    /// Keys of `execution_details`, oldest first
+    pub execution_detail_keys: VecDeque<RequestKey>,
     pub keepers: Vec<ActorId>,
+    /// Last activity timestamp per keeper
+    pub keeper_activity: HashMap<ActorId, u64>,
+    pub keeper_rotation: KeeperRotationConfig,
+    pub liquidation_audit: LiquidationAuditConfig,
+    /// Last position checked by the liquidation price audit
+    pub liquidation_audit_cursor: Option<PositionKey>,
     pub liquidators: Vec<ActorId>,
     pub next_request_id: u64,
+    /// Request id the stale-order sweep resumes from; no pending order below it can be swept
+    pub order_sweep_cursor: u64,
     pub balances: HashMap<ActorId, Usd>,
+    /// Native VARA held for keeper gas refunds
+    pub native_balances: HashMap<ActorId, u128>,
+    pub execution_fee_config: ExecutionFeeConfig,
+    /// Max deviation (bps) of a saved order's execution price from its trigger and the oracle mid (0 = off)
+    pub execution_price_guard_bps: u16,
+    pub order_books: HashMap<String, OrderBook>,
+    pub next_book_order_id: u64,
+    pub account_settings: HashMap<ActorId, AccountSettings>,
+    /// Minimum seconds between balances root publications (0 = every call)
+    pub solvency_epoch_seconds: u64,
+    pub balances_snapshot: Option<BalancesSnapshot>,
+    /// Minimum seconds between state checkpoints (0 = every call)
+    pub checkpoint_interval_seconds: u64,
+    /// Most recent state checkpoints, oldest first
+    pub checkpoints: VecDeque<StateCheckpoint>,
+    pub scheduler_config: SchedulerConfig,
+    pub gas_reservations: Vec<GasReservation>,
+    pub next_scheduled_run: Option<u32>,
+    pub last_scheduled_run: Option<u32>,
+    pub collateral_tokens: HashMap<ActorId, CollateralToken>,
+    /// (token, transfer nonce) pairs already credited, so replayed notifications are rejected
+    pub processed_transfers: HashSet<(ActorId, u64)>,
+    /// Auto-withdraw transfers (account, token, USD) queued by closes, sent once the call succeeds
+    pub pending_profit_sweeps: Vec<(ActorId, ActorId, Usd)>,
+    pub funding_stats: HashMap<String, FundingStats>,
+    pub trader_stats: HashMap<ActorId, TraderStats>,
+    pub market_fee_stats: HashMap<String, MarketFeeStats>,
+    pub slippage_stats: HashMap<String, SlippageStats>,
+    pub order_execution_stats: HashMap<String, OrderExecutionCounters>,
+    pub market_liquidations: HashMap<String, VecDeque<LiquidationRecord>>,
+    pub account_liquidations: HashMap<ActorId, VecDeque<LiquidationRecord>>,
+    pub account_fills: HashMap<ActorId, VecDeque<FillRecord>>,
+    pub notifications: HashMap<ActorId, NoticeInbox>,
+    /// Minimum seconds between equity samples of one account (0 = every execution)
+    pub equity_sample_interval_seconds: u64,
+    pub equity_history: HashMap<ActorId, VecDeque<EquitySample>>,
+    /// Last account sampled by `sample_active`; the next call resumes after it
+    pub equity_sample_cursor: Option<ActorId>,
+    /// Approved liquidity providers for markets with `lp_whitelist_enabled`
+    pub lp_whitelists: HashMap<String, HashSet<ActorId>>,
+}
+
+/// Copy of the entries that one account's trades, closes and fee settlements on one market
+/// write: its positions there with their conditional closes and top-up rules, the balances they
+/// pay into, its stats and notices, the market's pool and stats, and the buckets trading fees are
+/// routed to. Lets a flow roll back a failed step without cloning the whole state.
+#[derive(Debug, Clone)]
+pub struct PositionSnapshot {
+    account: ActorId,
+    market: String,
+    positions: Vec<(Position, Option<Vec<ConditionalClose>>, Option<AutoTopUpRule>)>,
+    account_positions: Option<Vec<PositionKey>>,
+    balances: Vec<(ActorId, Option<Usd>)>,
+    referral_stats: Option<(ActorId, Option<ReferralStats>)>,
+    fills: Option<VecDeque<FillRecord>>,
+    equity_history: Option<VecDeque<EquitySample>>,
+    trader_stats: Option<TraderStats>,
+    notifications: Option<NoticeInbox>,
+    pool: Option<PoolAmounts>,
+    backstop_pool: Option<BackstopPool>,
+    block_flow: Option<BlockFlow>,
+    funding_stats: Option<FundingStats>,
+    market_fee_stats: Option<MarketFeeStats>,
+    slippage_stats: Option<SlippageStats>,
+    insurance_fund_usd: Usd,
+    treasury_usd: Usd,
+}
+
+impl PositionSnapshot {
+    /// Also cover `account`'s balance, for a payout to a receiver other than the owner
+    pub fn with_balance(mut self, account: ActorId) -> Self {
+        if !self.balances.iter().any(|(a, _)| *a == account) {
+            self.balances.push((account, PerpetualDEXState::get().balances.get(&account).copied()));
+        }
+        self
+    }
+}
+
+/// Copy of the entries that preparing and saving orders for one account writes. Orders saved
+/// since are found by request id, so a failed batch of saves rolls back without cloning the
+/// whole state.
+#[derive(Debug, Clone)]
+pub struct OrderPlacementSnapshot {
+    account: ActorId,
+    next_request_id: u64,
+    open_orders: u64,
+    account_orders: Option<Vec<RequestKey>>,
+    native_balance: Option<u128>,
+    client_order_id: Option<(u64, Option<RequestKey>)>,
 }
 
 impl PerpetualDEXState {
@@ -47,9 +213,13 @@ impl PerpetualDEXState {
         Self {
             markets: HashMap::new(),
             market_configs: HashMap::new(),
+            market_templates: HashMap::new(),
+            market_metadata: HashMap::new(),
             pool_amounts: HashMap::new(),
             market_tokens: HashMap::new(),
-            positions: HashMap::new(),
+            backstop_pools: HashMap::new(),
+            backstop_stakes: HashMap::new(),
+            positions: BTreeMap::new(),
             account_positions: HashMap::new(),
             deposit_requests: HashMap::new(),
             withdrawal_requests: HashMap::new(),
@@ -58,10 +228,81 @@ impl PerpetualDEXState {
             order_counter: 0,
             oracle: OracleState::new(),
             admin,
+            admin_last_active: sails_rs::gstd::exec::block_timestamp(),
+            recovery_config: RecoveryConfig::default(),
+            recovery_votes: HashMap::new(),
+            recovery_round: 0,
+            fee_discount_config: FeeDiscountConfig::default(),
+            fee_discounts: HashMap::new(),
+            referral_codes: HashMap::new(),
+            referral_code_counts: HashMap::new(),
+            referrers: HashMap::new(),
+            referral_stats: HashMap::new(),
+            referral_fee_share_bps: 0,
+            correlation_groups: HashMap::new(),
+            block_flows: HashMap::new(),
+            insurance_fund_usd: 0,
+            treasury_usd: 0,
+            open_orders: 0,
+            scheduled_market_configs: HashMap::new(),
+            market_bootstraps: HashMap::new(),
+            market_stages: HashMap::new(),
+            settlement_prices: HashMap::new(),
+            settlement_twaps: HashMap::new(),
+            settlement_queues: HashMap::new(),
+            margin_called: HashSet::new(),
+            margin_call_cursor: None,
+            liquidation_scan_cursor: None,
+            auto_topups: HashMap::new(),
+            conditional_closes: BTreeMap::new(),
+            conditional_close_cursor: None,
+            dead_man_switches: HashMap::new(),
+            fee_settlement_cursors: HashMap::new(),
+            client_order_ids: HashMap::new(),
+            trading_disabled_until: HashMap::new(),
+            capacity_limits: CapacityLimits::default(),
+            withdraw_only: None,
+            execution_details: HashMap::new(),
+            execution_detail_keys: VecDeque::new(),
             keepers: Vec::new(),
+            keeper_activity: HashMap::new(),
+            keeper_rotation: KeeperRotationConfig::default(),
+            liquidation_audit: LiquidationAuditConfig::default(),
+            liquidation_audit_cursor: None,
             liquidators: Vec::new(),
             next_request_id: 1,
+            order_sweep_cursor: 1,
             balances: HashMap::new(),
+            native_balances: HashMap::new(),
+            execution_fee_config: ExecutionFeeConfig::default(),
+            execution_price_guard_bps: 0,
+            order_books: HashMap::new(),
+            next_book_order_id: 1,
+            account_settings: HashMap::new(),
+            solvency_epoch_seconds: 86_400,
+            balances_snapshot: None,
+            checkpoint_interval_seconds: 3_600,
+            checkpoints: VecDeque::new(),
+            scheduler_config: SchedulerConfig::default(),
+            gas_reservations: Vec::new(),
+            next_scheduled_run: None,
+            last_scheduled_run: None,
+            collateral_tokens: HashMap::new(),
+            processed_transfers: HashSet::new(),
+            pending_profit_sweeps: Vec::new(),
+            funding_stats: HashMap::new(),
+            trader_stats: HashMap::new(),
+            market_fee_stats: HashMap::new(),
+            slippage_stats: HashMap::new(),
+            order_execution_stats: HashMap::new(),
+            market_liquidations: HashMap::new(),
+            account_liquidations: HashMap::new(),
+            account_fills: HashMap::new(),
+            notifications: HashMap::new(),
+            equity_sample_interval_seconds: 3_600,
+            equity_history: HashMap::new(),
+            equity_sample_cursor: None,
+            lp_whitelists: HashMap::new(),
         }
     }
 
@@ -87,10 +328,161 @@ impl PerpetualDEXState {
         *state = Some(Self::new(admin));
     }
 
+    /// Full copy of the state, used to roll back multi-step operations on error
+    /// (returning `Err` from a service does not revert the message).
+    pub fn snapshot() -> Self {
+        Self::get().clone()
+    }
+
+    pub fn restore(snapshot: Self) {
+        *Self::get_mut() = snapshot;
+    }
+
+    /// Snapshot of the entries a trade, close or fee settlement of `pos` can write
+    pub fn snapshot_position(pos: &Position) -> PositionSnapshot {
+        Self::snapshot_trade(pos.account, &pos.market)
+    }
+
+    /// Snapshot of the entries `account` trading on `market` can write, whichever of its
+    /// positions there the trade opens, nets or closes. Covers the balances of the account, its
+    /// referrer and its default receiver.
+    pub fn snapshot_trade(account: ActorId, market: &str) -> PositionSnapshot {
+        let st = Self::get();
+        let account_positions = st.account_positions.get(&account).cloned();
+        let positions = account_positions
+            .iter()
+            .flatten()
+            .filter_map(|key| st.positions.get(key))
+            .filter(|pos| pos.market == market)
+            .map(|pos| {
+                (pos.clone(), st.conditional_closes.get(&pos.key).cloned(), st.auto_topups.get(&pos.key).cloned())
+            })
+            .collect();
+        let referrer = st.referrers.get(&account).copied();
+        let default_receiver = st.account_settings.get(&account).and_then(|s| s.default_receiver);
+        let mut balances = vec![(account, st.balances.get(&account).copied())];
+        for other in referrer.into_iter().chain(default_receiver) {
+            if !balances.iter().any(|(a, _)| *a == other) {
+                balances.push((other, st.balances.get(&other).copied()));
+            }
+        }
+        PositionSnapshot {
+            account,
+            market: market.into(),
+            positions,
+            account_positions,
+            balances,
+            referral_stats: referrer.map(|r| (r, st.referral_stats.get(&r).cloned())),
+            fills: st.account_fills.get(&account).cloned(),
+            equity_history: st.equity_history.get(&account).cloned(),
+            trader_stats: st.trader_stats.get(&account).cloned(),
+            notifications: st.notifications.get(&account).cloned(),
+            pool: st.pool_amounts.get(market).cloned(),
+            backstop_pool: st.backstop_pools.get(market).cloned(),
+            block_flow: st.block_flows.get(market).cloned(),
+            funding_stats: st.funding_stats.get(market).cloned(),
+            market_fee_stats: st.market_fee_stats.get(market).cloned(),
+            slippage_stats: st.slippage_stats.get(market).cloned(),
+            insurance_fund_usd: st.insurance_fund_usd,
+            treasury_usd: st.treasury_usd,
+        }
+    }
+
+    /// Undo the changes since `snapshot`, dropping the positions the account opened on the
+    /// market since. Overlapping snapshots are restored newest first.
+    pub fn restore_position(snapshot: PositionSnapshot) {
+        fn put<K: core::hash::Hash + Eq, V>(map: &mut HashMap<K, V>, key: K, value: Option<V>) {
+            match value {
+                Some(value) => map.insert(key, value),
+                None => map.remove(&key),
+            };
+        }
+        let mut st = Self::get_mut();
+        let st = &mut *st;
+        let (account, market) = (snapshot.account, snapshot.market);
+
+        let opened: Vec<PositionKey> = st
+            .account_positions
+            .get(&account)
+            .into_iter()
+            .flatten()
+            .filter(|key| !snapshot.positions.iter().any(|(pos, _, _)| pos.key == **key))
+            .filter(|key| st.positions.get(*key).is_some_and(|pos| pos.market == market))
+            .copied()
+            .collect();
+        for key in opened {
+            st.positions.remove(&key);
+            st.conditional_closes.remove(&key);
+            st.auto_topups.remove(&key);
+        }
+        for (pos, closes, topup) in snapshot.positions {
+            match closes {
+                Some(closes) => st.conditional_closes.insert(pos.key, closes),
+                None => st.conditional_closes.remove(&pos.key),
+            };
+            put(&mut st.auto_topups, pos.key, topup);
+            st.positions.insert(pos.key, pos);
+        }
+        put(&mut st.account_positions, account, snapshot.account_positions);
+        for (holder, balance) in snapshot.balances {
+            put(&mut st.balances, holder, balance);
+        }
+        if let Some((referrer, stats)) = snapshot.referral_stats {
+            put(&mut st.referral_stats, referrer, stats);
+        }
+        put(&mut st.account_fills, account, snapshot.fills);
+        put(&mut st.equity_history, account, snapshot.equity_history);
+        put(&mut st.trader_stats, account, snapshot.trader_stats);
+        put(&mut st.notifications, account, snapshot.notifications);
+        put(&mut st.pool_amounts, market.clone(), snapshot.pool);
+        put(&mut st.backstop_pools, market.clone(), snapshot.backstop_pool);
+        put(&mut st.block_flows, market.clone(), snapshot.block_flow);
+        put(&mut st.funding_stats, market.clone(), snapshot.funding_stats);
+        put(&mut st.market_fee_stats, market.clone(), snapshot.market_fee_stats);
+        put(&mut st.slippage_stats, market, snapshot.slippage_stats);
+        st.insurance_fund_usd = snapshot.insurance_fund_usd;
+        st.treasury_usd = snapshot.treasury_usd;
+    }
+
+    /// Snapshot of the entries placing orders with `client_order_id` writes for `account`
+    pub fn snapshot_order_placement(account: ActorId, client_order_id: Option<u64>) -> OrderPlacementSnapshot {
+        let st = Self::get();
+        OrderPlacementSnapshot {
+            account,
+            next_request_id: st.next_request_id,
+            open_orders: st.open_orders,
+            account_orders: st.account_orders.get(&account).cloned(),
+            native_balance: st.native_balances.get(&account).copied(),
+            client_order_id: client_order_id.map(|id| (id, st.client_order_ids.get(&(account, id)).copied())),
+        }
+    }
+
+    pub fn restore_order_placement(snapshot: OrderPlacementSnapshot) {
+        let mut st = Self::get_mut();
+        let account = snapshot.account;
+        for id in snapshot.next_request_id..st.next_request_id {
+            st.orders.remove(&crate::utils::request_key(id));
+        }
+        st.next_request_id = snapshot.next_request_id;
+        st.open_orders = snapshot.open_orders;
+        match snapshot.account_orders {
+            Some(keys) => st.account_orders.insert(account, keys),
+            None => st.account_orders.remove(&account),
+        };
+        match snapshot.native_balance {
+            Some(balance) => st.native_balances.insert(account, balance),
+            None => st.native_balances.remove(&account),
+        };
+        if let Some((id, claim)) = snapshot.client_order_id {
+            match claim {
+                Some(key) => st.client_order_ids.insert((account, id), key),
+                None => st.client_order_ids.remove(&(account, id)),
+            };
+        }
+    }
+
     pub fn generate_request_key(&mut self) -> RequestKey {
-        let mut bytes = [0u8; 32];
-        bytes[..8].copy_from_slice(&self.next_request_id.to_le_bytes());
-        let key = H256::from(bytes);
+        let key = crate::utils::request_key(self.next_request_id);
         self.next_request_id += 1;
         key
     }
@@ -105,6 +497,79 @@ impl PerpetualDEXState {
         crate::utils::position_key(account, market, collateral_token, is_long)
     }
 
+    /// Fresh sub-position key for account/market/collateral/side (sub-position mode)
+    pub fn new_sub_position_key(
+        &mut self,
+        account: ActorId,
+        market: &str,
+        collateral_token: &str,
+        is_long: bool,
+    ) -> PositionKey {
+        let nonce = self.next_request_id;
+        self.next_request_id += 1;
+        crate::utils::sub_position_key(Self::get_position_key(account, market, collateral_token, is_long), nonce)
+    }
+
+    /// Withdraw-only mode has taken effect
+    pub fn is_withdraw_only(&self) -> bool {
+        self.withdraw_only
+            .as_ref()
+            .is_some_and(|w| w.activates_at <= exec::block_timestamp())
+    }
+
+    /// Trading, LP deposits and liquidations are halted in withdraw-only mode
+    pub fn ensure_not_withdraw_only(&self) -> Result<(), Error> {
+        if self.is_withdraw_only() {
+            return Err(Error::WithdrawOnlyMode);
+        }
+        Ok(())
+    }
+
+    /// Current lifecycle stage; a reduce-only market past its deadline is settling
+    pub fn market_stage(&self, market: &str) -> MarketStage {
+        match self.market_stages.get(market) {
+            None => MarketStage::Listed,
+            Some(MarketStage::ReduceOnly { deadline }) if *deadline <= exec::block_timestamp() => MarketStage::Settling,
+            Some(stage) => stage.clone(),
+        }
+    }
+
+    /// Increases (and LP deposits) need a listed market; decreases are allowed until delisting
+    pub fn ensure_market_allows(&self, market: &str, is_increase: bool) -> Result<(), Error> {
+        match self.market_stage(market) {
+            MarketStage::Listed => Ok(()),
+            MarketStage::Delisted => Err(Error::MarketDelisted),
+            _ if is_increase => Err(Error::MarketReduceOnly),
+            _ => Ok(()),
+        }
+    }
+
+    /// The account has not paused its own trading; closing stays allowed either way
+    pub fn ensure_trading_enabled(&self, account: ActorId) -> Result<(), Error> {
+        if self.trading_disabled_until.get(&account).is_some_and(|until| *until > exec::block_timestamp()) {
+            return Err(Error::TradingDisabled);
+        }
+        Ok(())
+    }
+
+    /// Room for another open position under the global cap
+    pub fn ensure_position_capacity(&self) -> Result<(), Error> {
+        let max = self.capacity_limits.max_open_positions;
+        if max > 0 && self.positions.len() as u64 >= max {
+            return Err(Error::CapacityReached);
+        }
+        Ok(())
+    }
+
+    /// Room for another pending saved order under the global cap
+    pub fn ensure_order_capacity(&self) -> Result<(), Error> {
+        let max = self.capacity_limits.max_pending_orders;
+        if max > 0 && self.open_orders >= max {
+            return Err(Error::CapacityReached);
+        }
+        Ok(())
+    }
+
     pub fn is_keeper(&self, actor: ActorId) -> bool {
         self.keepers.contains(&actor)
     }
@@ -113,12 +578,32 @@ impl PerpetualDEXState {
         self.liquidators.contains(&actor)
     }
 
+    /// Liquidation is open to `actor` on `market`: a keeper/liquidator, or anyone if the market is permissionless
+    pub fn can_liquidate(&self, actor: ActorId, market: &str) -> bool {
+        self.is_keeper(actor)
+            || self.is_liquidator(actor)
+            || self
+                .market_configs
+                .get(market)
+                .is_some_and(|cfg| cfg.permissionless_liquidation)
+    }
+
     pub fn is_admin(&self, actor: ActorId) -> bool {
         self.admin == actor
     }
+
+    /// Record admin activity: every successful admin action resets the recovery timer
+    pub fn touch_admin(&mut self, actor: ActorId) {
+        if self.is_admin(actor) {
+            self.admin_last_active = exec::block_timestamp();
+        }
+    }
 }
 
-use services::{TradingService, ExecutorService, AdminService, OracleService, ViewService, WalletService, MarketService};
+use services::{
+    TradingService, ExecutorService, AdminService, OracleService, ViewService, WalletService, MarketService,
+    AccountSettingsService,
+};
 
 pub struct VaraPerpDexProgram(());
 
@@ -139,4 +624,5 @@ impl VaraPerpDexProgram {
     pub fn oracle(&self) -> OracleService { Default::default() }
     pub fn wallet(&self) -> WalletService { Default::default() }
     pub fn market(&self) -> MarketService { Default::default() }
+    pub fn account_settings(&self) -> AccountSettingsService { Default::default() }
 }
@@ -0,0 +1,200 @@
+use crate::{PerpetualDEXState, errors::Error, modules::notification::NotificationModule, types::*, utils};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Wait between asking to re-enable trading and being able to open again, so a leaked key
+/// cannot lift the pause and trade straight away
+pub const TRADING_REENABLE_DELAY_SECONDS: u64 = 24 * 60 * 60;
+
+/// Gas reserved for handling the token's reply to a profit sweep transfer
+pub const PROFIT_SWEEP_REPLY_DEPOSIT: u64 = 5_000_000_000;
+
+pub struct AccountModule;
+
+impl AccountModule {
+    /// Replace the caller's settings; the position and margin modes are kept, they change only
+    /// through their own setters
+    pub fn set_settings(caller: ActorId, settings: AccountSettings) -> Result<(), Error> {
+        let to_validate = [
+            settings.default_max_slippage_bps,
+            settings.default_take_profit_bps,
+            settings.default_stop_loss_bps,
+        ];
+        if to_validate.into_iter().flatten().any(|bps| bps == 0 || bps >= 10_000) {
+            return Err(Error::InvalidParameter);
+        }
+
+        let mut st = PerpetualDEXState::get_mut();
+        if let Some(auto) = &settings.auto_withdraw
+            && !st.collateral_tokens.contains_key(&auto.token)
+        {
+            return Err(Error::UnknownCollateralToken);
+        }
+        let current = st.account_settings.get(&caller).cloned().unwrap_or_default();
+        st.account_settings.insert(
+            caller,
+            AccountSettings {
+                position_mode: current.position_mode,
+                margin_mode: current.margin_mode,
+                ..settings
+            },
+        );
+        Ok(())
+    }
+
+    /// Enable (`Some`) or disable profit sweeps for the caller
+    pub fn set_auto_withdraw(caller: ActorId, auto_withdraw: Option<AutoWithdraw>) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if let Some(auto) = &auto_withdraw
+            && !st.collateral_tokens.contains_key(&auto.token)
+        {
+            return Err(Error::UnknownCollateralToken);
+        }
+        st.account_settings.entry(caller).or_default().auto_withdraw = auto_withdraw;
+        Ok(())
+    }
+
+    /// Post a funding notice whenever one settlement charges at least `threshold_usd`, or stop
+    /// doing so with `None`
+    pub fn set_funding_alert_threshold(caller: ActorId, threshold_usd: Option<Usd>) -> Result<(), Error> {
+        if threshold_usd == Some(0) {
+            return Err(Error::InvalidParameter);
+        }
+        PerpetualDEXState::get_mut().account_settings.entry(caller).or_default().funding_alert_threshold_usd =
+            threshold_usd;
+        Ok(())
+    }
+
+    /// Queue a sweep of `realized_pnl` if the account opted in and the profit reaches its minimum.
+    /// Queued in state so a rolled-back close also drops its sweep.
+    pub fn queue_profit_sweep(st: &mut PerpetualDEXState, account: ActorId, realized_pnl: i128) {
+        let Some(auto) = st.account_settings.get(&account).and_then(|s| s.auto_withdraw.clone()) else {
+            return;
+        };
+        if realized_pnl <= 0 || (realized_pnl as u128) < auto.min_profit_usd {
+            return;
+        }
+        st.pending_profit_sweeps.push((account, auto.token, realized_pnl as u128));
+    }
+
+    /// Send queued profit sweeps as token transfers, debiting the internal balance (never more
+    /// than it holds). A transfer that cannot be sent leaves the balance untouched; one the token
+    /// rejects is credited back when its reply arrives.
+    pub fn flush_profit_sweeps() {
+        let sweeps = core::mem::take(&mut PerpetualDEXState::get_mut().pending_profit_sweeps);
+        for (account, token, amount_usd) in sweeps {
+            let mut st = PerpetualDEXState::get_mut();
+            let st = &mut *st;
+            let Some(decimals) = st.collateral_tokens.get(&token).map(|t| t.decimals) else {
+                continue;
+            };
+            let balance = st.balances.get(&account).copied().unwrap_or(0);
+            let Some(token_amount) = utils::usd_to_token_amount(amount_usd.min(balance), decimals) else {
+                continue;
+            };
+            // Debit what the rounded-down token amount is worth
+            let Some(debit) = utils::token_amount_to_usd(token_amount, decimals).filter(|d| *d > 0) else {
+                continue;
+            };
+            let payload = ("Vft", "Transfer", account, U256::from(token_amount)).encode();
+            let sent = msg::send_bytes_for_reply(token, payload, 0, PROFIT_SWEEP_REPLY_DEPOSIT)
+                .and_then(|f| f.handle_reply(move || Self::on_sweep_reply(account, token, debit, token_amount)));
+            if sent.is_err() {
+                continue;
+            }
+            if let Some(bal) = st.balances.get_mut(&account) {
+                *bal = bal.saturating_sub(debit);
+            }
+        }
+    }
+
+    /// Reply to a sweep transfer: post the notice if the token moved, else credit the debit back
+    fn on_sweep_reply(account: ActorId, token: ActorId, debit: Usd, token_amount: u128) {
+        let transferred = msg::reply_code().is_ok_and(|code| code.is_success())
+            && msg::load_bytes()
+                .ok()
+                .and_then(|reply| <(String, String, bool)>::decode(&mut reply.as_slice()).ok())
+                .is_some_and(|(_, _, ok)| ok);
+
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        if !transferred {
+            let bal = st.balances.entry(account).or_insert(0);
+            *bal = bal.saturating_add(debit);
+            return;
+        }
+        NotificationModule::push(
+            &mut st.notifications,
+            account,
+            NoticeKind::ProfitWithdrawn {
+                token,
+                amount_usd: debit,
+                token_amount,
+            },
+        );
+    }
+
+    pub fn set_position_mode(caller: ActorId, mode: PositionMode) {
+        let mut st = PerpetualDEXState::get_mut();
+        st.account_settings.entry(caller).or_default().position_mode = mode;
+    }
+
+    /// Switch margin mode; only without open positions, since the switch would change how
+    /// existing positions are margined and liquidated
+    pub fn set_margin_mode(caller: ActorId, mode: MarginMode) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        let current = st.account_settings.get(&caller).map(|s| s.margin_mode).unwrap_or_default();
+        if current != mode && st.account_positions.get(&caller).is_some_and(|p| !p.is_empty()) {
+            return Err(Error::AccountHasOpenPositions);
+        }
+        st.account_settings.entry(caller).or_default().margin_mode = mode;
+        Ok(())
+    }
+
+    /// Stop the caller from opening or increasing positions; closes and decreases stay allowed
+    pub fn disable_trading(caller: ActorId) {
+        let mut st = PerpetualDEXState::get_mut();
+        st.trading_disabled_until.insert(caller, u64::MAX);
+    }
+
+    /// Lift the caller's pause after `TRADING_REENABLE_DELAY_SECONDS`; returns when opening resumes
+    pub fn enable_trading(caller: ActorId) -> u64 {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        let Some(until) = st.trading_disabled_until.get(&caller).copied().filter(|until| *until > now) else {
+            st.trading_disabled_until.remove(&caller);
+            return now;
+        };
+        let until = until.min(now.saturating_add(TRADING_REENABLE_DELAY_SECONDS));
+        st.trading_disabled_until.insert(caller, until);
+        until
+    }
+
+    /// When the account may open positions again (`None` = not paused)
+    pub fn trading_disabled_until(account: ActorId) -> Option<u64> {
+        let st = PerpetualDEXState::get();
+        st.trading_disabled_until.get(&account).copied().filter(|until| *until > exec::block_timestamp())
+    }
+
+    pub fn settings(account: ActorId) -> AccountSettings {
+        let st = PerpetualDEXState::get();
+        st.account_settings.get(&account).cloned().unwrap_or_default()
+    }
+
+    pub fn position_mode(account: ActorId) -> PositionMode {
+        let st = PerpetualDEXState::get();
+        st.account_settings.get(&account).map(|s| s.position_mode).unwrap_or_default()
+    }
+
+    pub fn default_max_slippage_bps(account: ActorId) -> Option<u16> {
+        let st = PerpetualDEXState::get();
+        st.account_settings.get(&account).and_then(|s| s.default_max_slippage_bps)
+    }
+
+    pub fn default_receiver(account: ActorId) -> Option<ActorId> {
+        let st = PerpetualDEXState::get();
+        st.account_settings.get(&account).and_then(|s| s.default_receiver)
+    }
+}
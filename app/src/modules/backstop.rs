@@ -0,0 +1,214 @@
+use crate::{PerpetualDEXState, errors::Error, modules::oracle::OracleModule, types::*};
+use sails_rs::prelude::*;
+
+/// Fixed-point scale of `BackstopPool::fee_per_share`
+pub const BACKSTOP_FEE_SCALE: u128 = 1_000_000_000_000;
+
+/// Junior/senior LP structure: each market's backstop tranche pays trader profits the main
+/// pool can no longer cover and takes a premium share of trading fees for that risk.
+pub struct BackstopModule;
+
+impl BackstopModule {
+    /// Deposit into the market's backstop tranche; returns the shares minted
+    pub fn deposit(
+        lp: ActorId,
+        market_id: String,
+        long_token_amount: u128,
+        short_token_amount: u128,
+        min_shares: u128,
+    ) -> Result<u128, Error> {
+        let added_value = {
+            let st = PerpetualDEXState::get();
+            st.ensure_not_withdraw_only()?;
+            let market = st.markets.get(&market_id).ok_or(Error::MarketNotFound)?;
+            st.ensure_market_allows(&market_id, true)?;
+            if st.market_configs.get(&market_id).is_some_and(|c| c.lp_whitelist_enabled)
+                && !st.lp_whitelists.get(&market_id).is_some_and(|l| l.contains(&lp))
+            {
+                return Err(Error::LpNotWhitelisted);
+            }
+
+            let long_price = OracleModule::mid(&market.long_token)?;
+            let short_price = OracleModule::mid(&market.short_token)?;
+            (long_token_amount.saturating_mul(long_price) / USD_SCALE)
+                .saturating_add(short_token_amount.saturating_mul(short_price) / USD_SCALE)
+        };
+
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        let pool = st.backstop_pools.entry(market_id.clone()).or_default();
+        let shares = if pool.total_shares == 0 {
+            added_value
+        } else {
+            // A tranche fully drawn down has shares but no value left to price new ones against
+            if pool.liquidity_usd == 0 {
+                return Err(Error::InsufficientLiquidity);
+            }
+            pool.total_shares.saturating_mul(added_value) / pool.liquidity_usd
+        };
+        if shares == 0 || shares < min_shares {
+            return Err(Error::SlippageExceeded);
+        }
+
+        let stake = st.backstop_stakes.entry((market_id, lp)).or_default();
+        Self::sync_fees(pool, stake);
+        stake.shares = stake.shares.saturating_add(shares);
+        pool.total_shares = pool.total_shares.saturating_add(shares);
+        pool.liquidity_usd = pool.liquidity_usd.saturating_add(added_value);
+        Ok(shares)
+    }
+
+    /// Burn backstop shares for their pro-rata liquidity, split between the market's long and
+    /// short tokens by price. Blocked while the main pool is empty or utilized above
+    /// `backstop_withdraw_max_utilization_bps`, so the junior tranche cannot leave just before
+    /// it has to pay. Accrued fees stay claimable.
+    pub fn withdraw(
+        lp: ActorId,
+        market_id: String,
+        shares: u128,
+        min_long_out: u128,
+        min_short_out: u128,
+    ) -> Result<(u128, u128), Error> {
+        let (long_price, short_price) = {
+            let st = PerpetualDEXState::get();
+            let market = st.markets.get(&market_id).ok_or(Error::MarketNotFound)?;
+            let max_utilization_bps =
+                st.market_configs.get(&market_id).map_or(0, |c| c.backstop_withdraw_max_utilization_bps);
+            let Some(pool) = st.pool_amounts.get(&market_id).filter(|p| p.liquidity_usd > 0) else {
+                return Err(Error::InsufficientLiquidity);
+            };
+            if max_utilization_bps > 0 && Self::utilization_bps(pool) > max_utilization_bps as u128 {
+                return Err(Error::InsufficientLiquidity);
+            }
+            (OracleModule::mid(&market.long_token)?, OracleModule::mid(&market.short_token)?)
+        };
+        let price_sum = long_price.saturating_add(short_price);
+        if price_sum == 0 || long_price == 0 || short_price == 0 {
+            return Err(Error::InvalidPrice);
+        }
+
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        let pool = st.backstop_pools.get_mut(&market_id).ok_or(Error::InsufficientBackstopShares)?;
+        let stake = st
+            .backstop_stakes
+            .get_mut(&(market_id, lp))
+            .ok_or(Error::InsufficientBackstopShares)?;
+        if shares == 0 || stake.shares < shares {
+            return Err(Error::InsufficientBackstopShares);
+        }
+
+        let value_usd = pool.liquidity_usd.saturating_mul(shares) / pool.total_shares;
+        let long_usd = value_usd.saturating_mul(long_price) / price_sum;
+        let short_usd = value_usd.saturating_sub(long_usd);
+        let long_out = long_usd.saturating_mul(USD_SCALE) / long_price;
+        let short_out = short_usd.saturating_mul(USD_SCALE) / short_price;
+        if long_out < min_long_out || short_out < min_short_out {
+            return Err(Error::SlippageExceeded);
+        }
+
+        Self::sync_fees(pool, stake);
+        stake.shares -= shares;
+        pool.total_shares = pool.total_shares.saturating_sub(shares);
+        pool.liquidity_usd = pool.liquidity_usd.saturating_sub(value_usd);
+        Ok((long_out, short_out))
+    }
+
+    /// Open interest over the main pool's liquidity (bps); saturates for an empty pool
+    fn utilization_bps(pool: &PoolAmounts) -> u128 {
+        let oi = pool.long_oi_usd.saturating_add(pool.short_oi_usd);
+        match pool.liquidity_usd {
+            0 => u128::MAX,
+            liquidity => oi.saturating_mul(10_000) / liquidity,
+        }
+    }
+
+    /// Move the caller's accrued backstop fees into their balance; returns the amount
+    pub fn claim_fees(lp: ActorId, market_id: String) -> Result<Usd, Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        let pool = st.backstop_pools.get(&market_id).ok_or(Error::MarketNotFound)?;
+        let stake = st
+            .backstop_stakes
+            .get_mut(&(market_id, lp))
+            .ok_or(Error::InsufficientBackstopShares)?;
+        Self::sync_fees(pool, stake);
+        let amount = core::mem::take(&mut stake.pending_fee_usd);
+
+        let bal = st.balances.entry(lp).or_insert(0);
+        *bal = bal.saturating_add(amount);
+        Ok(amount)
+    }
+
+    /// Pay `amount` of trader profit (or uncovered loss) for `market`: the main pool first, the
+    /// backstop tranche only for what the main pool cannot cover
+    pub fn draw_liquidity(st: &mut PerpetualDEXState, market: &str, amount: Usd) {
+        let Some(pool) = st.pool_amounts.get_mut(market) else { return };
+        let from_main = amount.min(pool.liquidity_usd);
+        pool.liquidity_usd -= from_main;
+
+        let rest = amount - from_main;
+        if rest > 0
+            && let Some(backstop) = st.backstop_pools.get_mut(market)
+        {
+            let from_backstop = rest.min(backstop.liquidity_usd);
+            backstop.liquidity_usd -= from_backstop;
+            backstop.absorbed_usd = backstop.absorbed_usd.saturating_add(from_backstop);
+        }
+    }
+
+    /// Route the market's backstop share of a trading fee to the tranche's holders.
+    /// Returns the part taken; the caller credits the rest to the main pool.
+    pub fn take_fee_share(st: &mut PerpetualDEXState, market: &str, fee: Usd) -> Usd {
+        let share_bps = st.market_configs.get(market).map_or(0, |c| c.backstop_fee_share_bps);
+        let Some(pool) = st.backstop_pools.get_mut(market) else { return 0 };
+        if share_bps == 0 || pool.total_shares == 0 || pool.liquidity_usd == 0 {
+            return 0;
+        }
+        let share = fee.saturating_mul(share_bps as u128) / 10_000;
+        let per_share = share.saturating_mul(BACKSTOP_FEE_SCALE) / pool.total_shares;
+        pool.fee_per_share = pool.fee_per_share.saturating_add(per_share);
+        // Rounding dust below one unit per share stays with the main pool
+        per_share.saturating_mul(pool.total_shares) / BACKSTOP_FEE_SCALE
+    }
+
+    fn sync_fees(pool: &BackstopPool, stake: &mut BackstopStake) {
+        let delta = pool.fee_per_share.saturating_sub(stake.fee_per_share_paid);
+        let earned = stake.shares.saturating_mul(delta) / BACKSTOP_FEE_SCALE;
+        stake.pending_fee_usd = stake.pending_fee_usd.saturating_add(earned);
+        stake.fee_per_share_paid = pool.fee_per_share;
+    }
+
+    pub fn get_pool(market_id: &str) -> BackstopPool {
+        PerpetualDEXState::get().backstop_pools.get(market_id).cloned().unwrap_or_default()
+    }
+
+    /// Provider stake with fees synced up to now
+    pub fn get_stake(market_id: String, lp: ActorId) -> BackstopStake {
+        let st = PerpetualDEXState::get();
+        let mut stake = st.backstop_stakes.get(&(market_id.clone(), lp)).cloned().unwrap_or_default();
+        if let Some(pool) = st.backstop_pools.get(&market_id) {
+            Self::sync_fees(pool, &mut stake);
+        }
+        stake
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utilization_bps() {
+        let pool = |liquidity_usd, long_oi_usd, short_oi_usd| PoolAmounts {
+            liquidity_usd,
+            long_oi_usd,
+            short_oi_usd,
+            ..Default::default()
+        };
+        assert_eq!(BackstopModule::utilization_bps(&pool(1_000, 300, 500)), 8_000);
+        assert_eq!(BackstopModule::utilization_bps(&pool(1_000, 0, 0)), 0);
+        assert_eq!(BackstopModule::utilization_bps(&pool(1_000, 1_500, 0)), 15_000);
+        assert_eq!(BackstopModule::utilization_bps(&pool(0, 0, 0)), u128::MAX);
+    }
+}
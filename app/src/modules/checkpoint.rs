@@ -0,0 +1,103 @@
+use crate::{PerpetualDEXState, errors::Error, types::*, utils};
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Checkpoints kept in state (most recent)
+pub const CHECKPOINT_LOG_SIZE: usize = 24;
+
+/// Periodic compact state summaries, attested on-chain by the admin and keepers
+pub struct CheckpointModule;
+
+impl CheckpointModule {
+    pub fn set_interval(caller: ActorId, interval_seconds: u64) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.checkpoint_interval_seconds = interval_seconds;
+        Ok(())
+    }
+
+    /// Compute and store a new checkpoint (keepers/admin/the program, once per interval)
+    pub fn publish(caller: ActorId) -> Result<StateCheckpoint, Error> {
+        let now = exec::block_timestamp();
+        let block = exec::block_height();
+        let mut st = PerpetualDEXState::get_mut();
+        if caller != exec::program_id() && !st.is_keeper(caller) && !st.is_admin(caller) {
+            return Err(Error::NotKeeper);
+        }
+        if let Some(last) = st.checkpoints.back()
+            && now < last.published_at.saturating_add(st.checkpoint_interval_seconds)
+        {
+            return Err(Error::EpochNotElapsed);
+        }
+
+        let (mut liquidity, mut long_oi, mut short_oi) = (0u128, 0u128, 0u128);
+        for pool in st.pool_amounts.values() {
+            liquidity = liquidity.saturating_add(pool.liquidity_usd);
+            long_oi = long_oi.saturating_add(pool.long_oi_usd);
+            short_oi = short_oi.saturating_add(pool.short_oi_usd);
+        }
+        let version = st.checkpoints.back().map_or(1, |c| c.version + 1);
+        let market_count = st.markets.len() as u32;
+        let insurance = st.insurance_fund_usd;
+
+        let checkpoint = StateCheckpoint {
+            version,
+            block,
+            published_at: now,
+            total_liquidity_usd: liquidity,
+            total_long_oi_usd: long_oi,
+            total_short_oi_usd: short_oi,
+            insurance_fund_usd: insurance,
+            market_count,
+            hash: utils::checkpoint_hash(version, block, now, [liquidity, long_oi, short_oi, insurance], market_count),
+            signers: Vec::new(),
+        };
+        if st.checkpoints.len() >= CHECKPOINT_LOG_SIZE {
+            st.checkpoints.pop_front();
+        }
+        st.checkpoints.push_back(checkpoint.clone());
+        Ok(checkpoint)
+    }
+
+    /// Attest a stored checkpoint. The caller must be the admin or a keeper and must pass
+    /// the hash it verified, so a signature never covers a checkpoint it did not check.
+    pub fn sign(caller: ActorId, version: u64, hash: H256) -> Result<StateCheckpoint, Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_keeper(caller) && !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        let checkpoint = st
+            .checkpoints
+            .iter_mut()
+            .find(|c| c.version == version)
+            .ok_or(Error::CheckpointNotFound)?;
+        if checkpoint.hash != hash {
+            return Err(Error::InvalidParameter);
+        }
+        if checkpoint.signers.contains(&caller) {
+            return Err(Error::AlreadySigned);
+        }
+        checkpoint.signers.push(caller);
+        Ok(checkpoint.clone())
+    }
+
+    pub fn latest() -> Option<StateCheckpoint> {
+        PerpetualDEXState::get().checkpoints.back().cloned()
+    }
+
+    pub fn get(version: u64) -> Result<StateCheckpoint, Error> {
+        let st = PerpetualDEXState::get();
+        st.checkpoints
+            .iter()
+            .find(|c| c.version == version)
+            .cloned()
+            .ok_or(Error::CheckpointNotFound)
+    }
+
+    /// Stored checkpoints, most recent first
+    pub fn recent(limit: u32) -> Vec<StateCheckpoint> {
+        let st = PerpetualDEXState::get();
+        st.checkpoints.iter().rev().take(limit as usize).cloned().collect()
+    }
+}
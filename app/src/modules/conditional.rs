@@ -0,0 +1,212 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{
+        notification::NotificationModule, oracle::OracleModule, position::PositionModule, risk::RiskModule,
+        trading::TradingModule,
+    },
+    types::*,
+    utils,
+};
+use core::ops::Bound;
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Upper bound on conditional closes resting on one position
+pub const MAX_CONDITIONAL_CLOSES_PER_POSITION: usize = 10;
+
+/// Conditional closes triggered by position metrics (unrealized PnL, leverage) rather than a
+/// price: keepers evaluate them and market-close the configured share of the position
+pub struct ConditionalCloseModule;
+
+impl ConditionalCloseModule {
+    pub fn create(
+        caller: ActorId,
+        position_key: PositionKey,
+        condition: PositionCondition,
+        close_bps: u16,
+        max_slippage_bps: u16,
+    ) -> Result<ConditionalClose, Error> {
+        if close_bps == 0 || close_bps > 10_000 || max_slippage_bps >= 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        if condition == PositionCondition::LeverageAtLeast(0) {
+            return Err(Error::InvalidParameter);
+        }
+        let mut st = PerpetualDEXState::get_mut();
+        let pos = st.positions.get(&position_key).ok_or(Error::PositionNotFound)?;
+        if pos.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        if st
+            .conditional_closes
+            .get(&position_key)
+            .is_some_and(|closes| closes.len() >= MAX_CONDITIONAL_CLOSES_PER_POSITION)
+        {
+            return Err(Error::TooManyConditionalCloses);
+        }
+
+        let close = ConditionalClose {
+            key: st.generate_request_key(),
+            account: caller,
+            position_key,
+            condition,
+            close_bps,
+            max_slippage_bps,
+            created_at: exec::block_timestamp(),
+        };
+        st.conditional_closes.entry(position_key).or_default().push(close.clone());
+        Ok(close)
+    }
+
+    pub fn cancel(caller: ActorId, key: RequestKey) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        let close = st
+            .conditional_closes
+            .values()
+            .flatten()
+            .find(|c| c.key == key)
+            .ok_or(Error::OrderNotFound)?;
+        if close.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        let position_key = close.position_key;
+        Self::remove(&mut st, position_key, key);
+        Ok(())
+    }
+
+    pub fn account_closes(account: ActorId) -> Vec<ConditionalClose> {
+        let st = PerpetualDEXState::get();
+        let mut closes: Vec<ConditionalClose> =
+            st.conditional_closes.values().flatten().filter(|c| c.account == account).cloned().collect();
+        closes.sort_unstable_by_key(|c| c.created_at);
+        closes
+    }
+
+    /// Evaluate the closes of up to `limit` positions in key order, resuming after the previous
+    /// pass, and execute those whose condition holds (keepers/admin). Each close is atomic and
+    /// one-shot; one that fails (e.g. on slippage) is skipped and stays for a later pass.
+    pub fn execute(caller: ActorId, limit: u32) -> Result<Vec<ConditionalCloseExecuted>, Error> {
+        let now = exec::block_timestamp();
+        let batch: Vec<(PositionKey, Vec<ConditionalClose>)> = {
+            let mut st = PerpetualDEXState::get_mut();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            let range = match st.conditional_close_cursor {
+                Some(after) => st.conditional_closes.range((Bound::Excluded(after), Bound::Unbounded)),
+                None => st.conditional_closes.range(..),
+            };
+            let batch: Vec<_> = range.take(limit as usize).map(|(key, closes)| (*key, closes.clone())).collect();
+            st.conditional_close_cursor =
+                if batch.len() < limit as usize { None } else { batch.last().map(|(key, _)| *key) };
+            batch
+        };
+
+        // Prices and fees are read through shared borrows only, after the cursor update above
+        let mut due = Vec::new();
+        {
+            let st = PerpetualDEXState::get();
+            for (position_key, closes) in batch {
+                let Some(pos) = st.positions.get(&position_key) else { continue };
+                let (Some(cfg), Some(pool)) = (st.market_configs.get(&pos.market), st.pool_amounts.get(&pos.market))
+                else {
+                    continue;
+                };
+                let Ok(price) = OracleModule::mid(&utils::price_key(&pos.market)) else { continue };
+                let Ok(Some(effective)) = RiskModule::effective_collateral(pos, pool, cfg, price, now) else {
+                    continue;
+                };
+                for close in closes {
+                    if Self::holds(&close.condition, pos.size_usd, pos.collateral_usd, effective) {
+                        due.push((close, pos.is_long, price));
+                    }
+                }
+            }
+        }
+
+        let mut executed = Vec::new();
+        for (close, is_long, price) in due {
+            // An earlier close of the same position may have closed it already
+            let Ok(pos) = PositionModule::get_position(&close.position_key) else { continue };
+            // Closing a long sells, so the bound sits below the mid; closing a short sits above
+            let slippage = price.saturating_mul(close.max_slippage_bps as u128) / 10_000;
+            let acceptable_price = if is_long { price - slippage } else { price.saturating_add(slippage) };
+
+            // A market close saves no orders, so the position snapshot covers everything it writes
+            let snapshot = PerpetualDEXState::snapshot_position(&pos);
+            let result = TradingModule::close_position_percent(
+                close.account,
+                close.position_key,
+                close.close_bps,
+                acceptable_price,
+            );
+            let Ok(ExecutionResult::Executed { execution_price, .. }) = result else {
+                PerpetualDEXState::restore_position(snapshot);
+                continue;
+            };
+
+            let record = ConditionalCloseExecuted {
+                key: close.key,
+                account: close.account,
+                position_key: close.position_key,
+                condition: close.condition,
+                size_delta_usd: pos.size_usd.saturating_mul(close.close_bps as u128) / 10_000,
+                execution_price,
+            };
+            let mut st = PerpetualDEXState::get_mut();
+            Self::remove(&mut st, close.position_key, close.key);
+            NotificationModule::push(
+                &mut st.notifications,
+                close.account,
+                NoticeKind::ConditionalCloseExecuted(record.clone()),
+            );
+            executed.push(record);
+        }
+        Ok(executed)
+    }
+
+    fn remove(st: &mut PerpetualDEXState, position_key: PositionKey, key: RequestKey) {
+        let Some(closes) = st.conditional_closes.get_mut(&position_key) else { return };
+        closes.retain(|c| c.key != key);
+        if closes.is_empty() {
+            st.conditional_closes.remove(&position_key);
+        }
+    }
+
+    /// `effective` is collateral + PnL - pending fees, so PnL here is net of fees; a position with
+    /// no effective collateral left counts as infinitely levered
+    fn holds(condition: &PositionCondition, size_usd: Usd, collateral_usd: Usd, effective: i128) -> bool {
+        let pnl = effective.saturating_sub(collateral_usd as i128);
+        match condition {
+            PositionCondition::PnlAtLeast(threshold) => pnl >= *threshold,
+            PositionCondition::PnlAtMost(threshold) => pnl <= *threshold,
+            PositionCondition::LeverageAtLeast(bps) => {
+                effective <= 0 || size_usd.saturating_mul(10_000) / effective as u128 >= *bps as u128
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pnl_conditions_net_of_collateral() {
+        // 1_000 collateral, effective 1_250: +250 PnL after fees
+        assert!(ConditionalCloseModule::holds(&PositionCondition::PnlAtLeast(250), 10_000, 1_000, 1_250));
+        assert!(!ConditionalCloseModule::holds(&PositionCondition::PnlAtLeast(251), 10_000, 1_000, 1_250));
+        assert!(ConditionalCloseModule::holds(&PositionCondition::PnlAtMost(-100), 10_000, 1_000, 900));
+        assert!(!ConditionalCloseModule::holds(&PositionCondition::PnlAtMost(-101), 10_000, 1_000, 900));
+    }
+
+    #[test]
+    fn test_leverage_condition_on_effective_collateral() {
+        // 10_000 size over 500 effective collateral is 20x
+        assert!(ConditionalCloseModule::holds(&PositionCondition::LeverageAtLeast(200_000), 10_000, 1_000, 500));
+        assert!(!ConditionalCloseModule::holds(&PositionCondition::LeverageAtLeast(200_001), 10_000, 1_000, 500));
+        // Wiped-out collateral is infinitely levered
+        assert!(ConditionalCloseModule::holds(&PositionCondition::LeverageAtLeast(u32::MAX), 10_000, 1_000, 0));
+        assert!(ConditionalCloseModule::holds(&PositionCondition::LeverageAtLeast(u32::MAX), 10_000, 1_000, -5));
+    }
+}
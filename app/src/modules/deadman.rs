@@ -0,0 +1,85 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{notification::NotificationModule, orderbook::OrderBookModule, trading::TradingModule},
+    types::*,
+};
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Dead man's switch for bots: accounts that opt in must call `heartbeat` within their
+/// interval, otherwise keepers cancel every resting order they have
+pub struct DeadManSwitchModule;
+
+impl DeadManSwitchModule {
+    /// Arm (or re-arm with a new interval) the caller's switch; counts as a heartbeat
+    pub fn enable(caller: ActorId, interval_seconds: u64) -> Result<DeadManSwitch, Error> {
+        if interval_seconds == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let switch = DeadManSwitch {
+            interval_seconds,
+            last_heartbeat: exec::block_timestamp(),
+        };
+        PerpetualDEXState::get_mut().dead_man_switches.insert(caller, switch.clone());
+        Ok(switch)
+    }
+
+    pub fn disable(caller: ActorId) {
+        PerpetualDEXState::get_mut().dead_man_switches.remove(&caller);
+    }
+
+    /// Returns the new deadline
+    pub fn heartbeat(caller: ActorId) -> Result<u64, Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        let switch = st.dead_man_switches.get_mut(&caller).ok_or(Error::DeadManSwitchNotEnabled)?;
+        switch.last_heartbeat = now;
+        Ok(now.saturating_add(switch.interval_seconds))
+    }
+
+    pub fn get(account: ActorId) -> Option<DeadManSwitch> {
+        PerpetualDEXState::get().dead_man_switches.get(&account).cloned()
+    }
+
+    /// Cancel the saved and order-book orders of up to `limit` accounts past their heartbeat
+    /// deadline, longest overdue first (keepers/admin). Each tripped switch is disarmed; the
+    /// owner re-enables it once back online.
+    pub fn trigger(caller: ActorId, limit: u32) -> Result<Vec<DeadManSwitchTrigger>, Error> {
+        let now = exec::block_timestamp();
+        let mut overdue: Vec<(u64, ActorId, u64)> = {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            st.dead_man_switches
+                .iter()
+                .map(|(account, s)| (s.last_heartbeat.saturating_add(s.interval_seconds), *account, s.last_heartbeat))
+                .filter(|(deadline, ..)| *deadline < now)
+                .collect()
+        };
+        overdue.sort_unstable();
+        overdue.truncate(limit as usize);
+
+        let mut triggered = Vec::with_capacity(overdue.len());
+        for (_, account, last_heartbeat) in overdue {
+            let cancelled_orders = TradingModule::cancel_all_orders(account, None);
+            let cancelled_book_orders = OrderBookModule::cancel_account_orders(account);
+            let trigger = DeadManSwitchTrigger {
+                account,
+                last_heartbeat,
+                cancelled_orders,
+                cancelled_book_orders,
+            };
+
+            let mut st = PerpetualDEXState::get_mut();
+            st.dead_man_switches.remove(&account);
+            NotificationModule::push(
+                &mut st.notifications,
+                account,
+                NoticeKind::DeadManSwitchTriggered(trigger.clone()),
+            );
+            triggered.push(trigger);
+        }
+        Ok(triggered)
+    }
+}
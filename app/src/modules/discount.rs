@@ -0,0 +1,84 @@
+use crate::{PerpetualDEXState, errors::Error, types::*};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Trading-fee discount tiers backed by balances in an external program.
+///
+/// Balances are fetched with a cross-program query and cached per account; fee
+/// charging only reads the cache, so an account without a fresh entry pays full fees.
+pub struct FeeDiscountModule;
+
+impl FeeDiscountModule {
+    pub fn set_config(caller: ActorId, mut cfg: FeeDiscountConfig) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if cfg.tiers.iter().any(|t| t.discount_bps > 10_000) {
+            return Err(Error::InvalidParameter);
+        }
+        cfg.tiers.sort_by_key(|t| t.min_balance);
+        st.fee_discount_config = cfg;
+        // Tiers may have changed; cached discounts must be re-derived
+        st.fee_discounts.clear();
+        Ok(())
+    }
+
+    /// Query the external program for `account`'s balance and cache the resulting tier.
+    pub async fn refresh(account: ActorId) -> Result<CachedDiscount, Error> {
+        let cfg = PerpetualDEXState::get().fee_discount_config.clone();
+        let program = cfg.program.ok_or(Error::FeeDiscountDisabled)?;
+
+        let payload = (cfg.service.clone(), cfg.method.clone(), account).encode();
+        let reply = msg::send_bytes_for_reply(program, payload, 0, cfg.reply_deposit)
+            .map_err(|_| Error::ExternalQueryFailed)?
+            .await
+            .map_err(|_| Error::ExternalQueryFailed)?;
+        let (_, _, balance) =
+            <(String, String, U256)>::decode(&mut reply.as_slice()).map_err(|_| Error::ExternalQueryFailed)?;
+
+        let cached = CachedDiscount {
+            balance,
+            discount_bps: Self::tier_for(&cfg.tiers, balance),
+            fetched_at: exec::block_timestamp(),
+        };
+        let mut st = PerpetualDEXState::get_mut();
+        // Config may have been replaced while awaiting the reply
+        if st.fee_discount_config.program == Some(program) {
+            st.fee_discounts.insert(account, cached.clone());
+        }
+        Ok(cached)
+    }
+
+    fn tier_for(tiers: &[DiscountTier], balance: U256) -> u16 {
+        tiers
+            .iter()
+            .rev()
+            .find(|t| balance >= t.min_balance)
+            .map_or(0, |t| t.discount_bps)
+    }
+
+    /// Cached discount for `account`, 0 if missing or expired
+    pub fn discount_bps(st: &PerpetualDEXState, account: ActorId, now: u64) -> u16 {
+        let ttl = st.fee_discount_config.cache_ttl_seconds;
+        st.fee_discounts
+            .get(&account)
+            .filter(|c| now < c.fetched_at.saturating_add(ttl))
+            .map_or(0, |c| c.discount_bps)
+    }
+
+    /// Trading fee on `size_usd` at `fee_bps`, less the account's cached discount
+    pub fn trading_fee(account: ActorId, size_usd: Usd, fee_bps: u128) -> Usd {
+        let fee = size_usd.saturating_mul(fee_bps) / 10_000;
+        let st = PerpetualDEXState::get();
+        let discount = Self::discount_bps(&st, account, exec::block_timestamp()) as u128;
+        fee.saturating_sub(fee.saturating_mul(discount) / 10_000)
+    }
+
+    pub fn cached(account: ActorId) -> Option<CachedDiscount> {
+        let st = PerpetualDEXState::get();
+        st.fee_discounts.get(&account).cloned()
+    }
+}
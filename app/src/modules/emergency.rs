@@ -0,0 +1,177 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{oracle::OracleModule, position::PositionModule, risk::RiskModule},
+    types::*,
+    utils,
+};
+use sails_rs::{collections::BTreeMap, gstd::exec, prelude::*};
+
+/// Delay between the admin proposing withdraw-only mode and it taking effect
+pub const WITHDRAW_ONLY_TIMELOCK_SECONDS: u64 = 86_400;
+
+/// Max distance (bps) between a proposed settlement price and the oracle mid, and between the
+/// price a position settles at and its market's proposed price
+pub const SETTLEMENT_PRICE_BAND_BPS: u128 = 1_000;
+
+/// Final safety state for catastrophic bugs. Trading, LP deposits and liquidations halt;
+/// owners can only close positions at banded settlement prices and withdraw balances.
+pub struct WithdrawOnlyModule;
+
+impl WithdrawOnlyModule {
+    /// Propose withdraw-only mode (admin only), optionally with reference settlement prices for
+    /// some markets. A proposed price must sit within `SETTLEMENT_PRICE_BAND_BPS` of the oracle
+    /// mid when the market has one. Takes effect after the timelock unless guardians approve it earlier.
+    pub fn propose(caller: ActorId, settlement_prices: Vec<(String, u128)>) -> Result<WithdrawOnlyState, Error> {
+        let now = exec::block_timestamp();
+        let settlement_prices: BTreeMap<String, u128> = settlement_prices.into_iter().collect();
+        for (market, price) in settlement_prices.iter() {
+            if *price == 0 {
+                return Err(Error::InvalidPrice);
+            }
+            if let Ok(mid) = OracleModule::mid(&utils::price_key(market))
+                && !Self::within_band(*price, mid)
+            {
+                return Err(Error::InvalidPrice);
+            }
+        }
+
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if st.is_withdraw_only() {
+            return Err(Error::WithdrawOnlyMode);
+        }
+        if settlement_prices.keys().any(|m| !st.markets.contains_key(m)) {
+            return Err(Error::MarketNotFound);
+        }
+
+        let state = WithdrawOnlyState {
+            proposed_at: now,
+            activates_at: now.saturating_add(WITHDRAW_ONLY_TIMELOCK_SECONDS),
+            settlement_prices,
+            approvals: Vec::new(),
+        };
+        st.withdraw_only = Some(state.clone());
+        Ok(state)
+    }
+
+    /// Withdraw a proposal before it takes effect (admin only).
+    pub fn cancel(caller: ActorId) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if st.is_withdraw_only() {
+            return Err(Error::WithdrawOnlyMode);
+        }
+        st.withdraw_only.take().map(|_| ()).ok_or(Error::NotInWithdrawOnlyMode)
+    }
+
+    /// Guardian approval of the pending proposal. Reaching the recovery threshold activates it
+    /// immediately. Returns true once active.
+    pub fn approve(caller: ActorId) -> Result<bool, Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        if !st.recovery_config.guardians.contains(&caller) {
+            return Err(Error::NotGuardian);
+        }
+        let threshold = st.recovery_config.threshold;
+        let pending = st.withdraw_only.as_mut().ok_or(Error::NotInWithdrawOnlyMode)?;
+        if pending.activates_at <= now {
+            return Ok(true);
+        }
+        if !pending.approvals.contains(&caller) {
+            pending.approvals.push(caller);
+        }
+        if threshold > 0 && pending.approvals.len() as u32 >= threshold {
+            pending.activates_at = now;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn status() -> Option<WithdrawOnlyState> {
+        PerpetualDEXState::get().withdraw_only.clone()
+    }
+
+    /// Close the caller's whole position at the market's settlement price: the oracle mid, held
+    /// within `SETTLEMENT_PRICE_BAND_BPS` of the proposed price when one was set. Markets without
+    /// a proposed price (including those listed during the timelock) settle at the mid.
+    pub fn close_position(caller: ActorId, key: PositionKey) -> Result<ExecutionResult, Error> {
+        let pos = PositionModule::get_position(&key)?;
+        if pos.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        let proposed = {
+            let st = PerpetualDEXState::get();
+            if !st.is_withdraw_only() {
+                return Err(Error::NotInWithdrawOnlyMode);
+            }
+            st.withdraw_only.as_ref().and_then(|w| w.settlement_prices.get(&pos.market).copied())
+        };
+        let mid = OracleModule::mid(&utils::price_key(&pos.market)).ok();
+        let price = Self::settlement_price(proposed, mid).ok_or(Error::PriceNotAvailable)?;
+
+        let snapshot = PerpetualDEXState::snapshot_position(&pos);
+        if let Err(e) = Self::apply_settlement_close(&pos, price) {
+            PerpetualDEXState::restore_position(snapshot);
+            return Err(e);
+        }
+
+        Ok(ExecutionResult::Executed {
+            position_key: key,
+            execution_price: price,
+        })
+    }
+
+    /// Oracle mid clamped to the band around the proposed price; either alone when the other is missing
+    fn settlement_price(proposed: Option<u128>, mid: Option<u128>) -> Option<u128> {
+        match (proposed, mid) {
+            (Some(proposed), Some(mid)) => {
+                let band = proposed.saturating_mul(SETTLEMENT_PRICE_BAND_BPS) / 10_000;
+                Some(mid.clamp(proposed.saturating_sub(band), proposed.saturating_add(band)))
+            }
+            (proposed, mid) => proposed.or(mid),
+        }
+    }
+
+    fn within_band(price: u128, mid: u128) -> bool {
+        price.abs_diff(mid).saturating_mul(10_000) <= mid.saturating_mul(SETTLEMENT_PRICE_BAND_BPS)
+    }
+
+    /// Only pool accrual and fee settlement can fail, before the close writes anything else
+    fn apply_settlement_close(pos: &Position, price: u128) -> Result<(), Error> {
+        RiskModule::accrue_pool(&pos.market, exec::block_timestamp())?;
+        PositionModule::decrease_position_at(pos.key, pos.account, pos.size_usd, pos.collateral_usd, price)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settlement_price() {
+        // Mid inside the band is used as is
+        assert_eq!(WithdrawOnlyModule::settlement_price(Some(1_000), Some(1_050)), Some(1_050));
+        // Mid outside the band is held at its edge
+        assert_eq!(WithdrawOnlyModule::settlement_price(Some(1_000), Some(2_000)), Some(1_100));
+        assert_eq!(WithdrawOnlyModule::settlement_price(Some(1_000), Some(10)), Some(900));
+        // Markets without a proposed price settle at the mid; no mid falls back to the proposal
+        assert_eq!(WithdrawOnlyModule::settlement_price(None, Some(1_234)), Some(1_234));
+        assert_eq!(WithdrawOnlyModule::settlement_price(Some(1_000), None), Some(1_000));
+        assert_eq!(WithdrawOnlyModule::settlement_price(None, None), None);
+    }
+
+    #[test]
+    fn test_within_band() {
+        assert!(WithdrawOnlyModule::within_band(1_100, 1_000));
+        assert!(WithdrawOnlyModule::within_band(900, 1_000));
+        assert!(!WithdrawOnlyModule::within_band(1_101, 1_000));
+        assert!(!WithdrawOnlyModule::within_band(899, 1_000));
+    }
+}
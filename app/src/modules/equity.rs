@@ -0,0 +1,110 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{oracle::OracleModule, position::PositionModule},
+    types::*,
+    utils,
+};
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Equity samples kept per account (most recent)
+pub const ACCOUNT_EQUITY_HISTORY_SIZE: usize = 200;
+
+/// Bounded per-account equity series, sampled on executions and by the keeper loop
+pub struct EquityModule;
+
+impl EquityModule {
+    pub fn set_interval(caller: ActorId, interval_seconds: u64) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.equity_sample_interval_seconds = interval_seconds;
+        Ok(())
+    }
+
+    /// Record the account's equity if its last sample is older than the interval.
+    /// Returns whether a sample was taken.
+    pub fn sample(account: ActorId) -> bool {
+        let now = exec::block_timestamp();
+        let sample = {
+            let st = PerpetualDEXState::get();
+            let due = st
+                .equity_history
+                .get(&account)
+                .and_then(|log| log.back())
+                .is_none_or(|last| now >= last.timestamp.saturating_add(st.equity_sample_interval_seconds));
+            if !due {
+                return false;
+            }
+            Self::current(&st, account, now)
+        };
+
+        let mut st = PerpetualDEXState::get_mut();
+        let log = st.equity_history.entry(account).or_default();
+        if log.len() >= ACCOUNT_EQUITY_HISTORY_SIZE {
+            log.pop_front();
+        }
+        log.push_back(sample);
+        true
+    }
+
+    /// Sample up to `limit` accounts with open positions that are due, in account order resuming
+    /// after where the previous call stopped; returns how many were sampled
+    pub fn sample_active(limit: u32) -> u32 {
+        let accounts: Vec<ActorId> = {
+            let mut st = PerpetualDEXState::get_mut();
+            let cursor = st.equity_sample_cursor;
+            let mut accounts: Vec<ActorId> = st
+                .account_positions
+                .iter()
+                .filter(|(account, keys)| !keys.is_empty() && cursor.is_none_or(|c| **account > c))
+                .map(|(account, _)| *account)
+                .collect();
+            accounts.sort_unstable();
+            accounts.truncate(limit as usize);
+            // A short batch reached the end; start over next time
+            st.equity_sample_cursor = if accounts.len() < limit as usize {
+                None
+            } else {
+                accounts.last().copied()
+            };
+            accounts
+        };
+        accounts.into_iter().filter(|a| Self::sample(*a)).count() as u32
+    }
+
+    /// Positions without a price count collateral only
+    fn current(st: &PerpetualDEXState, account: ActorId, now: u64) -> EquitySample {
+        let balance_usd = st.balances.get(&account).copied().unwrap_or(0);
+        let position_equity_usd = st
+            .account_positions
+            .get(&account)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| st.positions.get(key))
+            .map(|pos| {
+                let pnl = OracleModule::mid(&utils::price_key(&pos.market))
+                    .map(|price| PositionModule::calculate_pnl(pos, price))
+                    .unwrap_or(0);
+                (pos.collateral_usd as i128).saturating_add(pnl)
+            })
+            .fold(0i128, i128::saturating_add);
+        EquitySample {
+            timestamp: now,
+            block: exec::block_height(),
+            balance_usd,
+            position_equity_usd,
+            equity_usd: (balance_usd as i128).saturating_add(position_equity_usd),
+        }
+    }
+
+    /// Most recent first
+    pub fn history(account: ActorId, limit: u32) -> Vec<EquitySample> {
+        let st = PerpetualDEXState::get();
+        st.equity_history
+            .get(&account)
+            .map(|log| log.iter().rev().take(limit as usize).cloned().collect())
+            .unwrap_or_default()
+    }
+}
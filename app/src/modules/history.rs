@@ -0,0 +1,73 @@
+use crate::{PerpetualDEXState, types::*};
+use sails_rs::{collections::VecDeque, prelude::*};
+
+/// Liquidations kept per market (most recent)
+pub const MARKET_LIQUIDATION_LOG_SIZE: usize = 100;
+/// Liquidations kept per account (most recent)
+pub const ACCOUNT_LIQUIDATION_LOG_SIZE: usize = 20;
+
+/// Fills kept per account (most recent)
+pub const ACCOUNT_FILLS_SIZE: usize = 50;
+
+/// Bounded on-chain activity logs; oldest entries are dropped first
+pub struct HistoryModule;
+
+impl HistoryModule {
+    fn push_bounded<T>(log: &mut VecDeque<T>, item: T, cap: usize) {
+        if log.len() >= cap {
+            log.pop_front();
+        }
+        log.push_back(item);
+    }
+
+    pub fn record_liquidation(st: &mut PerpetualDEXState, record: LiquidationRecord) {
+        Self::push_bounded(
+            st.account_liquidations.entry(record.account).or_default(),
+            record.clone(),
+            ACCOUNT_LIQUIDATION_LOG_SIZE,
+        );
+        Self::push_bounded(
+            st.market_liquidations.entry(record.market.clone()).or_default(),
+            record,
+            MARKET_LIQUIDATION_LOG_SIZE,
+        );
+    }
+
+    pub fn record_fill(st: &mut PerpetualDEXState, account: ActorId, fill: FillRecord) {
+        Self::push_bounded(st.account_fills.entry(account).or_default(), fill, ACCOUNT_FILLS_SIZE);
+    }
+
+    /// Attach a trading fee charged after the fill was recorded (order book legs)
+    pub fn add_fee_to_last_fill(st: &mut PerpetualDEXState, account: ActorId, fee: Usd) {
+        if let Some(fill) = st.account_fills.get_mut(&account).and_then(|log| log.back_mut()) {
+            fill.trading_fee_usd = fill.trading_fee_usd.saturating_add(fee);
+        }
+    }
+
+    /// Most recent first
+    pub fn account_fills(account: ActorId, limit: u32) -> Vec<FillRecord> {
+        let st = PerpetualDEXState::get();
+        st.account_fills
+            .get(&account)
+            .map(|log| log.iter().rev().take(limit as usize).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Most recent first
+    pub fn market_liquidations(market: &str, limit: u32) -> Vec<LiquidationRecord> {
+        let st = PerpetualDEXState::get();
+        st.market_liquidations
+            .get(market)
+            .map(|log| log.iter().rev().take(limit as usize).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Most recent first
+    pub fn account_liquidations(account: ActorId, limit: u32) -> Vec<LiquidationRecord> {
+        let st = PerpetualDEXState::get();
+        st.account_liquidations
+            .get(&account)
+            .map(|log| log.iter().rev().take(limit as usize).cloned().collect())
+            .unwrap_or_default()
+    }
+}
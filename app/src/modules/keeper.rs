@@ -0,0 +1,644 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{
+        checkpoint::CheckpointModule, equity::EquityModule, notification::NotificationModule, oracle::OracleModule,
+        position::PositionModule, pricing::PricingModule, risk::RiskModule, trading::TradingModule,
+    },
+    types::*,
+    utils,
+};
+use core::ops::Bound;
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Keeper operations (funding accrual, order expiry, liquidations, margin calls, checkpoints,
+/// equity samples) and the self-scheduled loop that runs them from gas reservations when no
+/// external keeper is active.
+pub struct KeeperModule;
+
+impl KeeperModule {
+    /// Liquidate a position at the adverse side of the spread plus the market's liquidation
+    /// slippage. Returns the liquidation fee paid to `liquidator`.
+    pub fn liquidate(liquidator: ActorId, position_key: PositionKey) -> Result<u128, Error> {
+        let current_time = exec::block_timestamp();
+        PerpetualDEXState::get().ensure_not_withdraw_only()?;
+        Self::ensure_keeper_turn(liquidator)?;
+
+        let position = PositionModule::get_position(&position_key)?;
+
+        // CRITICAL: Accrue pool fees before checking liquidation
+        RiskModule::accrue_pool(&position.market, current_time)?;
+
+        // Check if liquidatable WITH pending fees (account-level for cross margin)
+        let (config, current_price) = {
+            let st = PerpetualDEXState::get();
+            let config = st.market_configs.get(&position.market).ok_or(Error::MarketNotFound)?.clone();
+            let current_price = Self::liquidation_price(&position, &config)?;
+            if !RiskModule::is_liquidatable_for_owner(&st, &position, current_price, current_time)? {
+                return Err(Error::PositionNotLiquidatable);
+            }
+            (config, current_price)
+        };
+
+        let (_, liquidation_fee) =
+            PositionModule::liquidate_position(liquidator, position_key, current_price, &config)?;
+        let side = if position.is_long { OrderSide::Long } else { OrderSide::Short };
+        PricingModule::record_block_flow(&position.market, &side, position.size_usd, false, 0);
+        if liquidator != exec::program_id() {
+            TradingModule::refund_liquidation_gas(position.account, liquidator);
+        }
+        Self::record_keeper_activity(liquidator);
+        Ok(liquidation_fee)
+    }
+
+    /// What `liquidate` would do right now, without mutating state: the pool is accrued and the
+    /// position's fees settled virtually, then the liquidation payout is worked out as in
+    /// `PositionModule::liquidate_position`. Fees above the collateral fail as the real call would.
+    pub fn simulate_liquidation(position_key: PositionKey) -> Result<LiquidationSimulation, Error> {
+        let now = exec::block_timestamp();
+        let st = PerpetualDEXState::get();
+        let pos = st.positions.get(&position_key).ok_or(Error::PositionNotFound)?;
+        let cfg = st.market_configs.get(&pos.market).ok_or(Error::MarketNotFound)?;
+        let pool = RiskModule::accrued_pool_virtual(&st, &pos.market, now)?;
+        let price = Self::liquidation_price(pos, cfg)?;
+
+        let mode = st.account_settings.get(&pos.account).map(|s| s.margin_mode).unwrap_or_default();
+        let liquidatable = if mode == MarginMode::Cross {
+            RiskModule::is_liquidatable_for_owner(&st, pos, price, now)?
+        } else {
+            RiskModule::is_liquidatable(pos, &pool, cfg, price, now)?
+        };
+
+        let (_, _, pending_fees_usd) = RiskModule::calculate_pending_fees_virtual(pos, &pool, cfg, now)?;
+        let collateral_usd = if pending_fees_usd >= 0 {
+            let fee = pending_fees_usd as u128;
+            if fee > pos.collateral_usd {
+                return Err(Error::InsufficientCollateral);
+            }
+            pos.collateral_usd - fee
+        } else {
+            pos.collateral_usd.saturating_add(pending_fees_usd.unsigned_abs())
+        };
+
+        let pnl = PositionModule::calculate_pnl(pos, price);
+        let liquidation_fee = cfg.liquidation_fee_usd(collateral_usd);
+        let remaining = collateral_usd.saturating_sub(liquidation_fee);
+        let (owner_payout_usd, shortfall_usd) = if pnl >= 0 {
+            (remaining.saturating_add(pnl as u128), 0)
+        } else {
+            let loss = pnl.unsigned_abs();
+            (remaining.saturating_sub(loss), loss.saturating_sub(remaining))
+        };
+
+        Ok(LiquidationSimulation {
+            position_key,
+            liquidatable,
+            price,
+            pending_fees_usd,
+            collateral_usd,
+            pnl,
+            liquidation_fee,
+            owner_payout_usd,
+            shortfall_usd,
+            insurance_draw_usd: shortfall_usd.min(st.insurance_fund_usd),
+        })
+    }
+
+    /// Current primary keeper and the end of its rotation slot
+    fn primary_keeper(st: &PerpetualDEXState, now: u64) -> Option<(ActorId, u64)> {
+        let cfg = &st.keeper_rotation;
+        if !cfg.enabled || cfg.interval_seconds == 0 || st.keepers.is_empty() {
+            return None;
+        }
+        let slot = now / cfg.interval_seconds;
+        let primary = st.keepers[(slot % st.keepers.len() as u64) as usize];
+        Some((primary, slot.saturating_add(1).saturating_mul(cfg.interval_seconds)))
+    }
+
+    /// Primary keeper has been silent past the backup delay
+    fn backups_active(st: &PerpetualDEXState, primary: ActorId, now: u64) -> bool {
+        let last_seen = st.keeper_activity.get(&primary).copied().unwrap_or(0);
+        now.saturating_sub(last_seen) >= st.keeper_rotation.backup_delay_seconds
+    }
+
+    /// Gate keeper work on the rotation. Non-keepers (owners executing their own orders,
+    /// permissionless liquidators) pass through.
+    pub fn ensure_keeper_turn(actor: ActorId) -> Result<(), Error> {
+        let now = exec::block_timestamp();
+        let st = PerpetualDEXState::get();
+        if !st.is_keeper(actor) {
+            return Ok(());
+        }
+        if let Some((primary, _)) = Self::primary_keeper(&st, now)
+            && primary != actor
+            && !Self::backups_active(&st, primary, now)
+        {
+            return Err(Error::NotPrimaryKeeper);
+        }
+        Ok(())
+    }
+
+    /// Record a keeper's successful execution; only this keeps backups from taking over its slot
+    pub fn record_keeper_activity(actor: ActorId) {
+        let mut st = PerpetualDEXState::get_mut();
+        if st.is_keeper(actor) {
+            st.keeper_activity.insert(actor, exec::block_timestamp());
+        }
+    }
+
+    /// Register a keeper (admin only); adding an existing keeper is a no-op
+    pub fn add_keeper(caller: ActorId, keeper: ActorId) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if !st.keepers.contains(&keeper) {
+            st.keepers.push(keeper);
+        }
+        Ok(())
+    }
+
+    /// Deregister a keeper (admin only); unknown keepers are ignored
+    pub fn remove_keeper(caller: ActorId, keeper: ActorId) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if let Some(i) = st.keepers.iter().position(|k| *k == keeper) {
+            st.keepers.swap_remove(i);
+        }
+        Ok(())
+    }
+
+    /// Configure primary-keeper rotation (admin only)
+    pub fn set_rotation(caller: ActorId, cfg: KeeperRotationConfig) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if cfg.enabled && cfg.interval_seconds == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        st.keeper_rotation = cfg;
+        Ok(())
+    }
+
+    /// Enable/disable the liquidation price audit and set its tolerance (admin only)
+    pub fn set_liquidation_audit(caller: ActorId, cfg: LiquidationAuditConfig) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if cfg.tolerance_bps > 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        st.liquidation_audit = cfg;
+        Ok(())
+    }
+
+    /// Recompute the liquidation price of up to `max_positions` positions in key order, resuming
+    /// after the previous pass, and report those whose cached price deviates beyond the
+    /// tolerance (keepers/admin, while the audit is enabled). Read-only for positions.
+    pub fn audit_liquidation_prices(caller: ActorId, max_positions: u32) -> Result<LiquidationAuditReport, Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_keeper(caller) && !st.is_admin(caller) {
+            return Err(Error::NotKeeper);
+        }
+        if !st.liquidation_audit.enabled {
+            return Err(Error::LiquidationAuditDisabled);
+        }
+        if max_positions == 0 {
+            return Err(Error::InvalidParameter);
+        }
+
+        let from = st.liquidation_audit_cursor.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut positions: Vec<&Position> =
+            st.positions.range((from, Bound::Unbounded)).map(|(_, p)| p).take(max_positions as usize + 1).collect();
+        let truncated = positions.len() > max_positions as usize;
+        positions.truncate(max_positions as usize);
+
+        let tolerance_bps = st.liquidation_audit.tolerance_bps as u128;
+        let mut mismatches = Vec::new();
+        for pos in &positions {
+            let Some(cfg) = st.market_configs.get(&pos.market) else { continue };
+            let recomputed = PositionModule::calculate_liquidation_price(pos, cfg.liquidation_threshold_bps);
+            let diff = pos.liquidation_price_usd.abs_diff(recomputed);
+            let deviation_bps = match recomputed {
+                0 if diff == 0 => 0,
+                0 => 10_000,
+                _ => diff.saturating_mul(10_000) / recomputed,
+            };
+            if deviation_bps > tolerance_bps {
+                mismatches.push(LiquidationPriceMismatch {
+                    position_key: pos.key,
+                    market: pos.market.clone(),
+                    cached_price: pos.liquidation_price_usd,
+                    recomputed_price: recomputed,
+                    deviation_bps: deviation_bps.min(u32::MAX as u128) as u32,
+                });
+            }
+        }
+
+        let report = LiquidationAuditReport {
+            checked: positions.len() as u32,
+            mismatches,
+            next_cursor: if truncated { positions.last().map(|p| p.key) } else { None },
+        };
+        st.liquidation_audit_cursor = report.next_cursor;
+        Ok(report)
+    }
+
+    pub fn liveness() -> KeeperLiveness {
+        let now = exec::block_timestamp();
+        let st = PerpetualDEXState::get();
+        let primary = Self::primary_keeper(&st, now);
+        KeeperLiveness {
+            rotation: st.keeper_rotation.clone(),
+            primary: primary.map(|(k, _)| k),
+            slot_ends_at: primary.map(|(_, end)| end),
+            backups_active: primary.is_none_or(|(k, _)| Self::backups_active(&st, k, now)),
+            keepers: st
+                .keepers
+                .iter()
+                .map(|k| KeeperActivity {
+                    keeper: *k,
+                    last_active_at: st.keeper_activity.get(k).copied(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Price a position is liquidated (and checked for liquidation) at
+    pub fn liquidation_price(position: &Position, cfg: &MarketConfig) -> Result<u128, Error> {
+        OracleModule::adverse_price(&utils::price_key(&position.market), position.is_long, cfg.liquidation_slippage_bps)
+    }
+
+    /// Owner-triggered liquidation of their own liquidatable position, for when keepers are down.
+    /// The liquidation fee goes to the insurance fund. Returns the fee.
+    pub fn self_liquidate(owner: ActorId, position_key: PositionKey) -> Result<u128, Error> {
+        let position = PositionModule::get_position(&position_key)?;
+        if position.account != owner {
+            return Err(Error::Unauthorized);
+        }
+
+        // Booked to the program's balance first, then moved to the insurance fund
+        let program = exec::program_id();
+        let fee = Self::liquidate(program, position_key)?;
+        let mut st = PerpetualDEXState::get_mut();
+        if let Some(bal) = st.balances.get_mut(&program) {
+            *bal = bal.saturating_sub(fee);
+        }
+        st.insurance_fund_usd = st.insurance_fund_usd.saturating_add(fee);
+        Ok(fee)
+    }
+
+    /// All positions that are liquidatable at their liquidation price (pending fees included)
+    pub fn liquidatable_positions() -> Vec<PositionKey> {
+        let st = PerpetualDEXState::get();
+        let current_time = exec::block_timestamp();
+
+        st.positions
+            .iter()
+            .filter(|(_, position)| Self::is_liquidatable_now(&st, position, current_time))
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Check up to `max_positions` positions in key order, resuming after the previous scan, and
+    /// return up to `max_found` liquidatable ones. The scan stops at the last one returned, so
+    /// positions past it are checked next time.
+    pub fn scan_liquidatable(max_positions: u32, max_found: u32) -> Vec<PositionKey> {
+        if max_found == 0 {
+            return Vec::new();
+        }
+        let now = exec::block_timestamp();
+        let (found, last, exhausted) = {
+            let st = PerpetualDEXState::get();
+            let from = st.liquidation_scan_cursor.map_or(Bound::Unbounded, Bound::Excluded);
+            let mut found = Vec::new();
+            let mut last = None;
+            let mut checked = 0;
+            for (key, position) in st.positions.range((from, Bound::Unbounded)).take(max_positions as usize) {
+                checked += 1;
+                last = Some(*key);
+                if Self::is_liquidatable_now(&st, position, now) {
+                    found.push(*key);
+                    if found.len() == max_found as usize {
+                        break;
+                    }
+                }
+            }
+            (found, last, checked < max_positions && found.len() < max_found as usize)
+        };
+        // A short batch reached the end; start over next time
+        PerpetualDEXState::get_mut().liquidation_scan_cursor = if exhausted { None } else { last };
+        found
+    }
+
+    fn is_liquidatable_now(st: &PerpetualDEXState, position: &Position, now: u64) -> bool {
+        let Some(cfg) = st.market_configs.get(&position.market) else {
+            return false;
+        };
+        let Ok(price) = Self::liquidation_price(position, cfg) else {
+            return false;
+        };
+        RiskModule::is_liquidatable_for_owner(st, position, price, now).unwrap_or(false)
+    }
+
+    /// Isolated-margin positions currently inside their market's margin-call band
+    pub fn margin_calls() -> Vec<MarginCall> {
+        let st = PerpetualDEXState::get();
+        let current_time = exec::block_timestamp();
+
+        st.positions
+            .values()
+            .filter_map(|position| Self::margin_call_for(&st, position, current_time))
+            .collect()
+    }
+
+    /// Margin call for an isolated-margin position, checked at its liquidation price so the
+    /// call fires before the position can be liquidated
+    fn margin_call_for(st: &PerpetualDEXState, position: &Position, now: u64) -> Option<MarginCall> {
+        if st.account_settings.get(&position.account).map(|s| s.margin_mode).unwrap_or_default()
+            != MarginMode::Isolated
+        {
+            return None;
+        }
+        let cfg = st.market_configs.get(&position.market)?;
+        let pool = st.pool_amounts.get(&position.market)?;
+        let price = Self::liquidation_price(position, cfg).ok()?;
+        RiskModule::margin_call(position, pool, cfg, price, now).ok().flatten()
+    }
+
+    /// Check up to `max_positions` positions in key order, resuming after the previous scan, and
+    /// return margin calls for those that entered the band since they were last checked. Checked
+    /// positions that left the band (and closed ones) are re-armed so a later entry notifies again.
+    pub fn scan_margin_calls(max_positions: u32) -> Vec<MarginCall> {
+        let now = exec::block_timestamp();
+        let checked: Vec<(PositionKey, Option<MarginCall>)> = {
+            let st = PerpetualDEXState::get();
+            let from = st.margin_call_cursor.map_or(Bound::Unbounded, Bound::Excluded);
+            st.positions
+                .range((from, Bound::Unbounded))
+                .take(max_positions as usize)
+                .map(|(key, position)| (*key, Self::margin_call_for(&st, position, now)))
+                .collect()
+        };
+
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        // A short batch reached the end; start over next time
+        st.margin_call_cursor = if checked.len() < max_positions as usize {
+            None
+        } else {
+            checked.last().map(|(key, _)| *key)
+        };
+        let positions = &st.positions;
+        st.margin_called.retain(|key| positions.contains_key(key));
+
+        let mut calls = Vec::new();
+        for (key, call) in checked {
+            let Some(call) = call else {
+                st.margin_called.remove(&key);
+                continue;
+            };
+            if st.margin_called.insert(key) {
+                NotificationModule::push(&mut st.notifications, call.account, NoticeKind::MarginCall(call.clone()));
+                calls.push(call);
+            }
+        }
+        calls
+    }
+
+    /// Settle funding and borrowing on up to `max_positions` of a market's positions in key
+    /// order, resuming after the previous pass (keepers/admin). Keeps claimable fee buckets and
+    /// collateral current for positions that rarely trade. A position whose fees exceed its
+    /// collateral is rolled back and reported for liquidation instead.
+    pub fn settle_market_fees(
+        caller: ActorId,
+        market: String,
+        max_positions: u32,
+    ) -> Result<MarketFeeSettlement, Error> {
+        let now = exec::block_timestamp();
+        let batch: Vec<Position> = {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            if !st.markets.contains_key(&market) {
+                return Err(Error::MarketNotFound);
+            }
+            if max_positions == 0 {
+                return Err(Error::InvalidParameter);
+            }
+            let from = st.fee_settlement_cursors.get(&market).copied().map_or(Bound::Unbounded, Bound::Excluded);
+            st.positions
+                .range((from, Bound::Unbounded))
+                .map(|(_, p)| p)
+                .filter(|p| p.market == market)
+                .take(max_positions as usize + 1)
+                .cloned()
+                .collect()
+        };
+        RiskModule::accrue_pool(&market, now)?;
+
+        let mut report = MarketFeeSettlement {
+            market: market.clone(),
+            settled: 0,
+            skipped: Vec::new(),
+            total_fees_usd: 0,
+            next_cursor: None,
+        };
+        for pos in batch.iter().take(max_positions as usize) {
+            let snapshot = PerpetualDEXState::snapshot_position(pos);
+            match PositionModule::settle_fees(&pos.key, now) {
+                Ok(fees) => {
+                    report.settled += 1;
+                    report.total_fees_usd = report.total_fees_usd.saturating_add(fees);
+                }
+                Err(_) => {
+                    PerpetualDEXState::restore_position(snapshot);
+                    report.skipped.push(pos.key);
+                }
+            }
+        }
+
+        if batch.len() > max_positions as usize {
+            report.next_cursor = batch.get(max_positions as usize - 1).map(|p| p.key);
+        }
+        let mut st = PerpetualDEXState::get_mut();
+        match report.next_cursor {
+            Some(cursor) => st.fee_settlement_cursors.insert(market, cursor),
+            None => st.fee_settlement_cursors.remove(&market),
+        };
+        Ok(report)
+    }
+
+    /// Run one keeper pass. Callable by keepers, the admin, or the program itself
+    /// (delayed message sent from a gas reservation); only self-sent runs reschedule.
+    pub fn run_scheduled(caller: ActorId) -> Result<ScheduledRunReport, Error> {
+        let program = exec::program_id();
+        let (cfg, markets, treasury) = {
+            let st = PerpetualDEXState::get();
+            if caller != program && !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            st.ensure_not_withdraw_only()?;
+            let markets: Vec<String> = st.market_configs.keys().cloned().collect();
+            (st.scheduler_config.clone(), markets, st.admin)
+        };
+        Self::ensure_keeper_turn(caller)?;
+        let now = exec::block_timestamp();
+        let mut report = ScheduledRunReport::default();
+
+        // 1. Funding accrual
+        for market in markets.iter() {
+            if RiskModule::accrue_pool(market, now).is_ok() {
+                report.accrued_markets += 1;
+            }
+        }
+
+        // 2. Order expiry
+        if cfg.order_ttl_seconds > 0 {
+            report.expired_orders = TradingModule::expire_orders(
+                now.saturating_sub(cfg.order_ttl_seconds),
+                cfg.max_order_expiry_checks_per_run,
+            );
+        }
+
+        // 3. Liquidation scan; protocol-run liquidation fees go to the admin treasury
+        let liquidator = if caller == program { treasury } else { caller };
+        for key in Self::scan_liquidatable(cfg.max_liquidation_checks_per_run, cfg.max_liquidations_per_run) {
+            if Self::liquidate(liquidator, key).is_ok() {
+                report.liquidated_positions += 1;
+            }
+        }
+
+        // 4. Margin-call scan
+        report.margin_calls = Self::scan_margin_calls(cfg.max_margin_call_checks_per_run);
+
+        // 5. State checkpoint, when the interval has elapsed
+        report.checkpoint_version = CheckpointModule::publish(caller).ok().map(|c| c.version);
+
+        // 6. Equity samples of accounts with open positions, a bounded batch per run
+        report.equity_samples = EquityModule::sample_active(cfg.max_equity_samples_per_run);
+
+        // Liquidations record themselves; a run that only expired orders counts as well
+        if report.expired_orders > 0 {
+            Self::record_keeper_activity(caller);
+        }
+        {
+            let mut st = PerpetualDEXState::get_mut();
+            st.last_scheduled_run = Some(exec::block_height());
+            if caller == program {
+                st.next_scheduled_run = None;
+            }
+        }
+        if caller == program && cfg.enabled {
+            // Out of reservations just stops the loop; the run itself succeeded
+            Self::schedule_next().ok();
+        }
+
+        Ok(report)
+    }
+
+    /// Queue the next run as a delayed self-message paid from the first live reservation.
+    pub fn schedule_next() -> Result<u32, Error> {
+        let now_block = exec::block_height();
+        let mut st = PerpetualDEXState::get_mut();
+        if let Some(next) = st.next_scheduled_run {
+            return Ok(next);
+        }
+        let delay = st.scheduler_config.interval_blocks;
+
+        // Expired reservations were already returned by the runtime
+        st.gas_reservations.retain(|r| r.expires_at_block > now_block);
+        let idx = st
+            .gas_reservations
+            .iter()
+            .position(|r| r.expires_at_block > now_block.saturating_add(delay))
+            .ok_or(Error::NoGasReservation)?;
+        let reservation = st.gas_reservations.remove(idx);
+
+        let payload = ("Executor", "RunScheduledTasks").encode();
+        msg::send_bytes_delayed_from_reservation(reservation.id, exec::program_id(), payload, 0, delay)
+            .map_err(|_| Error::GasReservationFailed)?;
+
+        let next = now_block.saturating_add(delay);
+        st.next_scheduled_run = Some(next);
+        Ok(next)
+    }
+
+    /// Reserve gas from the current message for future scheduled runs (admin only)
+    pub fn reserve_gas(caller: ActorId, amount: u64, duration_blocks: u32) -> Result<GasReservationInfo, Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if amount == 0 || duration_blocks == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let id = exec::reserve_gas(amount, duration_blocks).map_err(|_| Error::GasReservationFailed)?;
+        let now_block = exec::block_height();
+        let reservation = GasReservation {
+            id,
+            amount,
+            reserved_at_block: now_block,
+            expires_at_block: now_block.saturating_add(duration_blocks),
+        };
+        let info = Self::reservation_info(&reservation);
+        st.gas_reservations.push(reservation);
+        Ok(info)
+    }
+
+    /// Update scheduler settings; enabling it queues the first run immediately (admin only)
+    pub fn set_config(caller: ActorId, cfg: SchedulerConfig) -> Result<(), Error> {
+        {
+            let mut st = PerpetualDEXState::get_mut();
+            if !st.is_admin(caller) {
+                return Err(Error::Unauthorized);
+            }
+            if cfg.interval_blocks == 0
+                || cfg.max_margin_call_checks_per_run == 0
+                || cfg.max_liquidation_checks_per_run == 0
+                || cfg.max_order_expiry_checks_per_run == 0
+            {
+                return Err(Error::InvalidParameter);
+            }
+            st.scheduler_config = cfg.clone();
+        }
+        if cfg.enabled {
+            Self::schedule_next()?;
+        }
+        Ok(())
+    }
+
+    fn reservation_info(r: &GasReservation) -> GasReservationInfo {
+        GasReservationInfo {
+            amount: r.amount,
+            reserved_at_block: r.reserved_at_block,
+            expires_at_block: r.expires_at_block,
+        }
+    }
+
+    pub fn status() -> SchedulerStatus {
+        let st = PerpetualDEXState::get();
+        let now_block = exec::block_height();
+        let reservations: Vec<GasReservationInfo> = st
+            .gas_reservations
+            .iter()
+            .filter(|r| r.expires_at_block > now_block)
+            .map(Self::reservation_info)
+            .collect();
+        SchedulerStatus {
+            config: st.scheduler_config.clone(),
+            total_reserved_gas: reservations.iter().map(|r| r.amount).sum(),
+            reservations,
+            next_run_block: st.next_scheduled_run,
+            last_run_block: st.last_scheduled_run,
+        }
+    }
+}
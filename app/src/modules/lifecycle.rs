@@ -0,0 +1,306 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{oracle::OracleModule, position::PositionModule, risk::RiskModule, trading::TradingModule},
+    types::*,
+    utils,
+};
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Seconds after the delisting deadline during which keepers sample the settlement price
+pub const SETTLEMENT_WINDOW_SECONDS: u64 = 3_600;
+
+/// Market delisting: Listed → ReduceOnly(deadline) → Settling → Delisted.
+/// Reduce-only markets reject increases and LP deposits. Once the deadline passes, keepers sample
+/// the oracle mid for `SETTLEMENT_WINDOW_SECONDS` and positions close at the resulting TWAP (or at
+/// an admin-attested price if no sample was taken): traders queue settlement closes that keepers
+/// execute in FIFO order, and keepers settle whatever remains.
+pub struct LifecycleModule;
+
+impl LifecycleModule {
+    /// Announce a delisting: the market turns reduce-only now and settles at `deadline` (admin only).
+    pub fn announce_delisting(caller: ActorId, market_id: String, deadline: u64) -> Result<(), Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if !st.markets.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+        if st.market_stage(&market_id) != MarketStage::Listed || deadline <= now {
+            return Err(Error::InvalidParameter);
+        }
+        st.market_stages.insert(market_id, MarketStage::ReduceOnly { deadline });
+        Ok(())
+    }
+
+    /// Withdraw a delisting announcement before its deadline (admin only).
+    pub fn cancel_delisting(caller: ActorId, market_id: String) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if !matches!(st.market_stage(&market_id), MarketStage::ReduceOnly { .. }) {
+            return Err(Error::InvalidParameter);
+        }
+        st.market_stages.remove(&market_id);
+        Ok(())
+    }
+
+    /// Close up to `max_positions` remaining positions of a settling market at its settlement price
+    /// (keepers/admin). Queued positions are left to the queue, and while it is not empty only
+    /// positions at a loss are settled, so no profit is paid ahead of a queued request.
+    /// Each close is rolled back on its own if it fails. Returns the number settled.
+    pub fn settle_positions(caller: ActorId, market_id: String, max_positions: u32) -> Result<u32, Error> {
+        {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            if st.market_stage(&market_id) != MarketStage::Settling {
+                return Err(Error::InvalidParameter);
+            }
+        }
+        let price = Self::settlement_price(&market_id)?;
+        let keys: Vec<PositionKey> = {
+            let st = PerpetualDEXState::get();
+            let queue = st.settlement_queues.get(&market_id);
+            let queued = |key: &PositionKey| queue.is_some_and(|q| q.iter().any(|c| &c.position_key == key));
+            let queue_empty = queue.is_none_or(|q| q.is_empty());
+            st.positions
+                .iter()
+                .filter(|(k, p)| p.market == market_id && !queued(k))
+                .filter(|(_, p)| queue_empty || PositionModule::calculate_pnl(p, price) <= 0)
+                .map(|(k, _)| *k)
+                .take(max_positions as usize)
+                .collect()
+        };
+
+        let mut settled = 0;
+        for key in keys {
+            let pos = PositionModule::get_position(&key)?;
+            let snapshot = PerpetualDEXState::snapshot_position(&pos);
+            match Self::apply_settlement_close(&pos, price) {
+                Ok(()) => settled += 1,
+                Err(_) => PerpetualDEXState::restore_position(snapshot),
+            }
+        }
+        Ok(settled)
+    }
+
+    /// End of a settling market's sampling window
+    fn settlement_window_end(st: &PerpetualDEXState, market_id: &str) -> Option<u64> {
+        match st.market_stages.get(market_id) {
+            Some(MarketStage::ReduceOnly { deadline }) => Some(deadline.saturating_add(SETTLEMENT_WINDOW_SECONDS)),
+            _ => None,
+        }
+    }
+
+    /// Settlement price of a settling market. The first call after the sampling window fixes it
+    /// from the sampled TWAP; without samples the admin has to attest one.
+    fn settlement_price(market_id: &str) -> Result<u128, Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        if let Some(price) = st.settlement_prices.get(market_id) {
+            return Ok(*price);
+        }
+        let end = Self::settlement_window_end(&st, market_id).ok_or(Error::InvalidParameter)?;
+        if now < end {
+            return Err(Error::SettlementPriceNotFixed);
+        }
+        let twap = st.settlement_twaps.remove(market_id).ok_or(Error::SettlementPriceNotFixed)?;
+        let price = twap.average(end);
+        st.settlement_prices.insert(market_id.to_string(), price);
+        Ok(price)
+    }
+
+    /// Sample the fresh oracle mid of a settling market into its settlement TWAP while the
+    /// sampling window is open (keepers/admin)
+    pub fn sample_settlement_price(caller: ActorId, market_id: String) -> Result<(), Error> {
+        let now = exec::block_timestamp();
+        let token = utils::price_key(&market_id);
+        {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            if st.market_stage(&market_id) != MarketStage::Settling || st.settlement_prices.contains_key(&market_id) {
+                return Err(Error::InvalidParameter);
+            }
+            if Self::settlement_window_end(&st, &market_id).is_none_or(|end| now > end) {
+                return Err(Error::InvalidParameter);
+            }
+        }
+        OracleModule::ensure_fresh(&token)?;
+        let price = OracleModule::mid(&token)?;
+        PerpetualDEXState::get_mut()
+            .settlement_twaps
+            .entry(market_id)
+            .or_default()
+            .sample(price, now);
+        Ok(())
+    }
+
+    /// Fix the settlement price of a settling market whose sampling window passed without a
+    /// single sample, e.g. through an oracle outage (admin only)
+    pub fn attest_settlement_price(caller: ActorId, market_id: String, price: u128) -> Result<(), Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if price == 0 {
+            return Err(Error::InvalidPrice);
+        }
+        if st.market_stage(&market_id) != MarketStage::Settling
+            || st.settlement_prices.contains_key(&market_id)
+            || st.settlement_twaps.contains_key(&market_id)
+            || Self::settlement_window_end(&st, &market_id).is_none_or(|end| now < end)
+        {
+            return Err(Error::InvalidParameter);
+        }
+        st.settlement_prices.insert(market_id, price);
+        Ok(())
+    }
+
+    /// Queue a close of the caller's position in a settling market, to execute at the settlement price
+    pub fn submit_settlement_close(caller: ActorId, position_key: PositionKey) -> Result<(), Error> {
+        let pos = PositionModule::get_position(&position_key)?;
+        if pos.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        let mut st = PerpetualDEXState::get_mut();
+        if st.market_stage(&pos.market) != MarketStage::Settling {
+            return Err(Error::InvalidParameter);
+        }
+        let queue = st.settlement_queues.entry(pos.market).or_default();
+        if queue.iter().any(|c| c.position_key == position_key) {
+            return Err(Error::SettlementCloseQueued);
+        }
+        queue.push_back(SettlementClose {
+            position_key,
+            account: caller,
+            submitted_at: exec::block_timestamp(),
+        });
+        Ok(())
+    }
+
+    /// Work through up to `max_closes` queued settlement closes in FIFO order at the settlement
+    /// price (keepers/admin). A close whose profit the pool cannot pay stays queued, and so does
+    /// every later profitable close, so none jumps the queue; closes at a loss need no liquidity
+    /// and still execute past it. Requests for positions that are gone or fail to close are
+    /// dropped. Returns the number executed.
+    pub fn process_settlement_closes(caller: ActorId, market_id: String, max_closes: u32) -> Result<u32, Error> {
+        {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            if st.market_stage(&market_id) != MarketStage::Settling {
+                return Err(Error::InvalidParameter);
+            }
+        }
+        let price = Self::settlement_price(&market_id)?;
+
+        let mut executed = 0;
+        let mut index = 0;
+        let mut blocked = false;
+        for _ in 0..max_closes {
+            let next = {
+                let st = PerpetualDEXState::get();
+                let Some(request) = st.settlement_queues.get(&market_id).and_then(|q| q.get(index)) else {
+                    break;
+                };
+                let pos = st.positions.get(&request.position_key).cloned();
+                let liquidity = st.pool_amounts.get(&market_id).map_or(0, |p| p.liquidity_usd);
+                (pos, liquidity)
+            };
+            if let (Some(pos), liquidity) = next {
+                let pnl = PositionModule::calculate_pnl(&pos, price);
+                if pnl > 0 && (blocked || (pnl as u128) > liquidity) {
+                    blocked = true;
+                    index += 1;
+                    continue;
+                }
+                let snapshot = PerpetualDEXState::snapshot_position(&pos);
+                match Self::apply_settlement_close(&pos, price) {
+                    Ok(()) => executed += 1,
+                    Err(_) => PerpetualDEXState::restore_position(snapshot),
+                }
+            }
+            if let Some(queue) = PerpetualDEXState::get_mut().settlement_queues.get_mut(&market_id) {
+                queue.remove(index);
+            }
+        }
+        Ok(executed)
+    }
+
+    /// Pending settlement closes of a market, in execution order
+    pub fn settlement_queue(market_id: &str) -> Vec<SettlementClose> {
+        let st = PerpetualDEXState::get();
+        st.settlement_queues
+            .get(market_id)
+            .map(|q| q.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_settlement_price(market_id: &str) -> Option<u128> {
+        PerpetualDEXState::get().settlement_prices.get(market_id).copied()
+    }
+
+    /// Full close at `price`. Only pool accrual and fee settlement can fail, before the close
+    /// writes anything else, so `PerpetualDEXState::snapshot_position` covers a rollback.
+    fn apply_settlement_close(pos: &Position, price: u128) -> Result<(), Error> {
+        RiskModule::accrue_pool(&pos.market, exec::block_timestamp())?;
+        PositionModule::decrease_position_at(pos.key, pos.account, pos.size_usd, pos.collateral_usd, price)?;
+        Ok(())
+    }
+
+    /// Mark a settled market delisted once no positions remain, cancelling its pending orders
+    /// with their native fees refunded (keepers/admin). Returns the number of orders cancelled.
+    pub fn finalize_delisting(caller: ActorId, market_id: String) -> Result<u32, Error> {
+        {
+            let mut st = PerpetualDEXState::get_mut();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            if st.market_stage(&market_id) != MarketStage::Settling {
+                return Err(Error::InvalidParameter);
+            }
+            if st.positions.values().any(|p| p.market == market_id) {
+                return Err(Error::MarketHasOpenPositions);
+            }
+            st.market_stages.insert(market_id.clone(), MarketStage::Delisted);
+            st.settlement_queues.remove(&market_id);
+            st.settlement_twaps.remove(&market_id);
+        }
+        Ok(TradingModule::cancel_market_orders(&market_id))
+    }
+
+    pub fn stage(market_id: &str) -> Result<MarketStage, Error> {
+        let st = PerpetualDEXState::get();
+        if !st.markets.contains_key(market_id) {
+            return Err(Error::MarketNotFound);
+        }
+        Ok(st.market_stage(market_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settlement_twap() {
+        let mut twap = SettlementTwap::default();
+        twap.sample(100, 1_000);
+        assert_eq!(twap.average(1_000), 100);
+        twap.sample(200, 1_100);
+        // 100 for 100s, then 200 held for 300s until the window end
+        assert_eq!(twap.average(1_400), 175);
+        // Samples at the same time only keep the last price
+        twap.sample(300, 1_100);
+        assert_eq!(twap.average(1_200), 200);
+    }
+}
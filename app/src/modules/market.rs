@@ -1,5 +1,5 @@
-use crate::{PerpetualDEXState, errors::Error, modules::oracle::OracleModule, types::*};
-use sails_rs::prelude::*;
+use crate::{PerpetualDEXState, errors::Error, modules::oracle::OracleModule, types::*, utils};
+use sails_rs::{gstd::exec, prelude::*};
 
 pub struct MarketModule;
 
@@ -13,6 +13,7 @@ impl MarketModule {
         short_token: String,
         market_token: ActorId,
         config: MarketConfig,
+        metadata: MarketMetadata,
     ) -> Result<(), Error> {
         let mut st = PerpetualDEXState::get_mut();
 
@@ -22,6 +23,7 @@ impl MarketModule {
         if st.markets.contains_key(&market_id) {
             return Err(Error::MarketAlreadyExists);
         }
+        config.validate()?;
 
         let market = Market {
             market_token,
@@ -32,12 +34,74 @@ impl MarketModule {
 
         st.markets.insert(market_id.clone(), market);
         st.market_configs.insert(market_id.clone(), config);
+        st.market_metadata.insert(market_id.clone(), metadata);
         st.pool_amounts.insert(market_id.clone(), PoolAmounts::default());
         st.market_tokens.insert(market_id, MarketTokenInfo::default());
         Ok(())
     }
 
-    /// Update market configuration (admin only).
+    /// Create a market whose config is a copy of the named template (admin only).
+    pub fn create_market_from_template(
+        caller: ActorId,
+        market_id: String,
+        index_token: String,
+        long_token: String,
+        short_token: String,
+        market_token: ActorId,
+        template: &str,
+        metadata: MarketMetadata,
+    ) -> Result<(), Error> {
+        let mut config = Self::get_market_template(template)?;
+        config.market_id = market_id.clone();
+        Self::create_market(
+            caller, market_id, index_token, long_token, short_token, market_token, config, metadata,
+        )
+    }
+
+    /// Add or replace a named config template (admin only). Markets already created from it
+    /// are not affected.
+    pub fn set_market_template(caller: ActorId, name: String, config: MarketConfig) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if name.is_empty() {
+            return Err(Error::InvalidParameter);
+        }
+        config.validate()?;
+
+        st.market_templates.insert(name, config);
+        Ok(())
+    }
+
+    /// Delete a config template (admin only).
+    pub fn remove_market_template(caller: ActorId, name: String) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.market_templates
+            .remove(&name)
+            .map(|_| ())
+            .ok_or(Error::TemplateNotFound)
+    }
+
+    pub fn get_market_template(name: &str) -> Result<MarketConfig, Error> {
+        let st = PerpetualDEXState::get();
+        st.market_templates.get(name).cloned().ok_or(Error::TemplateNotFound)
+    }
+
+    /// Template names, sorted
+    pub fn market_template_names() -> Vec<String> {
+        let st = PerpetualDEXState::get();
+        let mut names: Vec<String> = st.market_templates.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Replace the market configuration (admin only); the new config must pass validation.
     pub fn set_market_config(caller: ActorId, market_id: String, config: MarketConfig) -> Result<(), Error> {
         let mut st = PerpetualDEXState::get_mut();
 
@@ -47,11 +111,271 @@ impl MarketModule {
         if !st.markets.contains_key(&market_id) {
             return Err(Error::MarketNotFound);
         }
+        config.validate()?;
+
+        Self::install_config(&mut st, market_id, config);
+        Ok(())
+    }
+
+    /// Apply the fields set in `patch` on top of the market's normal config; the merged
+    /// config is validated as a whole and installed only if every field passes.
+    pub fn update_market_config(caller: ActorId, market_id: String, patch: MarketConfigPatch) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        let mut config = match st.market_bootstraps.get(&market_id) {
+            Some(bootstrap) => bootstrap.normal_config.clone(),
+            None => st.market_configs.get(&market_id).cloned().ok_or(Error::MarketNotFound)?,
+        };
+
+        config.apply_patch(patch);
+        config.validate()?;
+        Self::install_config(&mut st, market_id, config);
+        Ok(())
+    }
+
+    /// Make `config` the market's normal config. During a bootstrap phase it is stored as the
+    /// post-bootstrap config and the market keeps running on its tightened version.
+    fn install_config(st: &mut PerpetualDEXState, market_id: String, config: MarketConfig) {
+        let active = match st.market_bootstraps.get_mut(&market_id) {
+            Some(bootstrap) => {
+                bootstrap.normal_config = config.clone();
+                Self::bootstrap_config(&config, &bootstrap.params)
+            }
+            None => config,
+        };
+        st.market_configs.insert(market_id, active);
+    }
+
+    /// `cfg` with the bootstrap leverage/OI caps and fee multiplier applied
+    fn bootstrap_config(cfg: &MarketConfig, params: &BootstrapParams) -> MarketConfig {
+        let scale_fee = |bps: u16| (bps as u64 * params.fee_multiplier_bps as u64 / 10_000).min(10_000) as u16;
+        let mut restricted = cfg.clone();
+        restricted.max_leverage = cfg.max_leverage.min(params.max_leverage);
+        restricted.max_long_oi = cfg.max_long_oi.min(params.max_long_oi);
+        restricted.max_short_oi = cfg.max_short_oi.min(params.max_short_oi);
+        restricted.trading_fee_bps = scale_fee(cfg.trading_fee_bps);
+        restricted.keeper_order_fee_bps = scale_fee(cfg.keeper_order_fee_bps);
+        restricted.maker_fee_bps = scale_fee(cfg.maker_fee_bps);
+        restricted.taker_fee_bps = scale_fee(cfg.taker_fee_bps);
+        restricted
+    }
+
+    /// Put a market into its bootstrap phase (admin only). The current config becomes the
+    /// normal config restored once TVL and time thresholds are both met.
+    pub fn start_bootstrap(caller: ActorId, market_id: String, params: BootstrapParams) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if params.max_leverage == 0 || params.fee_multiplier_bps < 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        if st.market_bootstraps.contains_key(&market_id) {
+            return Err(Error::InvalidParameter);
+        }
+        let normal_config = st.market_configs.get(&market_id).cloned().ok_or(Error::MarketNotFound)?;
+
+        st.market_bootstraps.insert(
+            market_id.clone(),
+            MarketBootstrap {
+                params,
+                normal_config: normal_config.clone(),
+                started_at: exec::block_timestamp(),
+            },
+        );
+        Self::install_config(&mut st, market_id, normal_config);
+        Ok(())
+    }
+
+    /// End a bootstrap phase early and restore the normal config (admin only).
+    pub fn end_bootstrap(caller: ActorId, market_id: String) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        let bootstrap = st.market_bootstraps.remove(&market_id).ok_or(Error::InvalidParameter)?;
+        st.market_configs.insert(market_id, bootstrap.normal_config);
+        Ok(())
+    }
+
+    /// Bootstrap phase has met both its TVL and time thresholds
+    fn bootstrap_complete(st: &PerpetualDEXState, market_id: &str, bootstrap: &MarketBootstrap, now: u64) -> bool {
+        let tvl = st.pool_amounts.get(market_id).map(|p| p.liquidity_usd).unwrap_or(0);
+        tvl >= bootstrap.params.min_tvl_usd
+            && now >= bootstrap.started_at.saturating_add(bootstrap.params.min_duration_seconds)
+    }
+
+    pub fn get_bootstrap(market_id: &str) -> Option<MarketBootstrap> {
+        PerpetualDEXState::get().market_bootstraps.get(market_id).cloned()
+    }
+
+    /// Announce a config update that takes effect at `effective_at` (admin only).
+    /// Replaces any update already scheduled for the market.
+    pub fn schedule_market_config(
+        caller: ActorId,
+        market_id: String,
+        config: MarketConfig,
+        effective_at: u64,
+    ) -> Result<(), Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if !st.markets.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+        if effective_at <= now {
+            return Err(Error::InvalidParameter);
+        }
+        config.validate()?;
+
+        st.scheduled_market_configs.insert(
+            market_id,
+            ScheduledMarketConfig {
+                config,
+                effective_at,
+                scheduled_at: now,
+            },
+        );
+        Ok(())
+    }
+
+    /// Drop a scheduled config update before it takes effect (admin only).
+    pub fn cancel_scheduled_market_config(caller: ActorId, market_id: String) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.scheduled_market_configs
+            .remove(&market_id)
+            .map(|_| ())
+            .ok_or(Error::InvalidParameter)
+    }
 
-        st.market_configs.insert(market_id, config);
+    /// Apply the market's scheduled config if it is due, and end its bootstrap phase once
+    /// complete. Called lazily from trading and accrual paths so the switch happens on the
+    /// first interaction after `effective_at` / the bootstrap thresholds.
+    pub fn activate_scheduled_config(market_id: &str) {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        if st
+            .scheduled_market_configs
+            .get(market_id)
+            .is_some_and(|s| s.effective_at <= now)
+            && let Some(scheduled) = st.scheduled_market_configs.remove(market_id)
+        {
+            Self::install_config(&mut st, String::from(market_id), scheduled.config);
+        }
+
+        let complete = st
+            .market_bootstraps
+            .get(market_id)
+            .is_some_and(|b| Self::bootstrap_complete(&st, market_id, b, now));
+        if complete && let Some(bootstrap) = st.market_bootstraps.remove(market_id) {
+            st.market_configs.insert(String::from(market_id), bootstrap.normal_config);
+        }
+    }
+
+    /// Config in force now (a due scheduled update counts as active) and any pending update.
+    pub fn config_schedule(market_id: &str) -> Result<MarketConfigSchedule, Error> {
+        Self::config_schedule_in(&PerpetualDEXState::get(), market_id, exec::block_timestamp())
+    }
+
+    /// `config_schedule` against an already borrowed state
+    pub fn config_schedule_in(
+        st: &PerpetualDEXState,
+        market_id: &str,
+        now: u64,
+    ) -> Result<MarketConfigSchedule, Error> {
+        let current = st.market_configs.get(market_id).cloned().ok_or(Error::MarketNotFound)?;
+        Ok(match st.scheduled_market_configs.get(market_id).cloned() {
+            Some(scheduled) if scheduled.effective_at <= now => MarketConfigSchedule {
+                active: match st.market_bootstraps.get(market_id) {
+                    Some(bootstrap) => Self::bootstrap_config(&scheduled.config, &bootstrap.params),
+                    None => scheduled.config,
+                },
+                pending: None,
+            },
+            pending => MarketConfigSchedule {
+                active: current,
+                pending,
+            },
+        })
+    }
+
+    /// Update market display metadata (admin only).
+    pub fn set_market_metadata(caller: ActorId, market_id: String, metadata: MarketMetadata) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if !st.markets.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+
+        st.market_metadata.insert(market_id, metadata);
+        Ok(())
+    }
+
+    /// Approve or revoke an LP for a permissioned market (admin only).
+    /// Revoking does not affect liquidity already provided.
+    pub fn set_lp_whitelisted(caller: ActorId, market_id: String, lp: ActorId, allowed: bool) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if !st.markets.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+
+        let list = st.lp_whitelists.entry(market_id).or_default();
+        if allowed {
+            list.insert(lp);
+        } else {
+            list.remove(&lp);
+        }
         Ok(())
     }
 
+    pub fn get_lp_whitelist(market_id: &str) -> Vec<ActorId> {
+        let st = PerpetualDEXState::get();
+        st.lp_whitelists
+            .get(market_id)
+            .map(|l| l.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Markets with their display metadata, sorted by id and starting after `after`,
+    /// cut to `limits` with a continuation cursor.
+    pub fn get_all_markets(after: Option<String>, limits: ViewLimits) -> MarketListingPage {
+        let st = PerpetualDEXState::get();
+        let mut ids: Vec<&String> = st
+            .markets
+            .keys()
+            .filter(|id| after.as_ref().is_none_or(|a| *id > a))
+            .collect();
+        ids.sort();
+
+        let listings = ids.into_iter().map(|id| MarketListing {
+            market_id: id.clone(),
+            market: st.markets[id].clone(),
+            metadata: st.market_metadata.get(id).cloned().unwrap_or_default(),
+        });
+        let (markets, truncated) = utils::take_within_limits(listings, &limits);
+        let next_cursor = if truncated { markets.last().map(|m| m.market_id.clone()) } else { None };
+        MarketListingPage { markets, next_cursor }
+    }
+
     /// Add liquidity (LP deposits tokens → converted to USD, LP tokens minted).
     /// Funds from LPs go ONLY into `liquidity_usd`.
     pub fn add_liquidity(
@@ -63,10 +387,18 @@ impl MarketModule {
     ) -> Result<u128, Error> {
         let (long_price, short_price, pool_liq_snapshot, total_supply_snapshot) = {
             let st = PerpetualDEXState::get();
+            st.ensure_not_withdraw_only()?;
 
             if !st.markets.contains_key(&market_id) {
                 return Err(Error::MarketNotFound);
             }
+            st.ensure_market_allows(&market_id, true)?;
+
+            if st.market_configs.get(&market_id).is_some_and(|c| c.lp_whitelist_enabled)
+                && !st.lp_whitelists.get(&market_id).is_some_and(|l| l.contains(&lp))
+            {
+                return Err(Error::LpNotWhitelisted);
+            }
 
             let market = st.markets.get(&market_id).unwrap();
 
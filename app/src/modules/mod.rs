@@ -2,7 +2,25 @@
 
 pub mod oracle;
 pub mod market;
+pub mod backstop;
 pub mod position;
 pub mod pricing;
 pub mod risk;
 pub mod trading;
+pub mod orderbook;
+pub mod account;
+pub mod solvency;
+pub mod checkpoint;
+pub mod keeper;
+pub mod topup;
+pub mod conditional;
+pub mod deadman;
+pub mod stats;
+pub mod history;
+pub mod notification;
+pub mod equity;
+pub mod recovery;
+pub mod discount;
+pub mod referral;
+pub mod emergency;
+pub mod lifecycle;
@@ -0,0 +1,108 @@
+use crate::{PerpetualDEXState, types::*};
+use sails_rs::{collections::HashMap, gstd::exec, prelude::*};
+
+/// Unacknowledged notices kept per account (most recent)
+pub const ACCOUNT_INBOX_SIZE: usize = 50;
+
+/// Age after which an unacknowledged notice is dropped
+pub const NOTICE_TTL_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// Per-account inbox of recent notices, polled by frontends and cleared by acknowledgement.
+/// Each inbox is capped at `ACCOUNT_INBOX_SIZE` and notices expire after `NOTICE_TTL_SECONDS`.
+pub struct NotificationModule;
+
+impl NotificationModule {
+    pub fn push(inboxes: &mut HashMap<ActorId, NoticeInbox>, account: ActorId, kind: NoticeKind) {
+        let now = exec::block_timestamp();
+        let inbox = inboxes.entry(account).or_default();
+        Self::prune(inbox, now);
+        if inbox.notices.len() >= ACCOUNT_INBOX_SIZE {
+            inbox.notices.pop_front();
+        }
+        inbox.next_id += 1;
+        inbox.notices.push_back(Notice {
+            id: inbox.next_id,
+            kind,
+            timestamp: now,
+            block: exec::block_height(),
+        });
+    }
+
+    /// Drop expired notices from the front of the inbox (oldest first)
+    fn prune(inbox: &mut NoticeInbox, now: u64) {
+        while inbox.notices.front().is_some_and(|n| Self::is_expired(n, now)) {
+            inbox.notices.pop_front();
+        }
+    }
+
+    fn is_expired(notice: &Notice, now: u64) -> bool {
+        now.saturating_sub(notice.timestamp) > NOTICE_TTL_SECONDS
+    }
+
+    /// Drop the caller's notices with id up to `up_to_id`; returns how many were removed
+    pub fn acknowledge(caller: ActorId, up_to_id: u64) -> u32 {
+        let mut st = PerpetualDEXState::get_mut();
+        let Some(inbox) = st.notifications.get_mut(&caller) else {
+            return 0;
+        };
+        Self::prune(inbox, exec::block_timestamp());
+        let before = inbox.notices.len();
+        inbox.notices.retain(|n| n.id > up_to_id);
+        (before - inbox.notices.len()) as u32
+    }
+
+    /// Unexpired notices, most recent first
+    pub fn notices(account: ActorId, limit: u32) -> Vec<Notice> {
+        let now = exec::block_timestamp();
+        let st = PerpetualDEXState::get();
+        st.notifications
+            .get(&account)
+            .map(|inbox| {
+                inbox
+                    .notices
+                    .iter()
+                    .rev()
+                    .take_while(|n| !Self::is_expired(n, now))
+                    .take(limit as usize)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notice(id: u64, timestamp: u64) -> Notice {
+        Notice {
+            id,
+            kind: NoticeKind::MarginCall(MarginCall {
+                position_key: Default::default(),
+                account: Default::default(),
+                market: String::new(),
+                is_long: true,
+                price: 0,
+                effective_collateral: 0,
+                liquidation_threshold_usd: 0,
+                margin_call_threshold_usd: 0,
+                timestamp,
+            }),
+            timestamp,
+            block: 0,
+        }
+    }
+
+    #[test]
+    fn test_prune_drops_only_expired_notices() {
+        let mut inbox = NoticeInbox {
+            next_id: 3,
+            notices: [notice(1, 0), notice(2, 100), notice(3, NOTICE_TTL_SECONDS + 50)].into_iter().collect(),
+        };
+        NotificationModule::prune(&mut inbox, NOTICE_TTL_SECONDS + 100);
+        let ids: Vec<u64> = inbox.notices.iter().map(|n| n.id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(inbox.next_id, 3);
+    }
+}
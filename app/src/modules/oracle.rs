@@ -1,6 +1,11 @@
-use sails_rs::{prelude::*, gstd::exec};
+use sails_rs::{prelude::*, gstd::{exec, msg}};
 use sails_rs::collections::BTreeMap;
-use crate::{types::*, errors::Error, PerpetualDEXState, utils};
+use crate::{types::*, errors::Error, PerpetualDEXState, utils, modules::risk::RiskModule};
+
+/// Max markets whose funding is accrued per `set_prices` call, bounding gas per oracle push
+pub const MAX_ACCRUALS_PER_PRICE_UPDATE: usize = 16;
+/// Upper bound on a feed's published price decimals
+pub const MAX_PRICE_DECIMALS: u8 = 30;
 
 #[derive(Encode, Decode, TypeInfo, Clone, Debug)]
 #[codec(crate = sails_rs::scale_codec)]
@@ -21,6 +26,10 @@ impl OracleState {
             timestamps: BTreeMap::new(),
             last_signer: BTreeMap::new(),
             config: OracleConfig { max_age_seconds: 60 },
+            ratio_feeds: BTreeMap::new(),
+            spot_sources: BTreeMap::new(),
+            spot_twaps: BTreeMap::new(),
+            price_decimals: BTreeMap::new(),
         }
     }
 
@@ -30,7 +39,69 @@ impl OracleState {
             timestamps: BTreeMap::new(),
             last_signer: BTreeMap::new(),
             config,
+            ratio_feeds: BTreeMap::new(),
+            spot_sources: BTreeMap::new(),
+            spot_twaps: BTreeMap::new(),
+            price_decimals: BTreeMap::new(),
+        }
+    }
+
+    /// Direct feed price, or the synthetic ratio of two direct feeds.
+    /// The ratio band is conservative: min = base.min / quote.max, max = base.max / quote.min.
+    pub fn price(&self, token: &str) -> Option<Price> {
+        if let Some(p) = self.direct_price(token) {
+            return Some(p);
+        }
+        let feed = self.ratio_feeds.get(token)?;
+        let base = self.direct_price(&feed.base)?;
+        let quote = self.direct_price(&feed.quote)?;
+        if quote.min == 0 || quote.max == 0 {
+            return None;
         }
+        Some(Price {
+            min: base.min.checked_mul(USD_SCALE)? / quote.max,
+            max: base.max.checked_mul(USD_SCALE)? / quote.min,
+        })
+    }
+
+    /// Signed price of a direct feed, with its mid moved `weight_bps` toward the spot TWAP
+    /// while a fresh one is cached
+    fn direct_price(&self, token: &str) -> Option<Price> {
+        let p = self.prices.get(token)?;
+        let twap = self.spot_sources.get(token).zip(self.spot_twaps.get(token)).filter(|(source, twap)| {
+            exec::block_timestamp().saturating_sub(twap.fetched_at) <= source.max_age_seconds
+        });
+        let Some((source, twap)) = twap else {
+            return Some(p.clone());
+        };
+        Some(blend_twap(p, twap.price, source.weight_bps, source.max_deviation_bps))
+    }
+
+    /// Publish time of a feed; for ratio feeds, the older of the two legs
+    pub fn timestamp(&self, token: &str) -> Option<u64> {
+        if let Some(ts) = self.timestamps.get(token) {
+            return Some(*ts);
+        }
+        let feed = self.ratio_feeds.get(token)?;
+        let base = self.timestamps.get(&feed.base)?;
+        let quote = self.timestamps.get(&feed.quote)?;
+        Some((*base).min(*quote))
+    }
+}
+
+/// Move the mid of a signed price `weight_bps` toward `twap`, keeping the signed spread around it.
+/// A TWAP more than `max_deviation_bps` away from the signed mid leaves the price unchanged.
+fn blend_twap(p: &Price, twap: u128, weight_bps: u16, max_deviation_bps: u16) -> Price {
+    let mid = p.min / 2 + p.max / 2 + (p.min % 2 + p.max % 2) / 2;
+    let max_deviation = mid.saturating_mul(max_deviation_bps as u128) / 10_000;
+    if twap.abs_diff(mid) > max_deviation {
+        return p.clone();
+    }
+    let w = weight_bps.min(10_000) as u128;
+    let blended = mid.saturating_mul(10_000 - w).saturating_add(twap.saturating_mul(w)) / 10_000;
+    Price {
+        min: blended.saturating_sub(mid.saturating_sub(p.min)),
+        max: blended.saturating_add(p.max.saturating_sub(mid)),
     }
 }
 
@@ -41,23 +112,71 @@ impl OracleModule {
         let mut st = PerpetualDEXState::get_mut();
         let now = exec::block_timestamp();
 
+        let mut updated: Vec<String> = Vec::new();
         for sp in batch {
             if now.saturating_sub(sp.timestamp) > st.oracle.config.max_age_seconds {
                 return Err(Error::PriceStale);
             }
+            // Ratio feeds are derived from their legs and cannot be pushed directly
+            if st.oracle.ratio_feeds.contains_key(&sp.token) {
+                return Err(Error::InvalidParameter);
+            }
             if !utils::verify_signature(&sp.token, &sp.price, sp.timestamp, &sp.signer, &sp.signature) {
                 return Err(Error::InvalidOracleSignature);
             }
-            st.oracle.prices.insert(sp.token.clone(), sp.price);
+            // Signatures cover the price as published; it is stored at the program's scale
+            let price = match st.oracle.price_decimals.get(&sp.token) {
+                Some(decimals) => Self::rescale(&sp.price, *decimals)?,
+                None => sp.price,
+            };
+            updated.push(sp.token.clone());
+            st.oracle.prices.insert(sp.token.clone(), price);
             st.oracle.timestamps.insert(sp.token.clone(), sp.timestamp);
             st.oracle.last_signer.insert(sp.token, sp.signer);
         }
+
+        // Keep funding indices current for markets priced off the updated tokens,
+        // stalest first, so accrual does not depend on trades arriving
+        let mut affected: Vec<(u64, String)> = st
+            .markets
+            .iter()
+            .filter(|(_, m)| {
+                updated.contains(&m.index_token) || Self::ratio_depends_on(&st.oracle, &m.index_token, &updated)
+            })
+            .filter_map(|(id, _)| st.pool_amounts.get(id).map(|p| (p.last_funding_update, id.clone())))
+            .collect();
+        drop(st);
+
+        affected.sort();
+        for (_, market) in affected.into_iter().take(MAX_ACCRUALS_PER_PRICE_UPDATE) {
+            // Best-effort: a failing market must not block the price update
+            let _ = RiskModule::accrue_pool(&market, now);
+        }
         Ok(())
     }
 
+    /// Normalize a price published with `decimals` to micro-USD. Prices that overflow, or that
+    /// round down to zero, are rejected rather than stored.
+    fn rescale(price: &Price, decimals: u8) -> Result<Price, Error> {
+        let to_usd = |v: u128| {
+            utils::token_amount_to_usd(v, decimals).filter(|usd| *usd > 0 || v == 0).ok_or(Error::InvalidPrice)
+        };
+        Ok(Price {
+            min: to_usd(price.min)?,
+            max: to_usd(price.max)?,
+        })
+    }
+
+    fn ratio_depends_on(oracle: &OracleState, token: &str, updated: &[String]) -> bool {
+        oracle
+            .ratio_feeds
+            .get(token)
+            .is_some_and(|f| updated.contains(&f.base) || updated.contains(&f.quote))
+    }
+
     pub fn get_price(token: &str) -> Result<Price, Error> {
         let st = PerpetualDEXState::get();
-        st.oracle.prices.get(token).cloned().ok_or(Error::PriceNotAvailable)
+        st.oracle.price(token).ok_or(Error::PriceNotAvailable)
     }
 
     pub fn mid(token: &str) -> Result<u128, Error> {
@@ -65,6 +184,17 @@ impl OracleModule {
         Ok((p.min + p.max) / 2)
     }
 
+    /// Adverse side of the spread for closing a position, `slippage_bps` further out:
+    /// bid minus slippage for longs, ask plus slippage for shorts
+    pub fn adverse_price(token: &str, is_long: bool, slippage_bps: u16) -> Result<u128, Error> {
+        let p = Self::get_price(token)?;
+        Ok(if is_long {
+            p.min.saturating_sub(p.min.saturating_mul(slippage_bps as u128) / 10_000)
+        } else {
+            p.max.saturating_add(p.max.saturating_mul(slippage_bps as u128) / 10_000)
+        })
+    }
+
     pub fn spread(token: &str) -> Result<u128, Error> {
         let p = Self::get_price(token)?;
         Ok(p.max.saturating_sub(p.min))
@@ -72,17 +202,27 @@ impl OracleModule {
 
     pub fn ensure_fresh(token: &str) -> Result<(), Error> {
         let st = PerpetualDEXState::get();
-        let ts = st.oracle.timestamps.get(token).ok_or(Error::PriceNotAvailable)?;
+        let ts = st.oracle.timestamp(token).ok_or(Error::PriceNotAvailable)?;
         let now = exec::block_timestamp();
-        if now.saturating_sub(*ts) > st.oracle.config.max_age_seconds {
+        if now.saturating_sub(ts) > st.oracle.config.max_age_seconds {
             return Err(Error::PriceStale);
         }
         Ok(())
     }
 
+    /// Price has been stale for longer than `max_age + grace_seconds`
+    pub fn is_in_outage(token: &str, grace_seconds: u64) -> bool {
+        let st = PerpetualDEXState::get();
+        let Some(ts) = st.oracle.timestamp(token) else {
+            return false;
+        };
+        let limit = st.oracle.config.max_age_seconds.saturating_add(grace_seconds);
+        exec::block_timestamp().saturating_sub(ts) > limit
+    }
+
     pub fn last_update(token: &str) -> Option<u64> {
         let st = PerpetualDEXState::get();
-        st.oracle.timestamps.get(token).cloned()
+        st.oracle.timestamp(token)
     }
 
     pub fn last_signer(token: &str) -> Option<ActorId> {
@@ -90,6 +230,159 @@ impl OracleModule {
         st.oracle.last_signer.get(token).cloned()
     }
 
+    /// Every direct feed, ratio feed and market index token with its age against `max_age_seconds`,
+    /// sorted by token
+    pub fn feed_statuses() -> Vec<FeedStatus> {
+        let st = PerpetualDEXState::get();
+        let now = exec::block_timestamp();
+        let max_age_seconds = st.oracle.config.max_age_seconds;
+
+        let mut tokens: Vec<&String> = st
+            .oracle
+            .prices
+            .keys()
+            .chain(st.oracle.ratio_feeds.keys())
+            .chain(st.markets.values().map(|m| &m.index_token))
+            .collect();
+        tokens.sort();
+        tokens.dedup();
+
+        tokens
+            .into_iter()
+            .map(|token| {
+                let last_update = st.oracle.timestamp(token);
+                let age_seconds = last_update.map(|ts| now.saturating_sub(ts));
+                let mut markets: Vec<String> = st
+                    .markets
+                    .iter()
+                    .filter(|(_, m)| &m.index_token == token)
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                markets.sort();
+                FeedStatus {
+                    token: token.clone(),
+                    is_ratio: st.oracle.ratio_feeds.contains_key(token),
+                    last_update,
+                    age_seconds,
+                    max_age_seconds,
+                    last_signer: st.oracle.last_signer.get(token).copied(),
+                    stale: age_seconds.is_none_or(|age| age > max_age_seconds),
+                    markets,
+                }
+            })
+            .collect()
+    }
+
+    /// Register `token` as a synthetic feed priced base / quote (admin only).
+    /// Both legs must be direct feeds.
+    pub fn set_ratio_feed(caller: ActorId, token: String, base: String, quote: String) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if base == quote || token == base || token == quote || st.oracle.prices.contains_key(&token) {
+            return Err(Error::InvalidParameter);
+        }
+        if st.oracle.ratio_feeds.contains_key(&base) || st.oracle.ratio_feeds.contains_key(&quote) {
+            return Err(Error::InvalidParameter);
+        }
+        st.oracle.ratio_feeds.insert(token, RatioFeed { base, quote });
+        Ok(())
+    }
+
+    pub fn remove_ratio_feed(caller: ActorId, token: &str) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.oracle.ratio_feeds.remove(token).ok_or(Error::PriceNotAvailable)?;
+        Ok(())
+    }
+
+    pub fn get_ratio_feed(token: &str) -> Option<RatioFeed> {
+        let st = PerpetualDEXState::get();
+        st.oracle.ratio_feeds.get(token).cloned()
+    }
+
+    /// Blend a spot DEX TWAP into a direct feed (`Some`), or stop blending (`None`) (admin only)
+    pub fn set_spot_source(caller: ActorId, token: String, source: Option<SpotTwapSource>) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        let invalid = |s: &SpotTwapSource| {
+            s.weight_bps > 10_000 || s.max_deviation_bps == 0 || s.max_deviation_bps > 10_000
+        };
+        if st.oracle.ratio_feeds.contains_key(&token) || source.as_ref().is_some_and(invalid) {
+            return Err(Error::InvalidParameter);
+        }
+        // A cached TWAP belongs to the previous source
+        st.oracle.spot_twaps.remove(&token);
+        match source {
+            Some(source) => st.oracle.spot_sources.insert(token, source),
+            None => st.oracle.spot_sources.remove(&token),
+        };
+        Ok(())
+    }
+
+    /// Set the decimals a direct feed is published in (`None` = micro-USD) (admin only).
+    /// Prices already stored keep their scale; the next push is rescaled.
+    pub fn set_price_decimals(caller: ActorId, token: String, decimals: Option<u8>) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if st.oracle.ratio_feeds.contains_key(&token) || decimals.is_some_and(|d| d > MAX_PRICE_DECIMALS) {
+            return Err(Error::InvalidParameter);
+        }
+        match decimals {
+            Some(decimals) => st.oracle.price_decimals.insert(token, decimals),
+            None => st.oracle.price_decimals.remove(&token),
+        };
+        Ok(())
+    }
+
+    pub fn get_price_decimals(token: &str) -> Option<u8> {
+        PerpetualDEXState::get().oracle.price_decimals.get(token).copied()
+    }
+
+    /// Query the token's spot DEX pool for its TWAP and cache it for blending (keepers/admin)
+    pub async fn refresh_spot_twap(caller: ActorId, token: String) -> Result<CachedSpotTwap, Error> {
+        let source = {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            st.oracle.spot_sources.get(&token).cloned().ok_or(Error::PriceNotAvailable)?
+        };
+
+        let payload = (source.service.clone(), source.method.clone(), source.window_seconds).encode();
+        let reply = msg::send_bytes_for_reply(source.program, payload, 0, source.reply_deposit)
+            .map_err(|_| Error::ExternalQueryFailed)?
+            .await
+            .map_err(|_| Error::ExternalQueryFailed)?;
+        let (_, _, price) =
+            <(String, String, u128)>::decode(&mut reply.as_slice()).map_err(|_| Error::ExternalQueryFailed)?;
+        if price == 0 {
+            return Err(Error::InvalidPrice);
+        }
+
+        let cached = CachedSpotTwap {
+            price,
+            fetched_at: exec::block_timestamp(),
+        };
+        let mut st = PerpetualDEXState::get_mut();
+        // Source may have been replaced while awaiting the reply
+        if st.oracle.spot_sources.get(&token) == Some(&source) {
+            st.oracle.spot_twaps.insert(token, cached.clone());
+        }
+        Ok(cached)
+    }
+
+    pub fn get_spot_twap(token: &str) -> Option<CachedSpotTwap> {
+        PerpetualDEXState::get().oracle.spot_twaps.get(token).cloned()
+    }
+
     pub fn set_config(caller: ActorId, cfg: OracleConfig) -> Result<(), Error> {
         let mut st = PerpetualDEXState::get_mut();
         if !st.is_admin(caller) {
@@ -98,4 +391,39 @@ impl OracleModule {
         st.oracle.config = cfg;
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_twap() {
+        let p = Price { min: 990, max: 1_010 };
+        // Half weight moves the mid halfway and keeps the signed spread
+        let blended = blend_twap(&p, 1_040, 5_000, 1_000);
+        assert_eq!((blended.min, blended.max), (1_010, 1_030));
+        // TWAP beyond the deviation is ignored
+        let ignored = blend_twap(&p, 1_200, 5_000, 1_000);
+        assert_eq!((ignored.min, ignored.max), (990, 1_010));
+        // Zero weight keeps the signed price
+        let kept = blend_twap(&p, 1_040, 0, 1_000);
+        assert_eq!((kept.min, kept.max), (990, 1_010));
+    }
+
+    #[test]
+    fn test_rescale() {
+        // 18-decimal feed at $2_000 / $2_001 to micro-USD
+        let p = Price {
+            min: 2_000 * 10u128.pow(18),
+            max: 2_001 * 10u128.pow(18),
+        };
+        let scaled = OracleModule::rescale(&p, 18).unwrap();
+        assert_eq!((scaled.min, scaled.max), (2_000_000_000, 2_001_000_000));
+        // Prices that round down to zero are rejected, a zero price is kept
+        assert!(OracleModule::rescale(&Price { min: 1, max: 2 }, 18).is_err());
+        let zero = OracleModule::rescale(&Price { min: 0, max: 0 }, 18).unwrap();
+        assert_eq!((zero.min, zero.max), (0, 0));
+        // Overflowing prices are rejected
+        assert!(OracleModule::rescale(&Price { min: u128::MAX, max: u128::MAX }, 0).is_err());
+    }
+}
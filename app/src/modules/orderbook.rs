@@ -0,0 +1,523 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{
+        backstop::BackstopModule,
+        discount::FeeDiscountModule,
+        history::HistoryModule,
+        oracle::OracleModule,
+        position::PositionModule,
+        pricing::{PricingModule, QuoteResult},
+        referral::ReferralModule,
+        risk::RiskModule,
+        stats::{FeeKind, StatsModule},
+        trading::TradingModule,
+    },
+    types::*,
+    utils,
+};
+use sails_rs::{gstd::exec, prelude::*};
+
+/// A maker order slice consumed by an incoming taker order
+#[derive(Clone, Debug)]
+pub struct BookFill {
+    pub order_id: u64,
+    pub maker: ActorId,
+    pub collateral_token: String,
+    pub price: u128,
+    pub size_usd: Usd,
+    /// Maker collateral released for this slice
+    pub collateral_usd: Usd,
+}
+
+/// Optional on-chain order book.
+///
+/// Makers rest post-only limit orders that open positions, priced within the market's band
+/// around the oracle mid. Market increase orders first match resting liquidity priced better
+/// than the pool quote; whatever is left is filled by the pool at the oracle quote (backstop
+/// liquidity).
+pub struct OrderBookModule;
+
+impl OrderBookModule {
+    pub fn is_enabled(market: &str) -> bool {
+        let st = PerpetualDEXState::get();
+        st.market_configs.get(market).is_some_and(|c| c.orderbook_enabled)
+    }
+
+    /// Place a post-only maker order. Collateral is escrowed until filled or cancelled.
+    pub fn place_order(
+        caller: ActorId,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        price: u128,
+        size_usd: u128,
+        collateral_usd: u128,
+    ) -> Result<u64, Error> {
+        let now = exec::block_timestamp();
+        let is_long = matches!(side, OrderSide::Long);
+        let price_key = utils::price_key(&market);
+        OracleModule::ensure_fresh(&price_key)?;
+        let mid = OracleModule::mid(&price_key)?;
+
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        st.ensure_not_withdraw_only()?;
+        st.ensure_market_allows(&market, true)?;
+        st.ensure_trading_enabled(caller)?;
+
+        let cfg = st.market_configs.get(&market).ok_or(Error::MarketNotFound)?;
+        if !cfg.orderbook_enabled {
+            return Err(Error::OrderBookDisabled);
+        }
+        if price == 0 {
+            return Err(Error::InvalidPrice);
+        }
+        if !Self::within_band(price, mid, cfg.maker_price_band_bps) {
+            return Err(Error::ExecutionPriceOutOfBounds);
+        }
+        if size_usd == 0 {
+            return Err(Error::InvalidOrderSize);
+        }
+        if collateral_usd == 0 {
+            return Err(Error::InvalidCollateralAmount);
+        }
+        let leverage_bps = size_usd.saturating_mul(10_000) / collateral_usd;
+        if leverage_bps > (cfg.max_leverage as u128).saturating_mul(10_000) {
+            return Err(Error::MaxLeverageExceeded);
+        }
+
+        let book = st.order_books.entry(market.clone()).or_default();
+        let crosses = if is_long {
+            book.asks.keys().next().is_some_and(|best_ask| price >= *best_ask)
+        } else {
+            book.bids.keys().next_back().is_some_and(|best_bid| price <= *best_bid)
+        };
+        if crosses {
+            return Err(Error::OrderWouldCross);
+        }
+
+        let bal = st.balances.get_mut(&caller).ok_or(Error::InsufficientBalance)?;
+        if *bal < collateral_usd {
+            return Err(Error::InsufficientBalance);
+        }
+        *bal = bal.saturating_sub(collateral_usd);
+
+        let id = st.next_book_order_id;
+        st.next_book_order_id += 1;
+
+        let levels = if is_long { &mut book.bids } else { &mut book.asks };
+        levels.entry(price).or_default().push(id);
+        book.orders.insert(
+            id,
+            BookOrder {
+                id,
+                account: caller,
+                market,
+                collateral_token,
+                is_long,
+                price,
+                size_usd,
+                collateral_usd,
+                created_at: now,
+            },
+        );
+
+        Ok(id)
+    }
+
+    /// Cancel a resting maker order and refund the remaining escrow.
+    pub fn cancel_order(caller: ActorId, market: &str, order_id: u64) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+
+        let book = st.order_books.get_mut(market).ok_or(Error::OrderNotFound)?;
+        let order = book.orders.get(&order_id).ok_or(Error::OrderNotFound)?;
+        if order.account != caller {
+            return Err(Error::Unauthorized);
+        }
+
+        let order = Self::remove_order(book, order_id).ok_or(Error::OrderNotFound)?;
+        let bal = st.balances.entry(caller).or_insert(0);
+        *bal = bal.saturating_add(order.collateral_usd);
+        Ok(())
+    }
+
+    /// Cancel every resting maker order of `account` across markets, refunding the escrow.
+    /// Returns how many were cancelled.
+    pub fn cancel_account_orders(account: ActorId) -> u32 {
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+
+        let mut refund = 0u128;
+        let mut cancelled = 0u32;
+        for book in st.order_books.values_mut() {
+            let ids: Vec<u64> = book.orders.values().filter(|o| o.account == account).map(|o| o.id).collect();
+            for id in ids {
+                if let Some(order) = Self::remove_order(book, id) {
+                    refund = refund.saturating_add(order.collateral_usd);
+                    cancelled += 1;
+                }
+            }
+        }
+        if refund > 0 {
+            let bal = st.balances.entry(account).or_insert(0);
+            *bal = bal.saturating_add(refund);
+        }
+        cancelled
+    }
+
+    /// Walk the opposite side of the book best-first, collecting fills priced
+    /// at or better than `limit_price`, up to `size_usd` in total.
+    pub fn match_fills(market: &str, taker_is_long: bool, limit_price: u128, size_usd: u128) -> Vec<BookFill> {
+        let st = PerpetualDEXState::get();
+        let Some(book) = st.order_books.get(market) else {
+            return Vec::new();
+        };
+        let Ok(mid) = OracleModule::mid(&utils::price_key(market)) else {
+            return Vec::new();
+        };
+        let band_bps = st.market_configs.get(market).map_or(0, |c| c.maker_price_band_bps);
+
+        let levels: Vec<(&u128, &Vec<u64>)> = if taker_is_long {
+            book.asks.iter().take_while(|(p, _)| **p <= limit_price).collect()
+        } else {
+            book.bids.iter().rev().take_while(|(p, _)| **p >= limit_price).collect()
+        };
+
+        let mut fills = Vec::new();
+        let mut remaining = size_usd;
+        for (_, ids) in levels {
+            for id in ids {
+                if remaining == 0 {
+                    return fills;
+                }
+                // Makers who paused their trading, or whose price the oracle has moved away
+                // from, are passed over, not filled
+                let Some(order) = book.orders.get(id).filter(|o| {
+                    st.ensure_trading_enabled(o.account).is_ok() && Self::within_band(o.price, mid, band_bps)
+                }) else {
+                    continue;
+                };
+                let take = remaining.min(order.size_usd);
+                let collateral = if take == order.size_usd {
+                    order.collateral_usd
+                } else {
+                    order.collateral_usd.saturating_mul(take) / order.size_usd
+                };
+                fills.push(BookFill {
+                    order_id: order.id,
+                    maker: order.account,
+                    collateral_token: order.collateral_token.clone(),
+                    price: order.price,
+                    size_usd: take,
+                    collateral_usd: collateral,
+                });
+                remaining -= take;
+            }
+        }
+        fills
+    }
+
+    /// Execute a taker increase against `fills`, routing any unfilled remainder to the pool at
+    /// the `pool` quote and its trading fee. All-or-nothing: a leg that fails after others were
+    /// applied rolls the state back. Legs write to every crossed maker's balance, position and
+    /// referrer, so the whole state is copied.
+    pub fn execute_taker(
+        taker: ActorId,
+        params: &CreateOrderParams,
+        fills: Vec<BookFill>,
+        pool: &QuoteResult,
+    ) -> Result<ExecutionResult, Error> {
+        let now = exec::block_timestamp();
+        let fee_bps = {
+            let st = PerpetualDEXState::get();
+            let cfg = st.market_configs.get(&params.market).ok_or(Error::MarketNotFound)?;
+            if st.balances.get(&taker).copied().unwrap_or(0) < params.collateral_delta_amount {
+                return Err(Error::InsufficientBalance);
+            }
+            (cfg.maker_fee_bps as u128, cfg.taker_fee_bps as u128, cfg.trading_fee_bps as u128)
+        };
+        let mid = OracleModule::mid(&utils::price_key(&params.market))?;
+        RiskModule::accrue_pool(&params.market, now)?;
+
+        let snapshot = PerpetualDEXState::snapshot();
+        let result = Self::apply_fills(taker, params, fills, pool, mid, fee_bps);
+        if result.is_err() {
+            PerpetualDEXState::restore(snapshot);
+        }
+        result
+    }
+
+    fn apply_fills(
+        taker: ActorId,
+        params: &CreateOrderParams,
+        fills: Vec<BookFill>,
+        pool: &QuoteResult,
+        mid: u128,
+        (maker_fee_bps, taker_fee_bps, pool_fee_bps): (u128, u128, u128),
+    ) -> Result<ExecutionResult, Error> {
+        let is_long = matches!(params.side, OrderSide::Long);
+        let maker_side = if is_long { OrderSide::Short } else { OrderSide::Long };
+        let mut position_key = None;
+        let mut filled_usd = 0u128;
+        let mut notional = 0u128;
+        let mut collateral_used = 0u128;
+
+        for fill in fills {
+            // Maker leg: release escrow back to the wallet, then open the opposite side
+            {
+                let mut st = PerpetualDEXState::get_mut();
+                let book = st.order_books.get_mut(&params.market).ok_or(Error::OrderNotFound)?;
+                Self::consume_order(book, fill.order_id, fill.size_usd, fill.collateral_usd)?;
+                let bal = st.balances.entry(fill.maker).or_insert(0);
+                *bal = bal.saturating_add(fill.collateral_usd);
+            }
+            let maker_fee = FeeDiscountModule::trading_fee(fill.maker, fill.size_usd, maker_fee_bps);
+            let maker_key = PositionModule::increase_position(
+                fill.maker,
+                params.market.clone(),
+                fill.collateral_token.clone(),
+                !is_long,
+                fill.size_usd,
+                fill.collateral_usd.saturating_sub(maker_fee),
+                fill.price,
+                None,
+            )?;
+            Self::collect_fee(&params.market, fill.maker, !is_long, maker_fee)?;
+            Self::ensure_solvent_at(maker_key, mid)?;
+
+            // Taker leg: pro-rata share of the order collateral
+            let taker_collateral = params.collateral_delta_amount.saturating_mul(fill.size_usd) / params.size_delta_usd;
+            let taker_fee = FeeDiscountModule::trading_fee(taker, fill.size_usd, taker_fee_bps);
+            let taker_key = PositionModule::increase_position(
+                taker,
+                params.market.clone(),
+                params.collateral_token.clone(),
+                is_long,
+                fill.size_usd,
+                taker_collateral.saturating_sub(taker_fee),
+                fill.price,
+                params.tag,
+            )?;
+            Self::collect_fee(&params.market, taker, is_long, taker_fee)?;
+            Self::ensure_solvent_at(taker_key, mid)?;
+            position_key = Some(taker_key);
+
+            // Both legs move pool OI; keep the block flow in step so pool trades later in the
+            // block are priced from the right starting OI
+            PricingModule::record_block_flow(&params.market, &maker_side, fill.size_usd, true, 0);
+            PricingModule::record_block_flow(&params.market, &params.side, fill.size_usd, true, 0);
+
+            filled_usd = filled_usd.saturating_add(fill.size_usd);
+            notional = notional.saturating_add(fill.size_usd.saturating_mul(fill.price));
+            collateral_used = collateral_used.saturating_add(taker_collateral);
+        }
+
+        // Pool backstop for the unfilled remainder, charged the pool trading fee
+        let remaining = params.size_delta_usd.saturating_sub(filled_usd);
+        if remaining > 0 {
+            let remainder = CreateOrderParams {
+                size_delta_usd: remaining,
+                collateral_delta_amount: params.collateral_delta_amount.saturating_sub(collateral_used),
+                ..params.clone()
+            };
+            let pool_fee = FeeDiscountModule::trading_fee(taker, remaining, pool_fee_bps);
+            position_key = Some(PositionModule::increase_position(
+                taker,
+                params.market.clone(),
+                params.collateral_token.clone(),
+                is_long,
+                remaining,
+                remainder.collateral_delta_amount.saturating_sub(pool_fee),
+                pool.execution_price,
+                params.tag,
+            )?);
+            Self::collect_fee(&params.market, taker, is_long, pool_fee)?;
+            // The quote priced the whole order; the remainder carries its share of the impact
+            let remainder_quote = QuoteResult {
+                price_impact_usd: pool.price_impact_usd.saturating_mul(remaining as i128)
+                    / params.size_delta_usd as i128,
+                ..pool.clone()
+            };
+            TradingModule::record_execution(&remainder, &remainder_quote);
+            notional = notional.saturating_add(remaining.saturating_mul(pool.execution_price));
+        }
+
+        Ok(ExecutionResult::Executed {
+            position_key: position_key.ok_or(Error::InvalidOrderSize)?,
+            execution_price: notional / params.size_delta_usd,
+        })
+    }
+
+    /// A position opened by a book fill must not already be liquidatable at the oracle mid, so an
+    /// off-market cross cannot hand one leg's loss to the pool as bad debt
+    fn ensure_solvent_at(key: PositionKey, mid: u128) -> Result<(), Error> {
+        let st = PerpetualDEXState::get();
+        let pos = st.positions.get(&key).ok_or(Error::PositionNotFound)?;
+        if RiskModule::is_liquidatable_for_owner(&st, pos, mid, exec::block_timestamp())? {
+            return Err(Error::InsufficientCollateral);
+        }
+        Ok(())
+    }
+
+    /// `price` lies within `band_bps` of `mid`
+    fn within_band(price: u128, mid: u128, band_bps: u16) -> bool {
+        price.abs_diff(mid).saturating_mul(10_000) <= mid.saturating_mul(band_bps as u128)
+    }
+
+    /// Move a fee from the payer's wallet into the LP claimable bucket of the payer's side,
+    /// less the payer's referrer share and the backstop tranche's share. A market fee split,
+    /// when configured, replaces the referrer share and also pays insurance and treasury. Fails, without
+    /// any change, when the wallet can't cover the fee in full.
+    pub fn collect_fee(market: &str, payer: ActorId, is_long: bool, fee: u128) -> Result<(), Error> {
+        if fee == 0 {
+            return Ok(());
+        }
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        {
+            let bal = st.balances.get_mut(&payer).filter(|b| **b >= fee).ok_or(Error::InsufficientBalance)?;
+            *bal -= fee;
+        }
+        let trader = StatsModule::trader(&mut st.trader_stats, payer);
+        trader.trading_fees = trader.trading_fees.saturating_add(fee);
+        HistoryModule::add_fee_to_last_fill(st, payer, fee);
+        StatsModule::record_fee(&mut st.market_fee_stats, market, FeeKind::Trading, fee, exec::block_timestamp());
+        let split = st.market_configs.get(market).map(|c| c.fee_split.clone()).filter(|s| s.is_set());
+        let paid_out = match split {
+            Some(split) => Self::route_fee_split(st, payer, fee, &split),
+            None => fee - ReferralModule::take_fee_share(st, payer, fee),
+        };
+        let to_pool = paid_out - BackstopModule::take_fee_share(st, market, paid_out);
+        if let Some(pool) = st.pool_amounts.get_mut(market) {
+            if is_long {
+                pool.claimable_fee_usd_long = pool.claimable_fee_usd_long.saturating_add(to_pool);
+            } else {
+                pool.claimable_fee_usd_short = pool.claimable_fee_usd_short.saturating_add(to_pool);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pay the insurance, treasury and referrer parts of a split fee; returns the LP part
+    /// (the configured LP share, unclaimed referrer share and rounding dust)
+    fn route_fee_split(st: &mut PerpetualDEXState, payer: ActorId, fee: Usd, split: &TradingFeeSplit) -> Usd {
+        let (insurance, treasury, referrer) = split.parts(fee);
+        let referrer = ReferralModule::pay_referrer(st, payer, referrer);
+
+        st.insurance_fund_usd = st.insurance_fund_usd.saturating_add(insurance);
+        st.treasury_usd = st.treasury_usd.saturating_add(treasury);
+        fee - insurance - treasury - referrer
+    }
+
+    /// Move `amount` of the treasury into `receiver`'s balance (admin only).
+    pub fn withdraw_treasury(caller: ActorId, receiver: ActorId, amount: Usd) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.treasury_usd = st.treasury_usd.checked_sub(amount).ok_or(Error::InsufficientBalance)?;
+        let bal = st.balances.entry(receiver).or_insert(0);
+        *bal = bal.saturating_add(amount);
+        Ok(())
+    }
+
+    fn consume_order(book: &mut OrderBook, order_id: u64, size_usd: Usd, collateral_usd: Usd) -> Result<(), Error> {
+        let order = book.orders.get_mut(&order_id).ok_or(Error::OrderNotFound)?;
+        order.size_usd = order.size_usd.saturating_sub(size_usd);
+        order.collateral_usd = order.collateral_usd.saturating_sub(collateral_usd);
+        if order.size_usd == 0 {
+            Self::remove_order(book, order_id);
+        }
+        Ok(())
+    }
+
+    fn remove_order(book: &mut OrderBook, order_id: u64) -> Option<BookOrder> {
+        let order = book.orders.remove(&order_id)?;
+        let levels = if order.is_long { &mut book.bids } else { &mut book.asks };
+        if let Some(ids) = levels.get_mut(&order.price) {
+            ids.retain(|id| *id != order_id);
+            if ids.is_empty() {
+                levels.remove(&order.price);
+            }
+        }
+        Some(order)
+    }
+
+    /// Aggregated depth, best levels first
+    pub fn get_depth(market: &str, levels: u32) -> OrderBookDepth {
+        let st = PerpetualDEXState::get();
+        let Some(book) = st.order_books.get(market) else {
+            return OrderBookDepth::default();
+        };
+
+        let level_size = |ids: &Vec<u64>| -> Usd {
+            ids.iter()
+                .filter_map(|id| book.orders.get(id))
+                .fold(0u128, |acc, o| acc.saturating_add(o.size_usd))
+        };
+
+        OrderBookDepth {
+            bids: book
+                .bids
+                .iter()
+                .rev()
+                .take(levels as usize)
+                .map(|(price, ids)| BookLevel { price: *price, size_usd: level_size(ids) })
+                .collect(),
+            asks: book
+                .asks
+                .iter()
+                .take(levels as usize)
+                .map(|(price, ids)| BookLevel { price: *price, size_usd: level_size(ids) })
+                .collect(),
+        }
+    }
+
+    pub fn get_account_orders(account: ActorId) -> Vec<BookOrder> {
+        let st = PerpetualDEXState::get();
+        st.order_books
+            .values()
+            .flat_map(|b| b.orders.values())
+            .filter(|o| o.account == account)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maker_price_band() {
+        let mid = 2_000 * USD_SCALE;
+        // 100 bps band: 1_980..=2_020
+        assert!(OrderBookModule::within_band(2_020 * USD_SCALE, mid, 100));
+        assert!(OrderBookModule::within_band(1_980 * USD_SCALE, mid, 100));
+        assert!(!OrderBookModule::within_band(2_020 * USD_SCALE + 1, mid, 100));
+        assert!(!OrderBookModule::within_band(1_980 * USD_SCALE - 1, mid, 100));
+        assert!(!OrderBookModule::within_band(mid + 1, mid, 0));
+    }
+
+    #[test]
+    fn test_fee_split_parts() {
+        let split = TradingFeeSplit {
+            lp_bps: 5_000,
+            insurance_bps: 2_000,
+            treasury_bps: 2_000,
+            referrer_bps: 1_000,
+        };
+        assert_eq!(split.parts(1_000), (200, 200, 100));
+        // Shares beyond 100% are cut down to what is left of the fee
+        let over = TradingFeeSplit {
+            lp_bps: 0,
+            insurance_bps: 8_000,
+            treasury_bps: 8_000,
+            referrer_bps: 8_000,
+        };
+        assert_eq!(over.parts(1_000), (800, 200, 0));
+    }
+}
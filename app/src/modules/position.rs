@@ -1,4 +1,17 @@
-use crate::{PerpetualDEXState, errors::Error, modules::risk::RiskModule, types::*};
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{
+        account::AccountModule,
+        backstop::BackstopModule,
+        history::HistoryModule,
+        notification::NotificationModule,
+        risk::RiskModule,
+        stats::{FeeKind, StatsModule},
+    },
+    types::*,
+    utils,
+};
 use sails_rs::gstd::exec;
 use sails_rs::prelude::*;
 
@@ -13,19 +26,47 @@ impl PositionModule {
         size_delta_usd: u128,
         collateral_delta_usd: u128,
         execution_price_usd: u128,
+        tag: Option<u32>,
     ) -> Result<PositionKey, Error> {
         let key = PerpetualDEXState::get_position_key(account, &market, &collateral_token, is_long);
+        Self::increase_position_at(
+            key,
+            account,
+            market,
+            collateral_token,
+            is_long,
+            size_delta_usd,
+            collateral_delta_usd,
+            execution_price_usd,
+            tag,
+        )
+    }
+
+    /// `increase_position` of the position stored under `key`, canonical or a sub-position
+    pub fn increase_position_at(
+        key: PositionKey,
+        account: ActorId,
+        market: String,
+        collateral_token: String,
+        is_long: bool,
+        size_delta_usd: u128,
+        collateral_delta_usd: u128,
+        execution_price_usd: u128,
+        tag: Option<u32>,
+    ) -> Result<PositionKey, Error> {
         let now = exec::block_timestamp();
         let current_block = exec::block_height();
 
-        let (config, balance, existing_pos_opt) = {
+        let (config, balance, existing_pos_opt, (other_size_usd, other_collateral_usd)) = {
             let st = PerpetualDEXState::get();
 
+            st.ensure_trading_enabled(account)?;
             let config = st.market_configs.get(&market).ok_or(Error::MarketNotFound)?.clone();
             let balance = st.balances.get(&account).copied().unwrap_or(0);
             let existing = st.positions.get(&key).cloned();
+            let others = Self::side_exposure(&st, account, &market, is_long, key);
 
-            (config, balance, existing)
+            (config, balance, existing, others)
         };
 
         let total_cost = collateral_delta_usd;
@@ -35,12 +76,17 @@ impl PositionModule {
 
         let mut pos;
         let is_new_position;
+        let mut settled_fees_usd = 0;
 
         if let Some(mut existing) = existing_pos_opt {
-            RiskModule::settle_position_fees(&mut existing, &market, now)?;
+            if existing.account != account || existing.is_long != is_long || existing.market != market {
+                return Err(Error::Unauthorized);
+            }
+            settled_fees_usd = RiskModule::settle_position_fees(&mut existing, &market, now)?.total_fee_usd;
             pos = existing;
             is_new_position = false;
         } else {
+            PerpetualDEXState::get().ensure_position_capacity()?;
             pos = Position {
                 key,
                 account,
@@ -56,10 +102,14 @@ impl PositionModule {
                 increased_at_block: current_block,
                 decreased_at_block: 0,
                 last_fee_update: now,
+                funding_paid: 0,
+                funding_received: 0,
+                borrowing_paid: 0,
             };
             is_new_position = true;
         }
 
+        let tokens_before = Self::index_tokens(pos.size_usd, pos.entry_price_usd);
         if pos.size_usd > 0 {
             let old_notional = pos.size_usd;
             let new_notional = size_delta_usd;
@@ -80,6 +130,14 @@ impl PositionModule {
         pos.collateral_usd = pos.collateral_usd.saturating_add(collateral_delta_usd);
         pos.increased_at_block = current_block;
 
+        Self::check_side_limits(
+            &config,
+            other_size_usd.saturating_add(pos.size_usd),
+            other_collateral_usd.saturating_add(pos.collateral_usd),
+        )?;
+
+        let tokens_after = Self::index_tokens(pos.size_usd, pos.entry_price_usd);
+
         let mut st = PerpetualDEXState::get_mut();
 
         let pool = st
@@ -88,34 +146,28 @@ impl PositionModule {
             .or_insert_with(PoolAmounts::default);
 
         let total_liquidity = pool.liquidity_usd;
-        let max_allowed_oi_from_liquidity = total_liquidity.saturating_mul(config.reserve_factor_bps as u128) / 10_000;
+        let max_allowed_oi_from_liquidity =
+            total_liquidity.saturating_mul(config.reserve_factor_bps_for(is_long) as u128) / 10_000;
 
-        if is_long {
-            let new_oi = pool.long_oi_usd.saturating_add(size_delta_usd);
-
-            if new_oi > config.max_long_oi {
-                return Err(Error::MaxOpenInterestExceeded);
-            }
-
-            if new_oi > max_allowed_oi_from_liquidity {
-                return Err(Error::InsufficientLiquidity);
-            }
-
-            pool.long_oi_usd = new_oi;
+        let (oi_usd, oi_tokens) = if is_long {
+            (&mut pool.long_oi_usd, &mut pool.long_oi_tokens)
         } else {
-            let new_oi = pool.short_oi_usd.saturating_add(size_delta_usd);
-
-            if new_oi > config.max_short_oi {
-                return Err(Error::MaxOpenInterestExceeded);
-            }
+            (&mut pool.short_oi_usd, &mut pool.short_oi_tokens)
+        };
+        let new_oi = oi_usd.saturating_add(size_delta_usd);
+        let new_oi_tokens = oi_tokens.saturating_sub(tokens_before).saturating_add(tokens_after);
 
-            if new_oi > max_allowed_oi_from_liquidity {
-                return Err(Error::InsufficientLiquidity);
-            }
+        if !config.oi_within_caps(is_long, new_oi, new_oi_tokens) {
+            return Err(Error::MaxOpenInterestExceeded);
+        }
 
-            pool.short_oi_usd = new_oi;
+        if new_oi > max_allowed_oi_from_liquidity {
+            return Err(Error::InsufficientLiquidity);
         }
 
+        *oi_usd = new_oi;
+        *oi_tokens = new_oi_tokens;
+
         {
             let bal_entry = st.balances.entry(account).or_insert(0);
             if *bal_entry < total_cost {
@@ -136,6 +188,25 @@ impl PositionModule {
         if is_new_position {
             st.account_positions.entry(account).or_insert_with(Vec::new).push(key);
         }
+        StatsModule::record_trade(&mut st.trader_stats, account, size_delta_usd, 0);
+        HistoryModule::record_fill(
+            &mut st,
+            account,
+            FillRecord {
+                position_key: key,
+                market,
+                is_long,
+                kind: FillKind::Increase,
+                size_usd: size_delta_usd,
+                price: execution_price_usd,
+                trading_fee_usd: 0,
+                settled_fees_usd,
+                realized_pnl: 0,
+                timestamp: now,
+                block: current_block,
+                tag,
+            },
+        );
 
         st.positions.insert(key, pos);
 
@@ -150,25 +221,91 @@ impl PositionModule {
         size_delta_usd: u128,
         collateral_delta_usd: u128,
         execution_price_usd: u128,
+    ) -> Result<PositionKey, Error> {
+        Self::decrease_position_to(
+            account,
+            account,
+            market,
+            collateral_token,
+            is_long,
+            size_delta_usd,
+            collateral_delta_usd,
+            execution_price_usd,
+        )
+    }
+
+    /// `decrease_position` crediting the released collateral and PnL to `receiver`'s balance
+    pub fn decrease_position_to(
+        account: ActorId,
+        receiver: ActorId,
+        market: String,
+        collateral_token: String,
+        is_long: bool,
+        size_delta_usd: u128,
+        collateral_delta_usd: u128,
+        execution_price_usd: u128,
     ) -> Result<PositionKey, Error> {
         let key = PerpetualDEXState::get_position_key(account, &market, &collateral_token, is_long);
+        Self::decrease_position_at(key, receiver, size_delta_usd, collateral_delta_usd, execution_price_usd)
+    }
+
+    /// `decrease_position_to` of the position stored under `key`, canonical or a sub-position
+    pub fn decrease_position_at(
+        key: PositionKey,
+        receiver: ActorId,
+        size_delta_usd: u128,
+        collateral_delta_usd: u128,
+        execution_price_usd: u128,
+    ) -> Result<PositionKey, Error> {
+        Self::decrease_position_charged(
+            key,
+            receiver,
+            size_delta_usd,
+            collateral_delta_usd,
+            execution_price_usd,
+            0,
+            None,
+        )
+    }
+
+    /// `decrease_position_at` withholding `fee_usd` from the payout into the position owner's
+    /// balance, for the caller to collect as a trading fee. Fails, before any change, when the
+    /// payout can't cover the fee. The fill is labelled with `tag`.
+    pub fn decrease_position_charged(
+        key: PositionKey,
+        receiver: ActorId,
+        size_delta_usd: u128,
+        collateral_delta_usd: u128,
+        execution_price_usd: u128,
+        fee_usd: u128,
+        tag: Option<u32>,
+    ) -> Result<PositionKey, Error> {
         let now = exec::block_timestamp();
         let current_block = exec::block_height();
 
         let (config, mut pos) = {
             let st = PerpetualDEXState::get();
 
-            let config = st.market_configs.get(&market).ok_or(Error::MarketNotFound)?.clone();
             let pos = st.positions.get(&key).cloned().ok_or(Error::PositionNotFound)?;
+            let config = st.market_configs.get(&pos.market).ok_or(Error::MarketNotFound)?.clone();
 
             (config, pos)
         };
+        let (account, market, is_long) = (pos.account, pos.market.clone(), pos.is_long);
 
-        RiskModule::settle_position_fees(&mut pos, &market, now)?;
+        let settled_fees_usd = RiskModule::settle_position_fees(&mut pos, &market, now)?.total_fee_usd;
 
         if size_delta_usd > pos.size_usd {
             return Err(Error::InsufficientPositionSize);
         }
+        // Never leave a residual below the dust threshold; close it entirely instead
+        let size_delta_usd = if pos.size_usd - size_delta_usd < config.min_position_size_usd {
+            pos.size_usd
+        } else {
+            size_delta_usd
+        };
+        let collateral_delta_usd =
+            Self::released_collateral(pos.size_usd, pos.collateral_usd, size_delta_usd, collateral_delta_usd);
         if collateral_delta_usd > pos.collateral_usd {
             return Err(Error::InsufficientCollateral);
         }
@@ -180,6 +317,8 @@ impl PositionModule {
             (total_pnl.saturating_mul(size_delta_usd as i128)) / (pos.size_usd as i128)
         };
 
+        let tokens_released = Self::index_tokens(pos.size_usd, pos.entry_price_usd)
+            .saturating_sub(Self::index_tokens(pos.size_usd - size_delta_usd, pos.entry_price_usd));
         pos.size_usd = pos.size_usd.saturating_sub(size_delta_usd);
         pos.collateral_usd = pos.collateral_usd.saturating_sub(collateral_delta_usd);
         pos.decreased_at_block = current_block;
@@ -191,6 +330,7 @@ impl PositionModule {
             let loss = pnl_partial.unsigned_abs();
             payout_usd = payout_usd.saturating_sub(payout_usd.min(loss));
         }
+        payout_usd = payout_usd.checked_sub(fee_usd).ok_or(Error::InsufficientCollateral)?;
 
         let mut st = PerpetualDEXState::get_mut();
 
@@ -201,39 +341,220 @@ impl PositionModule {
 
         if is_long {
             pool.long_oi_usd = pool.long_oi_usd.saturating_sub(size_delta_usd);
+            pool.long_oi_tokens = pool.long_oi_tokens.saturating_sub(tokens_released);
         } else {
             pool.short_oi_usd = pool.short_oi_usd.saturating_sub(size_delta_usd);
+            pool.short_oi_tokens = pool.short_oi_tokens.saturating_sub(tokens_released);
         }
 
         if pnl_partial > 0 {
             let pnl_usd = pnl_partial as u128;
-            pool.liquidity_usd = pool.liquidity_usd.saturating_sub(pnl_usd);
+            BackstopModule::draw_liquidity(&mut st, &market, pnl_usd);
         } else if pnl_partial < 0 {
             let loss_usd = pnl_partial.unsigned_abs();
             pool.liquidity_usd = pool.liquidity_usd.saturating_add(loss_usd);
         }
 
         {
-            let bal = st.balances.entry(account).or_insert(0);
+            let bal = st.balances.entry(receiver).or_insert(0);
             *bal = bal.saturating_add(payout_usd);
         }
+        if fee_usd > 0 {
+            let bal = st.balances.entry(account).or_insert(0);
+            *bal = bal.saturating_add(fee_usd);
+        }
+        StatsModule::record_trade(&mut st.trader_stats, account, size_delta_usd, pnl_partial);
+        AccountModule::queue_profit_sweep(&mut st, receiver, pnl_partial);
+        HistoryModule::record_fill(
+            &mut st,
+            account,
+            FillRecord {
+                position_key: key,
+                market,
+                is_long,
+                kind: FillKind::Decrease,
+                size_usd: size_delta_usd,
+                price: execution_price_usd,
+                trading_fee_usd: 0,
+                settled_fees_usd,
+                realized_pnl: pnl_partial,
+                timestamp: now,
+                block: current_block,
+                tag,
+            },
+        );
 
         if pos.size_usd > 0 {
             pos.liquidation_price_usd = Self::calculate_liquidation_price(&pos, config.liquidation_threshold_bps);
             st.positions.insert(key, pos);
         } else {
-            st.positions.remove(&key);
-            if let Some(vec) = st.account_positions.get_mut(&account) {
-                if let Some(i) = vec.iter().position(|k| *k == key) {
-                    vec.swap_remove(i);
-                }
-            }
+            Self::remove_position(&mut st, key, account);
         }
 
         Ok(key)
     }
 
-    fn calculate_pnl(pos: &Position, current_price_usd: u128) -> i128 {
+    /// Move `amount_usd` of collateral into (`is_add`) or out of a position without changing its
+    /// size. Pending fees are settled first. Removal must leave at least the market's minimum
+    /// collateral, stay within max leverage and keep the position clear of liquidation at `price_usd`.
+    pub fn adjust_collateral(
+        account: ActorId,
+        key: PositionKey,
+        amount_usd: u128,
+        is_add: bool,
+        price_usd: u128,
+    ) -> Result<Position, Error> {
+        if amount_usd == 0 {
+            return Err(Error::InvalidCollateralAmount);
+        }
+        let pos = Self::settled_owned_position(account, &key)?;
+        Self::apply_collateral_delta(pos, amount_usd, is_add, price_usd)
+    }
+
+    /// Add or remove collateral so the position's leverage becomes `leverage_x`, after settling
+    /// pending fees. Same checks as `adjust_collateral`; a position already at the target is unchanged.
+    pub fn set_target_leverage(
+        account: ActorId,
+        key: PositionKey,
+        leverage_x: u8,
+        price_usd: u128,
+    ) -> Result<Position, Error> {
+        if leverage_x == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let pos = Self::settled_owned_position(account, &key)?;
+        let target_collateral = pos.size_usd / leverage_x as u128;
+        match target_collateral.cmp(&pos.collateral_usd) {
+            core::cmp::Ordering::Greater => {
+                let delta = target_collateral - pos.collateral_usd;
+                Self::apply_collateral_delta(pos, delta, true, price_usd)
+            }
+            core::cmp::Ordering::Less => {
+                let delta = pos.collateral_usd - target_collateral;
+                Self::apply_collateral_delta(pos, delta, false, price_usd)
+            }
+            core::cmp::Ordering::Equal => {
+                PerpetualDEXState::get_mut().positions.insert(key, pos.clone());
+                Ok(pos)
+            }
+        }
+    }
+
+    /// Caller's position with pending fees settled (not yet written back)
+    fn settled_owned_position(account: ActorId, key: &PositionKey) -> Result<Position, Error> {
+        let mut pos = Self::get_position(key)?;
+        if pos.account != account {
+            return Err(Error::Unauthorized);
+        }
+        let market = pos.market.clone();
+        RiskModule::settle_position_fees(&mut pos, &market, exec::block_timestamp())?;
+        Ok(pos)
+    }
+
+    fn apply_collateral_delta(
+        mut pos: Position,
+        amount_usd: u128,
+        is_add: bool,
+        price_usd: u128,
+    ) -> Result<Position, Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        let config = st.market_configs.get(&pos.market).ok_or(Error::MarketNotFound)?;
+        let balance = st.balances.entry(pos.account).or_insert(0);
+        if is_add {
+            if *balance < amount_usd {
+                return Err(Error::InsufficientBalance);
+            }
+            *balance -= amount_usd;
+            pos.collateral_usd = pos.collateral_usd.saturating_add(amount_usd);
+        } else {
+            if amount_usd >= pos.collateral_usd {
+                return Err(Error::InsufficientCollateral);
+            }
+            pos.collateral_usd -= amount_usd;
+            if pos.collateral_usd < config.min_collateral_usd {
+                return Err(Error::InsufficientCollateral);
+            }
+            let leverage_bps = pos.size_usd.saturating_mul(10_000) / pos.collateral_usd;
+            if leverage_bps > (config.max_leverage as u128).saturating_mul(10_000) {
+                return Err(Error::MaxLeverageExceeded);
+            }
+            let (other_size_usd, other_collateral_usd) =
+                Self::side_exposure(st, pos.account, &pos.market, pos.is_long, pos.key);
+            Self::check_side_limits(
+                config,
+                other_size_usd.saturating_add(pos.size_usd),
+                other_collateral_usd.saturating_add(pos.collateral_usd),
+            )?;
+            let pool = st.pool_amounts.get(&pos.market).ok_or(Error::MarketNotFound)?;
+            if RiskModule::is_liquidatable(&pos, pool, config, price_usd, now)? {
+                return Err(Error::InsufficientCollateral);
+            }
+            *balance = balance.saturating_add(amount_usd);
+        }
+
+        pos.liquidation_price_usd = Self::calculate_liquidation_price(&pos, config.liquidation_threshold_bps);
+        st.positions.insert(pos.key, pos.clone());
+        Ok(pos)
+    }
+
+    /// Collateral a decrease of `size_delta_usd` releases: everything left on a full close, the
+    /// requested amount on a partial one, or the closed share when none is requested
+    fn released_collateral(size_usd: Usd, collateral_usd: Usd, size_delta_usd: Usd, requested_usd: Usd) -> Usd {
+        if size_delta_usd >= size_usd {
+            collateral_usd
+        } else if requested_usd == 0 {
+            collateral_usd.saturating_mul(size_delta_usd) / size_usd
+        } else {
+            requested_usd
+        }
+    }
+
+    /// Index token amount (USD_SCALE precision) of `size_usd` at `price_usd`
+    fn index_tokens(size_usd: Usd, price_usd: u128) -> u128 {
+        if price_usd == 0 {
+            return 0;
+        }
+        size_usd.saturating_mul(USD_SCALE) / price_usd
+    }
+
+    /// Size and collateral of the account's positions on a market side other than `except`, so
+    /// sub-positions are held to the same limits as one netted position
+    fn side_exposure(
+        st: &PerpetualDEXState,
+        account: ActorId,
+        market: &str,
+        is_long: bool,
+        except: PositionKey,
+    ) -> (Usd, Usd) {
+        st.account_positions
+            .get(&account)
+            .into_iter()
+            .flatten()
+            .filter(|k| **k != except)
+            .filter_map(|k| st.positions.get(k))
+            .filter(|p| p.market == market && p.is_long == is_long)
+            .fold((0, 0), |(size, collateral), p| {
+                (size.saturating_add(p.size_usd), collateral.saturating_add(p.collateral_usd))
+            })
+    }
+
+    /// Max position size and leverage bands, applied to an account's total size and collateral
+    /// on one market side
+    fn check_side_limits(config: &MarketConfig, size_usd: Usd, collateral_usd: Usd) -> Result<(), Error> {
+        if config.max_position_size_usd > 0 && size_usd > config.max_position_size_usd {
+            return Err(Error::MaxPositionSizeExceeded);
+        }
+        if collateral_usd > 0
+            && !config.leverage_bands_allow(size_usd, size_usd.saturating_mul(10_000) / collateral_usd)
+        {
+            return Err(Error::LeverageBandExceeded);
+        }
+        Ok(())
+    }
+
+    pub fn calculate_pnl(pos: &Position, current_price_usd: u128) -> i128 {
         if pos.size_usd == 0 || pos.entry_price_usd == 0 {
             return 0;
         }
@@ -265,7 +586,7 @@ impl PositionModule {
         }
     }
 
-    fn calculate_liquidation_price(pos: &Position, liq_bps: u16) -> u128 {
+    pub fn calculate_liquidation_price(pos: &Position, liq_bps: u16) -> u128 {
         if pos.size_usd == 0 || pos.entry_price_usd == 0 {
             return 0;
         }
@@ -281,6 +602,20 @@ impl PositionModule {
         }
     }
 
+    /// Settle a position's pending funding and borrowing into its collateral without trading.
+    /// Returns the net fee settled (negative = credited).
+    pub fn settle_fees(key: &PositionKey, now: u64) -> Result<i128, Error> {
+        let mut pos = Self::get_position(key)?;
+        let market = pos.market.clone();
+        let fees = RiskModule::settle_position_fees(&mut pos, &market, now)?;
+
+        let mut st = PerpetualDEXState::get_mut();
+        let liq_bps = st.market_configs.get(&market).ok_or(Error::MarketNotFound)?.liquidation_threshold_bps;
+        pos.liquidation_price_usd = Self::calculate_liquidation_price(&pos, liq_bps);
+        st.positions.insert(*key, pos);
+        Ok(fees.total_fee_usd)
+    }
+
     pub fn get_position(key: &PositionKey) -> Result<Position, Error> {
         let st = PerpetualDEXState::get();
         st.positions.get(key).cloned().ok_or(Error::PositionNotFound)
@@ -295,6 +630,22 @@ impl PositionModule {
             .collect()
     }
 
+    /// A market's positions sorted by key and starting after `after`, cut to `limits`
+    /// with a continuation cursor.
+    pub fn get_market_positions(market: &str, after: Option<PositionKey>, limits: ViewLimits) -> PositionPage {
+        let st = PerpetualDEXState::get();
+        let mut matching: Vec<&Position> = st
+            .positions
+            .values()
+            .filter(|p| p.market == market && after.is_none_or(|a| p.key > a))
+            .collect();
+        matching.sort_by_key(|p| p.key);
+
+        let (positions, truncated) = utils::take_within_limits(matching.into_iter().cloned(), &limits);
+        let next_cursor = if truncated { positions.last().map(|p| p.key) } else { None };
+        PositionPage { positions, next_cursor }
+    }
+
     pub fn get_position_pnl(key: &PositionKey, current_price: u128) -> Result<i128, Error> {
         let pos = Self::get_position(key)?;
         Ok(Self::calculate_pnl(&pos, current_price))
@@ -306,7 +657,7 @@ impl PositionModule {
         liquidator: ActorId,
         position_key: PositionKey,
         execution_price_usd: u128,
-        liquidation_fee_bps: u16,
+        config: &MarketConfig,
     ) -> Result<(PositionKey, u128), Error> {
         let now = exec::block_timestamp();
 
@@ -323,28 +674,32 @@ impl PositionModule {
         };
 
         // Settle fees first
-        RiskModule::settle_position_fees(&mut pos, &market, now)?;
+        let settled_fees_usd = RiskModule::settle_position_fees(&mut pos, &market, now)?.total_fee_usd;
 
         // Calculate PnL
         let total_pnl = Self::calculate_pnl(&pos, execution_price_usd);
 
-        // Calculate liquidation fee (from remaining collateral)
-        let liquidation_fee = pos.collateral_usd.saturating_mul(liquidation_fee_bps as u128) / 10_000;
+        // Calculate liquidation fee (from remaining collateral, within the market's floor and cap)
+        let liquidation_fee = config.liquidation_fee_usd(pos.collateral_usd);
 
         // Remaining collateral after liquidation fee
         let remaining_collateral = pos.collateral_usd.saturating_sub(liquidation_fee);
 
         // Calculate payout to position owner (collateral - fee + pnl)
         let mut payout_to_owner = remaining_collateral;
+        let mut shortfall_usd = 0;
         if total_pnl >= 0 {
             payout_to_owner = payout_to_owner.saturating_add(total_pnl as u128);
         } else {
             let loss = total_pnl.unsigned_abs();
+            shortfall_usd = loss.saturating_sub(payout_to_owner);
             payout_to_owner = payout_to_owner.saturating_sub(payout_to_owner.min(loss));
         }
+        let collateral_usd = pos.collateral_usd;
 
         // Save position data before mutating state
         let size_usd = pos.size_usd;
+        let size_tokens = Self::index_tokens(pos.size_usd, pos.entry_price_usd);
         let is_long = pos.is_long;
 
         let mut st = PerpetualDEXState::get_mut();
@@ -357,19 +712,29 @@ impl PositionModule {
         // Update pool OI
         if is_long {
             pool.long_oi_usd = pool.long_oi_usd.saturating_sub(size_usd);
+            pool.long_oi_tokens = pool.long_oi_tokens.saturating_sub(size_tokens);
         } else {
             pool.short_oi_usd = pool.short_oi_usd.saturating_sub(size_usd);
+            pool.short_oi_tokens = pool.short_oi_tokens.saturating_sub(size_tokens);
         }
 
         // Update pool liquidity based on PnL
         if total_pnl > 0 {
             let pnl_usd = total_pnl as u128;
-            pool.liquidity_usd = pool.liquidity_usd.saturating_sub(pnl_usd);
+            BackstopModule::draw_liquidity(&mut st, &market, pnl_usd);
         } else if total_pnl < 0 {
             let loss_usd = total_pnl.unsigned_abs();
             pool.liquidity_usd = pool.liquidity_usd.saturating_add(loss_usd);
         }
 
+        // The collateral did not cover the loss; the insurance fund backs the gap as far as it can
+        if shortfall_usd > 0 {
+            let covered = shortfall_usd.min(st.insurance_fund_usd);
+            st.insurance_fund_usd -= covered;
+            let uncovered = shortfall_usd - covered;
+            BackstopModule::draw_liquidity(&mut st, &market, uncovered);
+        }
+
         // Pay liquidation fee to liquidator
         {
             let liquidator_bal = st.balances.entry(liquidator).or_insert(0);
@@ -381,15 +746,156 @@ impl PositionModule {
             let owner_bal = st.balances.entry(owner).or_insert(0);
             *owner_bal = owner_bal.saturating_add(payout_to_owner);
         }
-
-        // Remove position
-        st.positions.remove(&position_key);
-        if let Some(vec) = st.account_positions.get_mut(&owner) {
-            if let Some(i) = vec.iter().position(|k| *k == position_key) {
-                vec.swap_remove(i);
-            }
+        StatsModule::record_trade(&mut st.trader_stats, owner, size_usd, total_pnl);
+        {
+            let trader = StatsModule::trader(&mut st.trader_stats, owner);
+            trader.liquidation_fees = trader.liquidation_fees.saturating_add(liquidation_fee);
         }
+        StatsModule::record_fee(&mut st.market_fee_stats, &market, FeeKind::Liquidation, liquidation_fee, now);
+        let record = LiquidationRecord {
+            position_key,
+            account: owner,
+            market: market.clone(),
+            is_long,
+            size_usd,
+            collateral_usd,
+            price: execution_price_usd,
+            pnl: total_pnl,
+            liquidation_fee,
+            shortfall_usd,
+            liquidator,
+            timestamp: now,
+            block: exec::block_height(),
+        };
+        NotificationModule::push(&mut st.notifications, owner, NoticeKind::Liquidated(record.clone()));
+        HistoryModule::record_liquidation(&mut st, record);
+        HistoryModule::record_fill(
+            &mut st,
+            owner,
+            FillRecord {
+                position_key,
+                market: market.clone(),
+                is_long,
+                kind: FillKind::Liquidation,
+                size_usd,
+                price: execution_price_usd,
+                trading_fee_usd: 0,
+                settled_fees_usd,
+                realized_pnl: total_pnl,
+                timestamp: now,
+                block: exec::block_height(),
+                tag: None,
+            },
+        );
+
+        Self::remove_position(&mut st, position_key, owner);
 
         Ok((position_key, liquidation_fee))
     }
+
+    /// Drop a fully closed position along with its conditional closes and auto-top-up rule, so
+    /// a position later reopened under the same key starts clean
+    fn remove_position(st: &mut PerpetualDEXState, key: PositionKey, owner: ActorId) {
+        st.positions.remove(&key);
+        if let Some(vec) = st.account_positions.get_mut(&owner)
+            && let Some(i) = vec.iter().position(|k| *k == key)
+        {
+            vec.swap_remove(i);
+        }
+        st.conditional_closes.remove(&key);
+        st.auto_topups.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_side_limits_on_aggregate() {
+        let config = MarketConfig {
+            max_position_size_usd: 1_000_000,
+            leverage_bands: vec![LeverageBand {
+                above_leverage_x: 10,
+                max_position_size_usd: 100_000,
+            }],
+            ..Default::default()
+        };
+        // Two 60k sub-positions at 20x breach the 100k band together, though each fits alone
+        assert!(PositionModule::check_side_limits(&config, 60_000, 3_000).is_ok());
+        assert!(matches!(
+            PositionModule::check_side_limits(&config, 120_000, 6_000),
+            Err(Error::LeverageBandExceeded)
+        ));
+        assert!(PositionModule::check_side_limits(&config, 120_000, 12_000).is_ok());
+        assert!(matches!(
+            PositionModule::check_side_limits(&config, 1_000_001, 1_000_001),
+            Err(Error::MaxPositionSizeExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_leverage_bands_allow() {
+        let config = MarketConfig {
+            leverage_bands: vec![
+                LeverageBand {
+                    above_leverage_x: 10,
+                    max_position_size_usd: 100_000,
+                },
+                LeverageBand {
+                    above_leverage_x: 25,
+                    max_position_size_usd: 10_000,
+                },
+            ],
+            ..Default::default()
+        };
+        // At or below a band's leverage the band does not apply
+        assert!(config.leverage_bands_allow(1_000_000, 100_000));
+        // Above 10x only the first band's size cap applies
+        assert!(config.leverage_bands_allow(100_000, 200_000));
+        assert!(!config.leverage_bands_allow(100_001, 200_000));
+        // Above 25x the tighter band binds
+        assert!(config.leverage_bands_allow(10_000, 300_000));
+        assert!(!config.leverage_bands_allow(10_001, 300_000));
+        assert!(MarketConfig { leverage_bands: vec![], ..config }.leverage_bands_allow(u128::MAX, u128::MAX));
+    }
+
+    #[test]
+    fn test_oi_within_caps() {
+        let config = MarketConfig {
+            max_long_oi: 1_000,
+            max_short_oi: 500,
+            max_long_oi_tokens: 10 * USD_SCALE,
+            max_short_oi_tokens: 0,
+            ..Default::default()
+        };
+        assert!(config.oi_within_caps(true, 1_000, 10 * USD_SCALE));
+        assert!(!config.oi_within_caps(true, 1_001, 0));
+        // The token cap binds even while USD OI has room
+        assert!(!config.oi_within_caps(true, 100, 10 * USD_SCALE + 1));
+        // A zero token cap means no token limit
+        assert!(config.oi_within_caps(false, 500, u128::MAX));
+        assert!(!config.oi_within_caps(false, 501, 0));
+    }
+
+    #[test]
+    fn test_index_tokens() {
+        // $3_000 of notional at $2_000 is 1.5 tokens
+        assert_eq!(PositionModule::index_tokens(3_000 * USD_SCALE, 2_000 * USD_SCALE), 3 * USD_SCALE / 2);
+        assert_eq!(PositionModule::index_tokens(3_000 * USD_SCALE, 0), 0);
+        // Halving a position releases half its tokens, whatever the current price
+        let full = PositionModule::index_tokens(4_000 * USD_SCALE, 2_000 * USD_SCALE);
+        let half = PositionModule::index_tokens(2_000 * USD_SCALE, 2_000 * USD_SCALE);
+        assert_eq!(full - half, USD_SCALE);
+    }
+
+    #[test]
+    fn test_released_collateral() {
+        // Full close releases everything left
+        assert_eq!(PositionModule::released_collateral(1_000, 100, 1_000, 0), 100);
+        // Partial close without a request releases the closed share, keeping leverage
+        assert_eq!(PositionModule::released_collateral(1_000, 100, 250, 0), 25);
+        // An explicit request is honoured
+        assert_eq!(PositionModule::released_collateral(1_000, 100, 250, 60), 60);
+    }
 }
@@ -1,9 +1,12 @@
 use crate::{PerpetualDEXState, errors::Error, modules::oracle::OracleModule, types::*, utils};
+use sails_rs::{gstd::exec, prelude::*};
 
 #[derive(Clone, Debug)]
 pub struct QuoteResult {
     pub execution_price: u128,
     pub price_impact_usd: i128, // Positive = better for trader, negative = worse
+    /// Oracle mid the quote was derived from
+    pub mid_price: u128,
 }
 
 pub struct PricingModule;
@@ -25,10 +28,24 @@ impl PricingModule {
         let price_key = utils::price_key(market);
         let mid = OracleModule::mid(&price_key)?;
         let spread = OracleModule::spread(&price_key)?;
+        Self::quote_at(cfg, pool, st.block_flows.get(market), mid, spread, side, size_usd, is_increase)
+    }
+
+    /// Quote against given pool/config state and oracle mid/spread
+    fn quote_at(
+        cfg: &MarketConfig,
+        pool: &PoolAmounts,
+        flow: Option<&BlockFlow>,
+        mid: u128,
+        spread: u128,
+        side: &OrderSide,
+        size_usd: u128,
+        is_increase: bool,
+    ) -> Result<QuoteResult, Error> {
         let ask = mid.saturating_add(spread / 2);
         let bid = mid.saturating_sub(spread / 2);
 
-        let price_impact_usd = Self::calculate_price_impact_usd(pool, cfg, side, size_usd, is_increase)?;
+        let price_impact_usd = Self::block_aggregated_impact_usd(flow, pool, cfg, side, size_usd, is_increase)?;
 
         // Convert impact to basis points for price adjustment
         let price_impact_bps = if size_usd > 0 {
@@ -73,9 +90,80 @@ impl PricingModule {
         Ok(QuoteResult {
             execution_price,
             price_impact_usd,
+            mid_price: mid,
         })
     }
 
+    /// Synthetic depth for an oracle-priced market: for each level, the largest opening size on
+    /// each side whose execution price stays within `bps` of mid, found by inverting the quote
+    /// (spread plus price impact) with a binary search to 1 USD. Sizes are capped by the
+    /// remaining OI headroom (OI caps and reserve factor).
+    pub fn synthetic_depth(market: &str, levels_bps: &[u16]) -> Result<Vec<DepthLevel>, Error> {
+        let st = PerpetualDEXState::get();
+        let cfg = st.market_configs.get(market).ok_or(Error::MarketNotFound)?;
+        let pool = st.pool_amounts.get(market).ok_or(Error::MarketNotFound)?;
+        let flow = st.block_flows.get(market);
+
+        let price_key = utils::price_key(market);
+        let mid = OracleModule::mid(&price_key)?;
+        let spread = OracleModule::spread(&price_key)?;
+
+        let headroom = |is_long: bool| {
+            let (cap_usd, cap_tokens) = cfg.oi_caps(is_long);
+            let (oi, oi_tokens) = if is_long {
+                (pool.long_oi_usd, pool.long_oi_tokens)
+            } else {
+                (pool.short_oi_usd, pool.short_oi_tokens)
+            };
+            let reserve = pool.liquidity_usd.saturating_mul(cfg.reserve_factor_bps_for(is_long) as u128) / 10_000;
+            let usd_headroom = cap_usd.min(reserve).saturating_sub(oi);
+            if cap_tokens == 0 {
+                return usd_headroom;
+            }
+            usd_headroom.min(cap_tokens.saturating_sub(oi_tokens).saturating_mul(mid) / USD_SCALE)
+        };
+        let (long_headroom, short_headroom) = (headroom(true), headroom(false));
+
+        levels_bps
+            .iter()
+            .map(|&bps| {
+                let max_size = |side: OrderSide, headroom: u128| -> Result<u128, Error> {
+                    let limit = mid.saturating_mul(bps as u128) / 10_000;
+                    let within = |size: u128| -> Result<bool, Error> {
+                        let price = Self::quote_at(cfg, pool, flow, mid, spread, &side, size, true)?.execution_price;
+                        // Only adverse deviation counts; an improving fill is always within the level
+                        let adverse = match side {
+                            OrderSide::Long => price.saturating_sub(mid),
+                            OrderSide::Short => mid.saturating_sub(price),
+                        };
+                        Ok(adverse <= limit)
+                    };
+                    if headroom == 0 || !within(1)? {
+                        return Ok(0);
+                    }
+                    if within(headroom)? {
+                        return Ok(headroom);
+                    }
+                    let (mut lo, mut hi) = (1, headroom);
+                    while hi - lo > USD_SCALE {
+                        let probe = lo + (hi - lo) / 2;
+                        if within(probe)? {
+                            lo = probe;
+                        } else {
+                            hi = probe;
+                        }
+                    }
+                    Ok(lo)
+                };
+                Ok(DepthLevel {
+                    bps,
+                    long_size_usd: max_size(OrderSide::Long, long_headroom)?,
+                    short_size_usd: max_size(OrderSide::Short, short_headroom)?,
+                })
+            })
+            .collect()
+    }
+
     /// Calculates price impact in USD based on how the trade affects market balance.
     ///
     /// Formula: impact = (d_after^exp - d_before^exp) × factor × size / 10000
@@ -98,34 +186,56 @@ impl PricingModule {
             return Ok(0);
         }
 
-        // Calculate normalized imbalance before trade (in bps)
-        let total_oi_before = long_oi + short_oi;
-        if total_oi_before <= 0 {
-            return Ok(0);
-        }
+        let (new_long_oi, new_short_oi) = Self::oi_after(pool, side, size_usd, is_increase)?;
+        Self::impact_between(cfg, (long_oi, short_oi), (new_long_oi, new_short_oi), size_usd)
+    }
 
-        let d_before_abs = (long_oi - short_oi).abs() as u128;
-        let d_before_bps = (d_before_abs * 10_000) / (total_oi_before as u128);
+    /// Pool OI after applying a trade
+    fn oi_after(
+        pool: &PoolAmounts,
+        side: &OrderSide,
+        size_usd: u128,
+        is_increase: bool,
+    ) -> Result<(i128, i128), Error> {
+        let long_oi = pool.long_oi_usd as i128;
+        let short_oi = pool.short_oi_usd as i128;
 
         // Simulate OI change
         let delta = size_usd as i128;
 
-        let (new_long_oi, new_short_oi) = match (side, is_increase) {
-            (OrderSide::Long, true) => (long_oi + delta, short_oi),
+        match (side, is_increase) {
+            (OrderSide::Long, true) => Ok((long_oi + delta, short_oi)),
             (OrderSide::Long, false) => {
                 if delta > long_oi {
                     return Err(Error::InsufficientOpenInterest);
                 }
-                (long_oi - delta, short_oi)
+                Ok((long_oi - delta, short_oi))
             }
-            (OrderSide::Short, true) => (long_oi, short_oi + delta),
+            (OrderSide::Short, true) => Ok((long_oi, short_oi + delta)),
             (OrderSide::Short, false) => {
                 if delta > short_oi {
                     return Err(Error::InsufficientOpenInterest);
                 }
-                (long_oi, short_oi - delta)
+                Ok((long_oi, short_oi - delta))
             }
-        };
+        }
+    }
+
+    /// Impact of moving the pool from `before` to `after` (long OI, short OI) with `size_usd` of flow
+    fn impact_between(
+        cfg: &MarketConfig,
+        (long_oi, short_oi): (i128, i128),
+        (new_long_oi, new_short_oi): (i128, i128),
+        size_usd: u128,
+    ) -> Result<i128, Error> {
+        // Calculate normalized imbalance before trade (in bps)
+        let total_oi_before = long_oi + short_oi;
+        if total_oi_before <= 0 {
+            return Ok(0);
+        }
+
+        let d_before_abs = (long_oi - short_oi).abs() as u128;
+        let d_before_bps = (d_before_abs * 10_000) / (total_oi_before as u128);
 
         let total_oi_after = new_long_oi + new_short_oi;
         if total_oi_after <= 0 {
@@ -172,6 +282,88 @@ impl PricingModule {
         Ok(price_impact_usd.max(-max_impact).min(max_impact))
     }
 
+    /// Price impact with same-block aggregation.
+    ///
+    /// Splitting an order into many small ones inside a block would otherwise dodge the
+    /// convex impact curve. Within a block, impact is computed for the block's net flow
+    /// (block-start OI to OI after this trade, over the absolute net size), and this trade
+    /// pays the difference to what earlier trades in the block already paid. Netting keeps a
+    /// round trip from being charged as if both legs pushed the pool the same way.
+    fn block_aggregated_impact_usd(
+        flow: Option<&BlockFlow>,
+        pool: &PoolAmounts,
+        cfg: &MarketConfig,
+        side: &OrderSide,
+        size_usd: u128,
+        is_increase: bool,
+    ) -> Result<i128, Error> {
+        match flow.filter(|f| f.block == exec::block_height() && f.trades > 0) {
+            Some(flow) => Self::marginal_impact_usd(flow, pool, cfg, side, size_usd, is_increase),
+            None => Self::calculate_price_impact_usd(pool, cfg, side, size_usd, is_increase),
+        }
+    }
+
+    /// Impact of the block's net flow including this trade, less what the block already paid
+    fn marginal_impact_usd(
+        flow: &BlockFlow,
+        pool: &PoolAmounts,
+        cfg: &MarketConfig,
+        side: &OrderSide,
+        size_usd: u128,
+        is_increase: bool,
+    ) -> Result<i128, Error> {
+        let after = Self::oi_after(pool, side, size_usd, is_increase)?;
+        let net_flow = flow.net_flow_usd.saturating_add(Self::signed_flow(side, size_usd, is_increase));
+        let aggregate = Self::impact_between(
+            cfg,
+            (flow.long_oi_start as i128, flow.short_oi_start as i128),
+            after,
+            net_flow.unsigned_abs(),
+        )?;
+        let marginal = aggregate.saturating_sub(flow.charged_impact_usd);
+
+        let max_impact = (size_usd as i128) / 10;
+        Ok(marginal.max(-max_impact).min(max_impact))
+    }
+
+    /// Signed OI flow of a trade, positive toward longs
+    fn signed_flow(side: &OrderSide, size_usd: u128, is_increase: bool) -> i128 {
+        let size = size_usd as i128;
+        if matches!(side, OrderSide::Long) == is_increase { size } else { -size }
+    }
+
+    /// Record an OI change into the market's current-block flow. Pool trades pass the impact
+    /// they were charged; book fills and liquidations pass zero so the block-start OI stays exact.
+    /// The first trade of a block resets the flow to the pre-trade OI.
+    pub fn record_block_flow(market: &str, side: &OrderSide, size_usd: u128, is_increase: bool, impact_usd: i128) {
+        let block = exec::block_height();
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        let Some(pool) = st.pool_amounts.get(market) else {
+            return;
+        };
+        let flow = st.block_flows.entry(String::from(market)).or_default();
+        if flow.block != block || flow.trades == 0 {
+            // Pool already reflects this trade; rewind it to get the block-start OI
+            let (mut long_oi, mut short_oi) = (pool.long_oi_usd, pool.short_oi_usd);
+            match (side, is_increase) {
+                (OrderSide::Long, true) => long_oi = long_oi.saturating_sub(size_usd),
+                (OrderSide::Long, false) => long_oi = long_oi.saturating_add(size_usd),
+                (OrderSide::Short, true) => short_oi = short_oi.saturating_sub(size_usd),
+                (OrderSide::Short, false) => short_oi = short_oi.saturating_add(size_usd),
+            }
+            *flow = BlockFlow {
+                block,
+                long_oi_start: long_oi,
+                short_oi_start: short_oi,
+                ..Default::default()
+            };
+        }
+        flow.net_flow_usd = flow.net_flow_usd.saturating_add(Self::signed_flow(side, size_usd, is_increase));
+        flow.trades = flow.trades.saturating_add(1);
+        flow.charged_impact_usd = flow.charged_impact_usd.saturating_add(impact_usd);
+    }
+
     fn safe_power(base: u128, exp: u64) -> Result<u128, Error> {
         if exp == 0 {
             return Ok(1);
@@ -347,4 +539,83 @@ mod tests {
 
         assert!(matches!(result, Err(Error::InsufficientOpenInterest)));
     }
+
+    #[test]
+    fn test_block_aggregation_matches_single_order() {
+        let cfg = MarketConfig {
+            pi_factor_positive: 1,
+            pi_factor_negative: 1,
+            pi_exponent: 2,
+            ..Default::default()
+        };
+        let pool = PoolAmounts {
+            long_oi_usd: 600_000,
+            short_oi_usd: 400_000,
+            ..Default::default()
+        };
+        let single = PricingModule::calculate_price_impact_usd(&pool, &cfg, &OrderSide::Long, 100_000, true).unwrap();
+
+        // Same size split in two trades inside one block
+        let first = PricingModule::calculate_price_impact_usd(&pool, &cfg, &OrderSide::Long, 50_000, true).unwrap();
+        let flow = BlockFlow {
+            block: 1,
+            long_oi_start: 600_000,
+            short_oi_start: 400_000,
+            net_flow_usd: 50_000,
+            trades: 1,
+            charged_impact_usd: first,
+        };
+        let mid_pool = PoolAmounts {
+            long_oi_usd: 650_000,
+            ..pool.clone()
+        };
+        let second =
+            PricingModule::marginal_impact_usd(&flow, &mid_pool, &cfg, &OrderSide::Long, 50_000, true).unwrap();
+        let naive_second =
+            PricingModule::calculate_price_impact_usd(&mid_pool, &cfg, &OrderSide::Long, 50_000, true).unwrap();
+
+        assert!(single < 0);
+        assert_eq!(first + second, single);
+        assert!(first + naive_second > single, "splitting should not pay less impact");
+    }
+
+    #[test]
+    fn test_block_aggregation_nets_round_trip() {
+        let cfg = MarketConfig {
+            pi_factor_positive: 1,
+            pi_factor_negative: 1,
+            pi_exponent: 2,
+            ..Default::default()
+        };
+        let pool = PoolAmounts {
+            long_oi_usd: 600_000,
+            short_oi_usd: 400_000,
+            ..Default::default()
+        };
+        let single = PricingModule::calculate_price_impact_usd(&pool, &cfg, &OrderSide::Long, 100_000, true).unwrap();
+        let opened = PoolAmounts {
+            long_oi_usd: 700_000,
+            ..pool.clone()
+        };
+        let mut flow = BlockFlow {
+            block: 1,
+            long_oi_start: 600_000,
+            short_oi_start: 400_000,
+            net_flow_usd: 100_000,
+            trades: 1,
+            charged_impact_usd: single,
+        };
+
+        // Closing in the same block undoes the open: the block's net impact returns to zero
+        let close = PricingModule::marginal_impact_usd(&flow, &opened, &cfg, &OrderSide::Long, 100_000, false).unwrap();
+        assert_eq!(close, -single);
+        flow.net_flow_usd = 0;
+        flow.trades = 2;
+        flow.charged_impact_usd += close;
+
+        // Reopening pays the same as a single open, not impact over the gross 300k traded
+        let reopen = PricingModule::marginal_impact_usd(&flow, &pool, &cfg, &OrderSide::Long, 100_000, true).unwrap();
+        assert_eq!(single + close + reopen, single);
+        assert_eq!(reopen, single);
+    }
 }
@@ -0,0 +1,84 @@
+use crate::{PerpetualDEXState, errors::Error, types::*};
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Dead-man switch: once the admin has been inactive for the configured period,
+/// a threshold of guardians can vote a new admin in.
+pub struct RecoveryModule;
+
+impl RecoveryModule {
+    /// Replace the guardian set (admin only). Clears any pending votes.
+    pub fn set_config(caller: ActorId, cfg: RecoveryConfig) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        let mut guardians = cfg.guardians.clone();
+        guardians.sort();
+        guardians.dedup();
+        if cfg.inactivity_period_seconds > 0
+            && (cfg.threshold == 0 || cfg.threshold as usize > guardians.len() || guardians.contains(&caller))
+        {
+            return Err(Error::InvalidParameter);
+        }
+        st.recovery_config = RecoveryConfig { guardians, ..cfg };
+        st.recovery_votes.clear();
+        Ok(())
+    }
+
+    /// Guardian vote to rotate the admin to `new_admin`. Returns true if the rotation happened.
+    pub fn vote(caller: ActorId, new_admin: ActorId) -> Result<bool, Error> {
+        let now = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+
+        if !st.recovery_config.guardians.contains(&caller) {
+            return Err(Error::NotGuardian);
+        }
+        let period = st.recovery_config.inactivity_period_seconds;
+        let last_active = st.admin_last_active;
+        if period == 0 || now < last_active.saturating_add(period) {
+            return Err(Error::AdminStillActive);
+        }
+        if new_admin == st.admin || new_admin == ActorId::zero() {
+            return Err(Error::InvalidParameter);
+        }
+
+        // Votes from an earlier inactivity round are void once the admin has acted since
+        if st.recovery_round != last_active {
+            st.recovery_votes.clear();
+            st.recovery_round = last_active;
+        }
+
+        let votes = st.recovery_votes.entry(new_admin).or_default();
+        votes.insert(caller);
+        if (votes.len() as u32) < st.recovery_config.threshold {
+            return Ok(false);
+        }
+
+        st.admin = new_admin;
+        st.admin_last_active = now;
+        st.recovery_votes.clear();
+        Ok(true)
+    }
+
+    pub fn status() -> RecoveryStatus {
+        let st = PerpetualDEXState::get();
+        let last_active = st.admin_last_active;
+        let period = st.recovery_config.inactivity_period_seconds;
+        let votes = if st.recovery_round == last_active {
+            st.recovery_votes
+                .iter()
+                .map(|(candidate, voters)| (*candidate, voters.len() as u32))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        RecoveryStatus {
+            config: st.recovery_config.clone(),
+            admin: st.admin,
+            admin_last_active: last_active,
+            recovery_available_at: (period > 0).then(|| last_active.saturating_add(period)),
+            votes,
+        }
+    }
+}
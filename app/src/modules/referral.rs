@@ -0,0 +1,106 @@
+use crate::{PerpetualDEXState, errors::Error, types::*};
+use sails_rs::prelude::*;
+
+/// Referral code length bounds (ASCII letters, digits, `-` and `_`)
+pub const MIN_REFERRAL_CODE_LEN: usize = 3;
+pub const MAX_REFERRAL_CODE_LEN: usize = 32;
+/// Codes one account may register
+pub const MAX_REFERRAL_CODES_PER_ACCOUNT: u32 = 5;
+
+/// Referral codes: traders bound to a code's owner pay that owner a share of their trading fees
+pub struct ReferralModule;
+
+impl ReferralModule {
+    pub fn register_code(caller: ActorId, code: String) -> Result<(), Error> {
+        let valid_chars = code.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid_chars || !(MIN_REFERRAL_CODE_LEN..=MAX_REFERRAL_CODE_LEN).contains(&code.len()) {
+            return Err(Error::InvalidParameter);
+        }
+        let mut st = PerpetualDEXState::get_mut();
+        if st.referral_codes.contains_key(&code) {
+            return Err(Error::ReferralCodeTaken);
+        }
+        let count = st.referral_code_counts.entry(caller).or_default();
+        if *count >= MAX_REFERRAL_CODES_PER_ACCOUNT {
+            return Err(Error::TooManyReferralCodes);
+        }
+        *count += 1;
+        st.referral_codes.insert(code, caller);
+        Ok(())
+    }
+
+    pub fn ensure_code(code: &str) -> Result<(), Error> {
+        if !PerpetualDEXState::get().referral_codes.contains_key(code) {
+            return Err(Error::ReferralCodeNotFound);
+        }
+        Ok(())
+    }
+
+    pub fn set_fee_share(caller: ActorId, share_bps: u16) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if share_bps > 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        st.referral_fee_share_bps = share_bps;
+        Ok(())
+    }
+
+    /// Bind `account` to the owner of `code`, once an order carrying it has succeeded (the code
+    /// is checked with `ensure_code` before). An existing binding is kept; own and unknown codes
+    /// are ignored.
+    pub fn bind(account: ActorId, code: &str) {
+        let mut st = PerpetualDEXState::get_mut();
+        let Some(referrer) = st.referral_codes.get(code).copied() else {
+            return;
+        };
+        if referrer == account || st.referrers.contains_key(&account) {
+            return;
+        }
+        st.referrers.insert(account, referrer);
+        st.referral_stats.entry(referrer).or_default().referred_accounts += 1;
+    }
+
+    /// Credit the payer's referrer with its share of a collected trading fee.
+    /// Returns the part taken; the caller routes the rest as usual.
+    pub fn take_fee_share(st: &mut PerpetualDEXState, payer: ActorId, fee: Usd) -> Usd {
+        let Some(referrer) = st.referrers.get(&payer).copied() else {
+            return 0;
+        };
+        let share = fee.saturating_mul(st.referral_fee_share_bps as u128) / 10_000;
+        Self::credit(st, referrer, share)
+    }
+
+    /// Credit the payer's referrer with `amount`; returns the amount credited (0 without a referrer)
+    pub fn pay_referrer(st: &mut PerpetualDEXState, payer: ActorId, amount: Usd) -> Usd {
+        match st.referrers.get(&payer).copied() {
+            Some(referrer) => Self::credit(st, referrer, amount),
+            None => 0,
+        }
+    }
+
+    fn credit(st: &mut PerpetualDEXState, referrer: ActorId, share: Usd) -> Usd {
+        if share == 0 {
+            return 0;
+        }
+        let bal = st.balances.entry(referrer).or_insert(0);
+        *bal = bal.saturating_add(share);
+        let stats = st.referral_stats.entry(referrer).or_default();
+        stats.total_earned_usd = stats.total_earned_usd.saturating_add(share);
+        share
+    }
+
+    pub fn code_owner(code: &str) -> Option<ActorId> {
+        PerpetualDEXState::get().referral_codes.get(code).copied()
+    }
+
+    pub fn referrer_of(account: ActorId) -> Option<ActorId> {
+        PerpetualDEXState::get().referrers.get(&account).copied()
+    }
+
+    pub fn stats(referrer: ActorId) -> ReferralStats {
+        PerpetualDEXState::get().referral_stats.get(&referrer).cloned().unwrap_or_default()
+    }
+}
@@ -1,4 +1,20 @@
-use crate::{PerpetualDEXState, errors::Error, types::*};
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{
+        market::MarketModule,
+        notification::NotificationModule,
+        oracle::OracleModule,
+        position::PositionModule,
+        stats::{FeeKind, StatsModule},
+    },
+    types::*,
+    utils,
+};
+use sails_rs::{
+    collections::{BTreeMap, HashMap},
+    prelude::*,
+};
 
 #[derive(Clone, Debug, Default)]
 pub struct SettledFees {
@@ -14,23 +30,35 @@ impl RiskModule {
     ///
     /// Borrowing fees are calculated and collected per-position in settle_position_fees.
     pub fn accrue_pool(market: &str, current_time: u64) -> Result<(), Error> {
+        MarketModule::activate_scheduled_config(market);
+
         let mut st = PerpetualDEXState::get_mut();
         let cfg = st.market_configs.get(market).ok_or(Error::MarketNotFound)?.clone();
         let pool = st.pool_amounts.get_mut(market).ok_or(Error::MarketNotFound)?;
+        Self::advance_funding(pool, &cfg, current_time)
+    }
 
+    /// Move the pool's funding indices forward to `current_time`
+    fn advance_funding(pool: &mut PoolAmounts, cfg: &MarketConfig, current_time: u64) -> Result<(), Error> {
         let dt = current_time.saturating_sub(pool.last_funding_update);
         if dt == 0 {
             return Ok(());
         }
 
         // Calculate funding rate in microUSD/USD
-        let funding_rate_micro = Self::funding_rate_micro(pool, &cfg, dt)?;
+        let funding_rate_micro = Self::funding_rate_micro(pool, cfg, dt)?;
+
+        // Payers are charged the full rate; receivers get it net of the funding spread
+        let receiver_share_bps = 10_000 - cfg.funding_spread_bps.min(10_000) as i128;
+        let net_rate_micro = funding_rate_micro.saturating_mul(receiver_share_bps) / 10_000;
+        let (long_delta, short_delta) = if funding_rate_micro > 0 {
+            (funding_rate_micro, -net_rate_micro)
+        } else {
+            (net_rate_micro, -funding_rate_micro)
+        };
 
-        pool.accumulated_funding_long_per_usd =
-            pool.accumulated_funding_long_per_usd.saturating_add(funding_rate_micro);
-        pool.accumulated_funding_short_per_usd = pool
-            .accumulated_funding_short_per_usd
-            .saturating_sub(funding_rate_micro);
+        pool.accumulated_funding_long_per_usd = pool.accumulated_funding_long_per_usd.saturating_add(long_delta);
+        pool.accumulated_funding_short_per_usd = pool.accumulated_funding_short_per_usd.saturating_add(short_delta);
 
         pool.last_funding_update = current_time;
         Ok(())
@@ -39,10 +67,11 @@ impl RiskModule {
     /// Settles fees for a position and updates pool balances
     ///
     /// Architecture (single source of truth):
-    /// - Funding fees: zero-sum between long/short via claimable_fee_*
+    /// - Funding fees: zero-sum between long/short via claimable_fee_*, less the funding spread
     ///   - Calculated from accumulated_funding_*_per_usd indices (updated in accrue_pool)
     ///   - Longs pay → claimable_fee_usd_short++ (shorts can claim)
     ///   - Shorts pay → claimable_fee_usd_long++ (longs can claim)
+    ///   - funding_spread_bps of each payment → insurance fund
     ///
     /// - Borrowing fees: trader → LP claimable_fee_*
     ///   - Calculated per-position based on utilization
@@ -50,6 +79,7 @@ impl RiskModule {
     ///   - This ensures sum(position_fees) = LP_claimable (no double counting)
     pub fn settle_position_fees(pos: &mut Position, market: &str, current_time: u64) -> Result<SettledFees, Error> {
         let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
         let cfg = st.market_configs.get(market).ok_or(Error::MarketNotFound)?.clone();
         let pool = st.pool_amounts.get_mut(market).ok_or(Error::MarketNotFound)?;
 
@@ -72,6 +102,25 @@ impl RiskModule {
         if fees.funding_fee > 0 {
             // Position PAYS funding → opposite side can claim
             let payment = fees.funding_fee as u128;
+            StatsModule::record_funding(&mut st.funding_stats, market, pos.is_long, payment, current_time);
+            let trader = StatsModule::trader(&mut st.trader_stats, pos.account);
+            trader.funding_paid = trader.funding_paid.saturating_add(payment);
+            pos.funding_paid = pos.funding_paid.saturating_add(payment);
+            let alert_threshold = st.account_settings.get(&pos.account).and_then(|s| s.funding_alert_threshold_usd);
+            if alert_threshold.is_some_and(|threshold| payment >= threshold) {
+                NotificationModule::push(
+                    &mut st.notifications,
+                    pos.account,
+                    NoticeKind::FundingCharged {
+                        position_key: pos.key,
+                        market: market.to_string(),
+                        amount_usd: payment,
+                    },
+                );
+            }
+            let spread = payment.saturating_mul(cfg.funding_spread_bps.min(10_000) as u128) / 10_000;
+            st.insurance_fund_usd = st.insurance_fund_usd.saturating_add(spread);
+            let payment = payment - spread;
             if pos.is_long {
                 pool.claimable_fee_usd_short = pool.claimable_fee_usd_short.saturating_add(payment);
             } else {
@@ -80,6 +129,7 @@ impl RiskModule {
         } else if fees.funding_fee < 0 {
             // Position RECEIVES funding → deduct from our side's claimable
             let credit = (-fees.funding_fee) as u128;
+            let trader = StatsModule::trader(&mut st.trader_stats, pos.account);
             if pos.is_long {
                 if pool.claimable_fee_usd_long < credit {
                     // Insufficient funding pool - should not happen in normal operation
@@ -87,6 +137,9 @@ impl RiskModule {
                     let available = pool.claimable_fee_usd_long;
                     pool.claimable_fee_usd_long = 0;
                     pos.collateral_usd = pos.collateral_usd.saturating_add(available);
+                    trader.funding_received = trader.funding_received.saturating_add(available);
+                    pos.funding_received = pos.funding_received.saturating_add(available);
+                    StatsModule::record_funding_received(&mut st.funding_stats, market, available, current_time);
 
                     // Update fees to reflect what was actually paid.
                     fees.funding_fee = -(available as i128);
@@ -96,11 +149,17 @@ impl RiskModule {
                     return Ok(fees);
                 }
                 pool.claimable_fee_usd_long = pool.claimable_fee_usd_long.saturating_sub(credit);
+                trader.funding_received = trader.funding_received.saturating_add(credit);
+                pos.funding_received = pos.funding_received.saturating_add(credit);
+                StatsModule::record_funding_received(&mut st.funding_stats, market, credit, current_time);
             } else {
                 if pool.claimable_fee_usd_short < credit {
                     let available = pool.claimable_fee_usd_short;
                     pool.claimable_fee_usd_short = 0;
                     pos.collateral_usd = pos.collateral_usd.saturating_add(available);
+                    trader.funding_received = trader.funding_received.saturating_add(available);
+                    pos.funding_received = pos.funding_received.saturating_add(available);
+                    StatsModule::record_funding_received(&mut st.funding_stats, market, available, current_time);
 
                     fees.funding_fee = -(available as i128);
                     fees.total_fee_usd = fees.funding_fee.saturating_add(fees.borrowing_fee as i128);
@@ -108,13 +167,17 @@ impl RiskModule {
                     return Ok(fees);
                 }
                 pool.claimable_fee_usd_short = pool.claimable_fee_usd_short.saturating_sub(credit);
+                trader.funding_received = trader.funding_received.saturating_add(credit);
+                pos.funding_received = pos.funding_received.saturating_add(credit);
+                StatsModule::record_funding_received(&mut st.funding_stats, market, credit, current_time);
             }
         }
 
         // 2. BORROWING FEE (trader pays → goes to LP claimable)
         let dt = current_time.saturating_sub(pos.last_fee_update);
         if dt > 0 && pos.size_usd > 0 {
-            fees.borrowing_fee = Self::position_borrowing_fee(pos, pool, &cfg, dt)?;
+            let base_usd = Self::borrowing_base_usd(&st.positions, &st.account_positions, pos, &cfg);
+            fees.borrowing_fee = Self::position_borrowing_fee(base_usd, pool, &cfg, dt)?;
 
             // Add borrowing fee to LP claimable for this side.
             // This is the ONLY place where borrowing fees are calculated and added.
@@ -126,6 +189,16 @@ impl RiskModule {
 
             // Track total for statistics
             pool.total_borrowing_fees_usd = pool.total_borrowing_fees_usd.saturating_add(fees.borrowing_fee);
+            let trader = StatsModule::trader(&mut st.trader_stats, pos.account);
+            trader.borrowing_fees = trader.borrowing_fees.saturating_add(fees.borrowing_fee);
+            pos.borrowing_paid = pos.borrowing_paid.saturating_add(fees.borrowing_fee);
+            StatsModule::record_fee(
+                &mut st.market_fee_stats,
+                market,
+                FeeKind::Borrowing,
+                fees.borrowing_fee,
+                current_time,
+            );
         }
         pos.last_fee_update = current_time;
 
@@ -196,15 +269,49 @@ impl RiskModule {
         Ok(rate_micro)
     }
 
-    fn position_borrowing_fee(pos: &Position, pool: &PoolAmounts, cfg: &MarketConfig, dt: u64) -> Result<u128, Error> {
+    /// Notional the position pays borrowing on. With `net_borrowing_for_hedges`, an account holding
+    /// both sides of the market pays only on its net exposure: the larger side is charged on
+    /// `own_side - other_side` (pro-rata across its positions), the smaller side on nothing.
+    fn borrowing_base_usd(
+        positions: &BTreeMap<PositionKey, Position>,
+        account_positions: &HashMap<ActorId, Vec<PositionKey>>,
+        pos: &Position,
+        cfg: &MarketConfig,
+    ) -> Usd {
+        if !cfg.net_borrowing_for_hedges {
+            return pos.size_usd;
+        }
+        let (mut own_side, mut other_side) = (0u128, 0u128);
+        for other in account_positions
+            .get(&pos.account)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| positions.get(key))
+            .filter(|p| p.market == pos.market && p.key != pos.key)
+        {
+            if other.is_long == pos.is_long {
+                own_side = own_side.saturating_add(other.size_usd);
+            } else {
+                other_side = other_side.saturating_add(other.size_usd);
+            }
+        }
+        // `pos` may be a settled copy that differs from the stored one, so count it directly
+        own_side = own_side.saturating_add(pos.size_usd);
+        if own_side <= other_side {
+            return 0;
+        }
+        pos.size_usd.saturating_mul(own_side - other_side) / own_side
+    }
+
+    fn position_borrowing_fee(base_usd: Usd, pool: &PoolAmounts, cfg: &MarketConfig, dt: u64) -> Result<u128, Error> {
         // Use total pool liquidity as the base for utilization
         let liquidity = pool.liquidity_usd;
-        if liquidity == 0 {
+        if liquidity == 0 || base_usd == 0 {
             return Ok(0);
         }
 
         // Calculate utilization in bps
-        let util_bps = pos.size_usd.saturating_mul(10_000) / liquidity;
+        let util_bps = base_usd.saturating_mul(10_000) / liquidity;
 
         // Apply non-linear exponent to utilization
         let exponent = cfg.borrowing_exponent.max(1);
@@ -223,7 +330,7 @@ impl RiskModule {
         // Apply time factor: fee = size * rate * dt / year
         let seconds_per_year = 365 * 24 * 60 * 60u128;
         Ok(rate_bps
-            .saturating_mul(pos.size_usd)
+            .saturating_mul(base_usd)
             .saturating_mul(dt as u128)
             .saturating_div(seconds_per_year * 10_000))
     }
@@ -250,7 +357,9 @@ impl RiskModule {
         let borrowing_fee = {
             let dt = current_time.saturating_sub(pos.last_fee_update);
             if dt > 0 && pos.size_usd > 0 {
-                Self::position_borrowing_fee(pos, pool, cfg, dt)?
+                let st = PerpetualDEXState::get();
+                let base_usd = Self::borrowing_base_usd(&st.positions, &st.account_positions, pos, cfg);
+                Self::position_borrowing_fee(base_usd, pool, cfg, dt)?
             } else {
                 0
             }
@@ -260,6 +369,39 @@ impl RiskModule {
         Ok((funding_fee, borrowing_fee, total_fee))
     }
 
+    /// Copy of the market's pool with funding advanced to `current_time`, without touching state
+    pub fn accrued_pool_virtual(st: &PerpetualDEXState, market: &str, current_time: u64) -> Result<PoolAmounts, Error> {
+        let cfg = st.market_configs.get(market).ok_or(Error::MarketNotFound)?;
+        let mut pool = st.pool_amounts.get(market).cloned().ok_or(Error::MarketNotFound)?;
+        Self::advance_funding(&mut pool, cfg, current_time)?;
+        Ok(pool)
+    }
+
+    /// Unsettled funding and borrowing across all open positions of a market, as if the pool
+    /// were accrued and every position settled at `current_time`
+    pub fn market_pending_fees(market: &str, current_time: u64) -> Result<MarketPendingFees, Error> {
+        let st = PerpetualDEXState::get();
+        let cfg = st.market_configs.get(market).ok_or(Error::MarketNotFound)?;
+        let pool = Self::accrued_pool_virtual(&st, market, current_time)?;
+
+        let mut fees = MarketPendingFees {
+            market: String::from(market),
+            timestamp: current_time,
+            ..Default::default()
+        };
+        for pos in st.positions.values().filter(|p| p.market == market) {
+            let (funding, borrowing, _) = Self::calculate_pending_fees_virtual(pos, &pool, cfg, current_time)?;
+            if funding >= 0 {
+                fees.funding_owed_usd = fees.funding_owed_usd.saturating_add(funding as u128);
+            } else {
+                fees.funding_due_usd = fees.funding_due_usd.saturating_add(funding.unsigned_abs());
+            }
+            fees.borrowing_usd = fees.borrowing_usd.saturating_add(borrowing);
+            fees.open_positions += 1;
+        }
+        Ok(fees)
+    }
+
     /// Check if position is liquidatable AFTER applying pending fees.
     /// This is the correct way to check liquidation status.
     pub fn is_liquidatable(
@@ -269,13 +411,93 @@ impl RiskModule {
         current_price_usd: u128,
         current_time: u64,
     ) -> Result<bool, Error> {
-        if pos.size_usd == 0 || pos.entry_price_usd == 0 {
+        let Some(effective_collateral) = Self::effective_collateral(pos, pool, cfg, current_price_usd, current_time)?
+        else {
             return Ok(false);
+        };
+
+        // Liquidation threshold based on ORIGINAL collateral
+        let threshold = (pos.collateral_usd as i128).saturating_mul(cfg.liquidation_threshold_bps as i128) / 10_000;
+
+        Ok(effective_collateral <= threshold)
+    }
+
+    /// Margin call for a position inside the warning band: above the liquidation threshold but
+    /// within `margin_call_buffer_bps` of collateral of it. Isolated-margin check only.
+    pub fn margin_call(
+        pos: &Position,
+        pool: &PoolAmounts,
+        cfg: &MarketConfig,
+        current_price_usd: u128,
+        current_time: u64,
+    ) -> Result<Option<MarginCall>, Error> {
+        if cfg.margin_call_buffer_bps == 0 {
+            return Ok(None);
+        }
+        let Some(effective_collateral) = Self::effective_collateral(pos, pool, cfg, current_price_usd, current_time)?
+        else {
+            return Ok(None);
+        };
+
+        let liquidation_threshold_usd =
+            pos.collateral_usd.saturating_mul(cfg.liquidation_threshold_bps as u128) / 10_000;
+        let margin_call_threshold_usd = liquidation_threshold_usd
+            .saturating_add(pos.collateral_usd.saturating_mul(cfg.margin_call_buffer_bps as u128) / 10_000);
+        if effective_collateral <= liquidation_threshold_usd as i128
+            || effective_collateral > margin_call_threshold_usd as i128
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(MarginCall {
+            position_key: pos.key,
+            account: pos.account,
+            market: pos.market.clone(),
+            is_long: pos.is_long,
+            price: current_price_usd,
+            effective_collateral,
+            liquidation_threshold_usd,
+            margin_call_threshold_usd,
+            timestamp: current_time,
+        }))
+    }
+
+    /// Effective collateral over the liquidation threshold, in bps (10_000 = liquidatable).
+    /// Isolated-margin measure; `None` for empty positions or a zero threshold.
+    pub fn health_factor_bps(
+        pos: &Position,
+        pool: &PoolAmounts,
+        cfg: &MarketConfig,
+        current_price_usd: u128,
+        current_time: u64,
+    ) -> Result<Option<u32>, Error> {
+        let Some(effective_collateral) = Self::effective_collateral(pos, pool, cfg, current_price_usd, current_time)?
+        else {
+            return Ok(None);
+        };
+        let threshold = pos.collateral_usd.saturating_mul(cfg.liquidation_threshold_bps as u128) / 10_000;
+        if threshold == 0 {
+            return Ok(None);
+        }
+        let health = effective_collateral.max(0).saturating_mul(10_000) / threshold as i128;
+        Ok(Some(health.min(u32::MAX as i128) as u32))
+    }
+
+    /// Collateral + PnL - pending fees; `None` for empty positions
+    pub fn effective_collateral(
+        pos: &Position,
+        pool: &PoolAmounts,
+        cfg: &MarketConfig,
+        current_price_usd: u128,
+        current_time: u64,
+    ) -> Result<Option<i128>, Error> {
+        if pos.size_usd == 0 || pos.entry_price_usd == 0 {
+            return Ok(None);
         }
 
         let tokens_usdx = pos.size_usd.saturating_mul(USD_SCALE) / pos.entry_price_usd;
         if tokens_usdx == 0 {
-            return Ok(false);
+            return Ok(None);
         }
 
         // Calculate PnL
@@ -290,13 +512,124 @@ impl RiskModule {
         let (_, _, total_fee) = Self::calculate_pending_fees_virtual(pos, pool, cfg, current_time)?;
 
         // Effective collateral = initial collateral + PnL - fees
-        let effective_collateral = (pos.collateral_usd as i128)
-            .saturating_add(pnl)
-            .saturating_sub(total_fee);
+        Ok(Some(
+            (pos.collateral_usd as i128)
+                .saturating_add(pnl)
+                .saturating_sub(total_fee),
+        ))
+    }
 
-        // Liquidation threshold based on ORIGINAL collateral
-        let threshold = (pos.collateral_usd as i128).saturating_mul(cfg.liquidation_threshold_bps as i128) / 10_000;
+    /// Liquidation check honoring the owner's margin mode: isolated positions are checked
+    /// on their own collateral, cross-margin positions against account-level health.
+    pub fn is_liquidatable_for_owner(
+        st: &PerpetualDEXState,
+        pos: &Position,
+        current_price_usd: u128,
+        current_time: u64,
+    ) -> Result<bool, Error> {
+        let mode = st.account_settings.get(&pos.account).map(|s| s.margin_mode).unwrap_or_default();
+        if mode == MarginMode::Cross {
+            let margin = Self::account_margin(st, pos.account, current_time)?;
+            return Ok(margin.equity <= margin.maintenance_net as i128);
+        }
+        let cfg = st.market_configs.get(&pos.market).ok_or(Error::MarketNotFound)?;
+        let pool = st.pool_amounts.get(&pos.market).ok_or(Error::MarketNotFound)?;
+        Self::is_liquidatable(pos, pool, cfg, current_price_usd, current_time)
+    }
 
-        Ok(effective_collateral <= threshold)
+    /// Account-level margin across all positions, valued at the oracle mid.
+    ///
+    /// Maintenance per position is `collateral * liquidation_threshold_bps` (as in `is_liquidatable`).
+    /// Within a correlation group, the hedged share of notional `2 * min(long, short) / (long + short)`
+    /// waives `offset_bps` of the group's maintenance.
+    pub fn account_margin(st: &PerpetualDEXState, account: ActorId, current_time: u64) -> Result<AccountMargin, Error> {
+        let mut margin = AccountMargin::default();
+        // market -> (long notional, short notional, maintenance)
+        let mut per_market: BTreeMap<&str, (Usd, Usd, Usd)> = BTreeMap::new();
+
+        for key in st.account_positions.get(&account).into_iter().flatten() {
+            let Some(pos) = st.positions.get(key) else {
+                continue;
+            };
+            let cfg = st.market_configs.get(&pos.market).ok_or(Error::MarketNotFound)?;
+            let pool = st.pool_amounts.get(&pos.market).ok_or(Error::MarketNotFound)?;
+            let price = OracleModule::mid(&utils::price_key(&pos.market))?;
+
+            let pnl = PositionModule::calculate_pnl(pos, price);
+            let (_, _, fees) = Self::calculate_pending_fees_virtual(pos, pool, cfg, current_time)?;
+            margin.equity = margin
+                .equity
+                .saturating_add(pos.collateral_usd as i128)
+                .saturating_add(pnl)
+                .saturating_sub(fees);
+
+            let maintenance = pos.collateral_usd.saturating_mul(cfg.liquidation_threshold_bps as u128) / 10_000;
+            margin.maintenance_gross = margin.maintenance_gross.saturating_add(maintenance);
+
+            let entry = per_market.entry(pos.market.as_str()).or_default();
+            if pos.is_long {
+                entry.0 = entry.0.saturating_add(pos.size_usd);
+            } else {
+                entry.1 = entry.1.saturating_add(pos.size_usd);
+            }
+            entry.2 = entry.2.saturating_add(maintenance);
+        }
+
+        for group in st.correlation_groups.values() {
+            let (long, short, maintenance) = group
+                .markets
+                .iter()
+                .filter_map(|m| per_market.get(m.as_str()))
+                .fold((0u128, 0u128, 0u128), |acc, (l, s, m)| {
+                    (acc.0.saturating_add(*l), acc.1.saturating_add(*s), acc.2.saturating_add(*m))
+                });
+            let total = long.saturating_add(short);
+            if total == 0 {
+                continue;
+            }
+            let hedged_bps = long.min(short).saturating_mul(20_000) / total;
+            let offset = maintenance
+                .saturating_mul(hedged_bps)
+                .saturating_mul(group.offset_bps as u128)
+                / 100_000_000;
+            margin.maintenance_offset = margin.maintenance_offset.saturating_add(offset);
+        }
+
+        margin.maintenance_net = margin.maintenance_gross.saturating_sub(margin.maintenance_offset);
+        Ok(margin)
+    }
+
+    /// Create or replace a correlation group (admin only). A market may belong to one group.
+    pub fn set_correlation_group(caller: ActorId, group: CorrelationGroup) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if group.offset_bps > 10_000 || group.markets.len() < 2 {
+            return Err(Error::InvalidParameter);
+        }
+        for market in &group.markets {
+            if !st.markets.contains_key(market) {
+                return Err(Error::MarketNotFound);
+            }
+            let taken = st
+                .correlation_groups
+                .values()
+                .any(|g| g.group_id != group.group_id && g.markets.contains(market));
+            if taken {
+                return Err(Error::InvalidParameter);
+            }
+        }
+        st.correlation_groups.insert(group.group_id.clone(), group);
+        Ok(())
+    }
+
+    pub fn remove_correlation_group(caller: ActorId, group_id: &str) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.correlation_groups.remove(group_id).ok_or(Error::InvalidParameter)?;
+        Ok(())
     }
 }
@@ -0,0 +1,119 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{oracle::OracleModule, position::PositionModule},
+    types::*,
+    utils,
+};
+use sails_rs::{collections::BTreeMap, gstd::exec, prelude::*};
+
+/// Periodic Merkle commitments over account balances for off-chain solvency checks
+pub struct SolvencyModule;
+
+impl SolvencyModule {
+    pub fn set_epoch(caller: ActorId, epoch_seconds: u64) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.solvency_epoch_seconds = epoch_seconds;
+        Ok(())
+    }
+
+    /// Compute and store a new balances root (keepers/admin, once per epoch).
+    pub fn publish(caller: ActorId) -> Result<BalancesRootInfo, Error> {
+        let now = exec::block_timestamp();
+        let leaves = {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            if let Some(last) = &st.balances_snapshot
+                && now < last.published_at.saturating_add(st.solvency_epoch_seconds)
+            {
+                return Err(Error::EpochNotElapsed);
+            }
+            Self::collect_leaves(&st)
+        };
+
+        let hashes: Vec<H256> = leaves
+            .iter()
+            .map(|(account, balance, equity)| utils::balance_leaf(*account, *balance, *equity))
+            .collect();
+        let root = utils::merkle_root(&hashes);
+
+        let mut st = PerpetualDEXState::get_mut();
+        let epoch = st.balances_snapshot.as_ref().map_or(1, |s| s.epoch + 1);
+        let snapshot = BalancesSnapshot {
+            epoch,
+            root,
+            published_at: now,
+            leaves,
+        };
+        let info = Self::info(&snapshot);
+        st.balances_snapshot = Some(snapshot);
+        Ok(info)
+    }
+
+    /// (account, balance, equity) for every account with a balance or open positions, sorted by account.
+    /// Equity is valued at the oracle mid; positions without a price count collateral only.
+    fn collect_leaves(st: &PerpetualDEXState) -> Vec<(ActorId, Usd, i128)> {
+        let mut accounts: BTreeMap<ActorId, (Usd, i128)> = BTreeMap::new();
+        for (account, balance) in st.balances.iter() {
+            accounts.entry(*account).or_default().0 = *balance;
+        }
+        for pos in st.positions.values() {
+            let pnl = OracleModule::mid(&utils::price_key(&pos.market))
+                .map(|price| PositionModule::calculate_pnl(pos, price))
+                .unwrap_or(0);
+            let equity = (pos.collateral_usd as i128).saturating_add(pnl);
+            let entry = accounts.entry(pos.account).or_default();
+            entry.1 = entry.1.saturating_add(equity);
+        }
+        accounts
+            .into_iter()
+            .map(|(account, (balance, equity))| (account, balance, equity))
+            .collect()
+    }
+
+    fn info(snapshot: &BalancesSnapshot) -> BalancesRootInfo {
+        BalancesRootInfo {
+            epoch: snapshot.epoch,
+            root: snapshot.root,
+            published_at: snapshot.published_at,
+            leaf_count: snapshot.leaves.len() as u32,
+        }
+    }
+
+    pub fn latest_root() -> Option<BalancesRootInfo> {
+        let st = PerpetualDEXState::get();
+        st.balances_snapshot.as_ref().map(Self::info)
+    }
+
+    pub fn proof(account: ActorId) -> Result<BalanceProof, Error> {
+        let st = PerpetualDEXState::get();
+        let snapshot = st.balances_snapshot.as_ref().ok_or(Error::SnapshotNotPublished)?;
+        let index = snapshot
+            .leaves
+            .binary_search_by(|(a, _, _)| a.cmp(&account))
+            .map_err(|_| Error::InvalidParameter)?;
+
+        let hashes: Vec<H256> = snapshot
+            .leaves
+            .iter()
+            .map(|(a, balance, equity)| utils::balance_leaf(*a, *balance, *equity))
+            .collect();
+        let (_, balance, equity) = snapshot.leaves[index];
+
+        Ok(BalanceProof {
+            account,
+            balance,
+            equity,
+            leaf_index: index as u32,
+            leaf_count: hashes.len() as u32,
+            leaf: hashes[index],
+            proof: utils::merkle_proof(&hashes, index),
+            root: snapshot.root,
+        })
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{
+    PerpetualDEXState,
+    modules::{oracle::OracleModule, position::PositionModule},
+    types::*,
+    utils,
+};
+use sails_rs::{collections::HashMap, prelude::*};
+
+/// Bucket width for recent-window statistics
+pub const STATS_BUCKET_SECONDS: u64 = 3_600;
+/// Length of the recent window reported next to lifetime totals
+pub const STATS_WINDOW_SECONDS: u64 = 86_400;
+
+impl WindowedUsd {
+    pub fn add(&mut self, amount: Usd, now: u64) {
+        if amount == 0 {
+            return;
+        }
+        self.total = self.total.saturating_add(amount);
+
+        let bucket = now - now % STATS_BUCKET_SECONDS;
+        match self.buckets.last_mut() {
+            Some((start, sum)) if *start == bucket => *sum = sum.saturating_add(amount),
+            _ => self.buckets.push((bucket, amount)),
+        }
+        self.buckets.retain(|(start, _)| start + STATS_WINDOW_SECONDS > bucket);
+    }
+
+    /// Sum over the trailing `STATS_WINDOW_SECONDS` (bucket-granular)
+    pub fn recent(&self, now: u64) -> Usd {
+        let bucket = now - now % STATS_BUCKET_SECONDS;
+        self.buckets
+            .iter()
+            .filter(|(start, _)| start + STATS_WINDOW_SECONDS > bucket)
+            .fold(0u128, |acc, (_, sum)| acc.saturating_add(*sum))
+    }
+}
+
+/// Fee inflow categories tracked per market
+#[derive(Clone, Copy, Debug)]
+pub enum FeeKind {
+    Trading,
+    Borrowing,
+    Liquidation,
+    PriceImpact,
+}
+
+/// Historical accumulators maintained at execution/settlement time
+pub struct StatsModule;
+
+impl StatsModule {
+    /// Record a settled funding payment made by the `is_long` side of `market`
+    pub fn record_funding(stats: &mut HashMap<String, FundingStats>, market: &str, is_long: bool, paid: Usd, now: u64) {
+        let entry = stats.entry(String::from(market)).or_default();
+        if is_long {
+            entry.paid_by_longs.add(paid, now);
+        } else {
+            entry.paid_by_shorts.add(paid, now);
+        }
+    }
+
+    pub fn record_funding_received(stats: &mut HashMap<String, FundingStats>, market: &str, credited: Usd, now: u64) {
+        stats.entry(String::from(market)).or_default().received.add(credited, now);
+    }
+
+    pub fn record_fee(stats: &mut HashMap<String, MarketFeeStats>, market: &str, kind: FeeKind, amount: Usd, now: u64) {
+        let entry = stats.entry(String::from(market)).or_default();
+        let bucket = match kind {
+            FeeKind::Trading => &mut entry.trading,
+            FeeKind::Borrowing => &mut entry.borrowing,
+            FeeKind::Liquidation => &mut entry.liquidation,
+            FeeKind::PriceImpact => &mut entry.price_impact,
+        };
+        bucket.add(amount, now);
+    }
+
+    /// Record a pool execution. `is_buy` is true for long increases and short decreases.
+    pub fn record_execution(
+        stats: &mut HashMap<String, SlippageStats>,
+        market: &str,
+        is_buy: bool,
+        size_usd: Usd,
+        execution_price: u128,
+        mid_price: u128,
+        price_impact_usd: i128,
+        now: u64,
+    ) {
+        if mid_price == 0 {
+            return;
+        }
+        let entry = stats.entry(String::from(market)).or_default();
+        entry.executions += 1;
+        entry.volume_usd = entry.volume_usd.saturating_add(size_usd);
+
+        let adverse = if is_buy { execution_price > mid_price } else { execution_price < mid_price };
+        let diff = execution_price.abs_diff(mid_price);
+        let slippage_usd = size_usd.saturating_mul(diff) / mid_price;
+        if adverse {
+            entry.adverse_slippage_usd.add(slippage_usd, now);
+            let bps = diff.saturating_mul(10_000) / mid_price;
+            entry.max_slippage_bps = entry.max_slippage_bps.max(bps.min(u32::MAX as u128) as u32);
+        } else {
+            entry.favorable_slippage_usd = entry.favorable_slippage_usd.saturating_add(slippage_usd);
+        }
+        if price_impact_usd > 0 {
+            entry.price_impact_rebated_usd = entry.price_impact_rebated_usd.saturating_add(price_impact_usd as u128);
+        }
+    }
+
+    pub fn slippage_stats(market: &str, now: u64) -> SlippageStatsView {
+        let st = PerpetualDEXState::get();
+        let s = st.slippage_stats.get(market).cloned().unwrap_or_default();
+        let impact = st
+            .market_fee_stats
+            .get(market)
+            .map(|f| f.price_impact.clone())
+            .unwrap_or_default();
+
+        let net = (s.adverse_slippage_usd.total as i128).saturating_sub(s.favorable_slippage_usd as i128);
+        let avg_slippage_bps = if s.volume_usd == 0 {
+            0
+        } else {
+            (net.saturating_mul(10_000) / s.volume_usd as i128) as i64
+        };
+
+        SlippageStatsView {
+            market: String::from(market),
+            executions: s.executions,
+            volume_usd: s.volume_usd,
+            avg_slippage_bps,
+            max_slippage_bps: s.max_slippage_bps,
+            adverse_slippage_total: s.adverse_slippage_usd.total,
+            adverse_slippage_recent: s.adverse_slippage_usd.recent(now),
+            favorable_slippage_total: s.favorable_slippage_usd,
+            price_impact_paid_total: impact.total,
+            price_impact_paid_recent: impact.recent(now),
+            price_impact_rebated_total: s.price_impact_rebated_usd,
+            window_seconds: STATS_WINDOW_SECONDS,
+        }
+    }
+
+    /// Record a saved order reaching `Executed`, `wait_seconds` after it was created
+    pub fn record_order_executed(stats: &mut HashMap<String, OrderExecutionCounters>, market: &str, wait_seconds: u64) {
+        let entry = stats.entry(String::from(market)).or_default();
+        entry.executed += 1;
+        entry.total_wait_seconds = entry.total_wait_seconds.saturating_add(wait_seconds);
+    }
+
+    pub fn record_orders_expired(stats: &mut HashMap<String, OrderExecutionCounters>, market: &str, count: u64) {
+        stats.entry(String::from(market)).or_default().expired += count;
+    }
+
+    /// Lifetime outcomes plus live pending/frozen counts (scans the market's saved orders)
+    pub fn order_execution_stats(market: &str) -> OrderExecutionStats {
+        let st = PerpetualDEXState::get();
+        let c = st.order_execution_stats.get(market).cloned().unwrap_or_default();
+        let (mut pending, mut frozen) = (0u32, 0u32);
+        for o in st.orders.values().filter(|o| o.market == market) {
+            if o.status == OrderStatus::Frozen || (o.status == OrderStatus::Created && o.is_frozen) {
+                frozen += 1;
+            } else if o.status == OrderStatus::Created {
+                pending += 1;
+            }
+        }
+
+        OrderExecutionStats {
+            market: String::from(market),
+            pending,
+            frozen,
+            executed: c.executed,
+            expired: c.expired,
+            avg_time_to_execution_seconds: c.total_wait_seconds.checked_div(c.executed).unwrap_or(0),
+        }
+    }
+
+    /// Totals across markets. Iterates per-market pools and fee stats only; position and
+    /// order counts come from maintained counters.
+    pub fn protocol_stats(now: u64) -> ProtocolStats {
+        let st = PerpetualDEXState::get();
+        let mut stats = ProtocolStats {
+            markets: st.markets.len() as u64,
+            open_positions: st.positions.len() as u64,
+            open_orders: st.open_orders,
+            insurance_fund_usd: st.insurance_fund_usd,
+            ..Default::default()
+        };
+        for pool in st.pool_amounts.values() {
+            stats.total_liquidity_usd = stats.total_liquidity_usd.saturating_add(pool.liquidity_usd);
+            stats.total_long_oi_usd = stats.total_long_oi_usd.saturating_add(pool.long_oi_usd);
+            stats.total_short_oi_usd = stats.total_short_oi_usd.saturating_add(pool.short_oi_usd);
+        }
+        for market in st.markets.keys() {
+            let revenue = Self::fee_revenue(market, now);
+            stats.total_fees_usd = stats.total_fees_usd.saturating_add(revenue.lifetime.total);
+        }
+        stats
+    }
+
+    pub fn fee_revenue(market: &str, now: u64) -> MarketFeeRevenue {
+        let st = PerpetualDEXState::get();
+        let fees = st.market_fee_stats.get(market).cloned().unwrap_or_default();
+        let funding = st.funding_stats.get(market).cloned().unwrap_or_default();
+
+        let breakdown = |read: &dyn Fn(&WindowedUsd) -> Usd| {
+            let paid = read(&funding.paid_by_longs).saturating_add(read(&funding.paid_by_shorts));
+            let mut b = FeeBreakdown {
+                trading: read(&fees.trading),
+                borrowing: read(&fees.borrowing),
+                funding_spread: paid.saturating_sub(read(&funding.received)),
+                liquidation: read(&fees.liquidation),
+                price_impact: read(&fees.price_impact),
+                total: 0,
+            };
+            b.total = b
+                .trading
+                .saturating_add(b.borrowing)
+                .saturating_add(b.funding_spread)
+                .saturating_add(b.liquidation)
+                .saturating_add(b.price_impact);
+            b
+        };
+
+        MarketFeeRevenue {
+            market: String::from(market),
+            lifetime: breakdown(&|w| w.total),
+            recent: breakdown(&|w| w.recent(now)),
+            window_seconds: STATS_WINDOW_SECONDS,
+        }
+    }
+
+    pub fn trader(stats: &mut HashMap<ActorId, TraderStats>, account: ActorId) -> &mut TraderStats {
+        stats.entry(account).or_default()
+    }
+
+    /// Record an executed size change; `realized_pnl` is zero for increases
+    pub fn record_trade(
+        stats: &mut HashMap<ActorId, TraderStats>,
+        account: ActorId,
+        size_delta_usd: Usd,
+        realized_pnl: i128,
+    ) {
+        let t = Self::trader(stats, account);
+        t.volume_usd = t.volume_usd.saturating_add(size_delta_usd);
+        t.realized_pnl = t.realized_pnl.saturating_add(realized_pnl);
+        t.trade_count += 1;
+    }
+
+    pub fn trader_stats(account: ActorId) -> TraderStatsView {
+        let (stats, positions) = {
+            let st = PerpetualDEXState::get();
+            let stats = st.trader_stats.get(&account).cloned().unwrap_or_default();
+            (stats, PositionModule::get_account_positions(account))
+        };
+        let unrealized_pnl = positions
+            .iter()
+            .filter_map(|pos| {
+                let price = OracleModule::mid(&utils::price_key(&pos.market)).ok()?;
+                Some(PositionModule::calculate_pnl(pos, price))
+            })
+            .fold(0i128, |acc, pnl| acc.saturating_add(pnl));
+
+        TraderStatsView {
+            account,
+            stats,
+            unrealized_pnl,
+            open_positions: positions.len() as u32,
+        }
+    }
+
+    pub fn funding_stats(market: &str, now: u64) -> FundingStatsView {
+        let st = PerpetualDEXState::get();
+        let stats = st.funding_stats.get(market).cloned().unwrap_or_default();
+        FundingStatsView {
+            market: String::from(market),
+            paid_by_longs_total: stats.paid_by_longs.total,
+            paid_by_shorts_total: stats.paid_by_shorts.total,
+            paid_by_longs_recent: stats.paid_by_longs.recent(now),
+            paid_by_shorts_recent: stats.paid_by_shorts.recent(now),
+            window_seconds: STATS_WINDOW_SECONDS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windowed_usd_drops_old_buckets() {
+        let mut w = WindowedUsd::default();
+        w.add(100, 0);
+        w.add(50, 1_800);
+        w.add(25, STATS_BUCKET_SECONDS * 5);
+        assert_eq!(w.total, 175);
+        assert_eq!(w.recent(STATS_BUCKET_SECONDS * 5), 175);
+
+        let later = STATS_WINDOW_SECONDS + STATS_BUCKET_SECONDS;
+        assert_eq!(w.recent(later), 25);
+        w.add(10, later);
+        assert_eq!(w.buckets.len(), 2);
+        assert_eq!(w.total, 185);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{
+    PerpetualDEXState,
+    errors::Error,
+    modules::{notification::NotificationModule, oracle::OracleModule, risk::RiskModule, trading::TradingModule},
+    types::*,
+    utils,
+};
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Per-position auto-top-up rules: keepers move the owner's balance into collateral once the
+/// position's health drops below its trigger, so offline traders avoid avoidable liquidations
+pub struct AutoTopUpModule;
+
+impl AutoTopUpModule {
+    /// Register (or replace) the rule of one of the caller's positions. The trigger must sit
+    /// above the liquidation threshold (10_000 bps); usage of a replaced rule carries over.
+    pub fn set_rule(
+        caller: ActorId,
+        position_key: PositionKey,
+        trigger_health_bps: u32,
+        max_amount_usd: Usd,
+    ) -> Result<AutoTopUpRule, Error> {
+        if trigger_health_bps <= 10_000 || max_amount_usd == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let mut st = PerpetualDEXState::get_mut();
+        let pos = st.positions.get(&position_key).ok_or(Error::PositionNotFound)?;
+        if pos.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        let used_usd = st.auto_topups.get(&position_key).map_or(0, |r| r.used_usd);
+        let rule = AutoTopUpRule {
+            trigger_health_bps,
+            max_amount_usd,
+            used_usd,
+        };
+        st.auto_topups.insert(position_key, rule.clone());
+        Ok(rule)
+    }
+
+    pub fn remove_rule(caller: ActorId, position_key: PositionKey) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        let pos = st.positions.get(&position_key).ok_or(Error::PositionNotFound)?;
+        if pos.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        st.auto_topups.remove(&position_key);
+        Ok(())
+    }
+
+    pub fn get_rule(position_key: PositionKey) -> Option<AutoTopUpRule> {
+        PerpetualDEXState::get().auto_topups.get(&position_key).cloned()
+    }
+
+    /// Top up up to `limit` isolated positions below their rule's trigger, least healthy first
+    /// (keepers/admin). Each top-up restores the trigger health where the rule's remaining
+    /// budget and the owner's balance allow; failed top-ups are skipped.
+    pub fn apply(caller: ActorId, limit: u32) -> Result<Vec<AutoTopUpApplied>, Error> {
+        let now = exec::block_timestamp();
+        // Shared borrow only: the oracle and fee helpers below borrow the state themselves
+        let mut due = {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+
+            let mut due = Vec::new();
+            for (key, rule) in &st.auto_topups {
+                let Some(pos) = st.positions.get(key) else { continue };
+                if st.account_settings.get(&pos.account).map(|s| s.margin_mode).unwrap_or_default()
+                    != MarginMode::Isolated
+                {
+                    continue;
+                }
+                let (Some(cfg), Some(pool)) = (st.market_configs.get(&pos.market), st.pool_amounts.get(&pos.market))
+                else {
+                    continue;
+                };
+                let Ok(price) = OracleModule::mid(&utils::price_key(&pos.market)) else { continue };
+                let Ok(Some(health)) = RiskModule::health_factor_bps(pos, pool, cfg, price, now) else { continue };
+                if health >= rule.trigger_health_bps {
+                    continue;
+                }
+                let Ok(Some(effective)) = RiskModule::effective_collateral(pos, pool, cfg, price, now) else { continue };
+
+                let needed = Self::amount_to_restore(
+                    pos.collateral_usd,
+                    effective,
+                    cfg.liquidation_threshold_bps,
+                    rule.trigger_health_bps,
+                );
+                let balance = st.balances.get(&pos.account).copied().unwrap_or(0);
+                let amount = needed.min(rule.max_amount_usd.saturating_sub(rule.used_usd)).min(balance);
+                if amount > 0 {
+                    due.push((health, *key, pos.account, amount));
+                }
+            }
+            due
+        };
+        due.sort_unstable_by_key(|(health, ..)| *health);
+        due.truncate(limit as usize);
+
+        let mut applied = Vec::new();
+        for (health, key, account, amount) in due {
+            if TradingModule::top_up_collateral(account, key, amount).is_err() {
+                continue;
+            }
+            let record = AutoTopUpApplied {
+                position_key: key,
+                account,
+                amount_usd: amount,
+                health_before_bps: health,
+            };
+            let mut st = PerpetualDEXState::get_mut();
+            if let Some(rule) = st.auto_topups.get_mut(&key) {
+                rule.used_usd = rule.used_usd.saturating_add(amount);
+            }
+            NotificationModule::push(&mut st.notifications, account, NoticeKind::CollateralToppedUp(record.clone()));
+            applied.push(record);
+        }
+        Ok(applied)
+    }
+
+    /// Collateral to add so health returns to `trigger_bps`. Added collateral also raises the
+    /// threshold, so solve `(eff + a) * 1e8 >= trigger * threshold_bps * (collateral + a)`;
+    /// a target the threshold makes unreachable asks for the whole remaining budget.
+    fn amount_to_restore(collateral: Usd, effective: i128, threshold_bps: u16, trigger_bps: u32) -> Usd {
+        let ratio = (trigger_bps as i128).saturating_mul(threshold_bps as i128);
+        let denom = 100_000_000i128 - ratio;
+        if denom <= 0 {
+            return Usd::MAX;
+        }
+        let numer = ratio.saturating_mul(collateral as i128).saturating_sub(effective.saturating_mul(100_000_000));
+        if numer <= 0 {
+            return 0;
+        }
+        numer.saturating_add(denom - 1) as Usd / denom as Usd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amount_to_restore_reaches_trigger() {
+        // 1_000 collateral, 50% threshold, 1.2x trigger, 550 effective: 125 brings health to
+        // 675 / (1_125 * 50%) = 1.2
+        assert_eq!(AutoTopUpModule::amount_to_restore(1_000, 550, 5_000, 12_000), 125);
+        assert_eq!(AutoTopUpModule::amount_to_restore(1_000, 700, 5_000, 12_000), 0);
+    }
+
+    #[test]
+    fn test_amount_to_restore_unreachable_target() {
+        assert_eq!(AutoTopUpModule::amount_to_restore(1_000, 550, 10_000, 10_001), Usd::MAX);
+    }
+}
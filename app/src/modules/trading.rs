@@ -1,41 +1,761 @@
 use crate::{
-    PerpetualDEXState,
+    OrderPlacementSnapshot, PerpetualDEXState, PositionSnapshot,
     errors::Error,
-    modules::{oracle::OracleModule, position::PositionModule, pricing::PricingModule, risk::RiskModule},
+    modules::{
+        account::AccountModule, discount::FeeDiscountModule, equity::EquityModule, keeper::KeeperModule,
+        market::MarketModule, notification::NotificationModule, oracle::OracleModule, orderbook::OrderBookModule,
+        position::PositionModule, pricing::{PricingModule, QuoteResult}, referral::ReferralModule, risk::RiskModule,
+        stats::{FeeKind, StatsModule},
+    },
     types::*,
     utils,
 };
-use sails_rs::{gstd::exec, prelude::*};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Upper bound on TWAP tranches, keeping per-order keeper work bounded
+pub const MAX_TWAP_TRANCHES: u32 = 100;
+/// Upper bound on the orders one scaled-order call creates
+pub const MAX_SCALED_ORDERS: u32 = 50;
+/// Upper bound on gas an order may forward to its callback contract
+pub const MAX_CALLBACK_GAS_LIMIT: u64 = 5_000_000_000;
+/// Executed orders whose details are kept; the oldest are dropped first
+pub const MAX_EXECUTION_DETAILS: usize = 10_000;
+
+/// Orders cancelled by one sweep walk, with what is owed for them
+#[derive(Default)]
+struct OrderSweep {
+    swept: Vec<RequestKey>,
+    /// Escrowed native and priority fees owed back to each owner
+    refunds: Vec<(ActorId, u128)>,
+    /// Cancelled orders with a callback contract to notify
+    callbacks: Vec<Order>,
+    /// Native fee share earned by the sweeper
+    reward: u128,
+}
+
+/// Undo point for one order of a multi-step flow
+enum OrderRollback {
+    /// The order placement and the caller's entries on the order's market
+    Entries(OrderPlacementSnapshot, PositionSnapshot),
+    /// A market open may cross the order book and write to maker accounts, so the whole state
+    /// is copied
+    Full(Box<PerpetualDEXState>),
+}
 
 pub struct TradingModule;
 
 impl TradingModule {
+    /// Execute or save an order. A referral code binds the account only once the order has
+    /// executed or been saved.
     pub fn create_order(caller: ActorId, params: CreateOrderParams) -> Result<ExecutionResult, Error> {
-        let st = PerpetualDEXState::get();
-        if !st.markets.contains_key(&params.market) {
-            return Err(Error::MarketNotFound);
-        }
-        if !st.market_configs.contains_key(&params.market) {
-            return Err(Error::MarketNotFound);
+        let referral_code = params.referral_code.clone();
+        let result = Self::place_order(caller, params)?;
+        if matches!(result, ExecutionResult::Executed { .. } | ExecutionResult::Saved { .. }) {
+            Self::bind_referrer(caller, referral_code.as_deref());
         }
+        Ok(result)
+    }
 
-        Self::validate_order_params(&params)?;
-
+    fn place_order(caller: ActorId, mut params: CreateOrderParams) -> Result<ExecutionResult, Error> {
+        Self::prepare_order(caller, &mut params)?;
         let price_key = utils::price_key(&params.market);
-        OracleModule::ensure_fresh(&price_key)?;
 
-        match params.order_type {
-            OrderType::MarketIncrease | OrderType::MarketDecrease => Self::execute_market_order(caller, params),
+        let result = match params.order_type {
+            OrderType::MarketIncrease => {
+                let settings = AccountModule::settings(caller);
+                if settings.default_take_profit_bps.is_none() && settings.default_stop_loss_bps.is_none() {
+                    Self::execute_market_order(caller, params)?
+                } else {
+                    // Brackets that cannot be saved roll back the open with them
+                    let rollback = Self::rollback_point(caller, &params);
+                    let result = Self::execute_market_order(caller, params.clone()).and_then(|result| {
+                        Self::register_default_tp_sl(caller, &params, &result, &settings)?;
+                        Ok(result)
+                    });
+                    if result.is_err() {
+                        Self::roll_back(vec![rollback]);
+                    }
+                    result?
+                }
+            }
+            OrderType::MarketDecrease => Self::execute_market_order(caller, params)?,
             OrderType::LimitIncrease | OrderType::LimitDecrease | OrderType::StopLossDecrease => {
                 let mid = OracleModule::mid(&price_key)?;
                 if Self::can_execute_limit_order(&params, mid) {
-                    Self::execute_limit_order(caller, params)
+                    Self::execute_limit_order(caller, params)?
                 } else {
-                    Self::save_order(caller, params)
+                    match params.time_in_force {
+                        TimeInForce::Gtc => {
+                            Self::validate_projected_leverage(caller, &params)?;
+                            return Self::save_order(caller, params);
+                        }
+                        TimeInForce::Ioc => return Ok(ExecutionResult::Cancelled),
+                        TimeInForce::Fok => return Err(Error::FillOrKillNotFilled),
+                    }
                 }
             }
-            _ => Err(Error::UnsupportedOrderType),
+            _ => return Err(Error::UnsupportedOrderType),
+        };
+        EquityModule::sample(caller);
+        Ok(result)
+    }
+
+    /// Market checks, fresh price, default slippage and parameter validation shared by all order entry points
+    fn prepare_order(caller: ActorId, params: &mut CreateOrderParams) -> Result<(), Error> {
+        {
+            let st = PerpetualDEXState::get();
+            st.ensure_not_withdraw_only()?;
+            if !st.markets.contains_key(&params.market) {
+                return Err(Error::MarketNotFound);
+            }
+            st.ensure_market_allows(&params.market, Self::is_increase(&params.order_type))?;
+            if !st.market_configs.contains_key(&params.market) {
+                return Err(Error::MarketNotFound);
+            }
+        }
+        MarketModule::activate_scheduled_config(&params.market);
+
+        let price_key = utils::price_key(&params.market);
+        OracleModule::ensure_fresh(&price_key)?;
+
+        // Omitted acceptable price: derive it from the account's default slippage
+        if params.acceptable_price == 0
+            && let Some(slippage_bps) = AccountModule::default_max_slippage_bps(caller)
+        {
+            let mid = OracleModule::mid(&price_key)?;
+            params.acceptable_price = Self::slippage_bound(params, mid, slippage_bps);
+        }
+
+        // Bound by the entry point once the order succeeds
+        if let Some(code) = &params.referral_code {
+            ReferralModule::ensure_code(code)?;
         }
+        Self::resolve_target_position(caller, params)?;
+
+        Self::validate_order_params(params)?;
+        Self::claim_client_order_id(caller, params)
+    }
+
+    fn bind_referrer(caller: ActorId, referral_code: Option<&str>) {
+        if let Some(code) = referral_code {
+            ReferralModule::bind(caller, code);
+        }
+    }
+
+    /// Claim the order's client id under a fresh request key, so a retried message is rejected
+    /// whether the first one saved its order or executed it
+    fn claim_client_order_id(caller: ActorId, params: &CreateOrderParams) -> Result<(), Error> {
+        let Some(id) = params.client_order_id else {
+            return Ok(());
+        };
+        let mut st = PerpetualDEXState::get_mut();
+        if st.client_order_ids.contains_key(&(caller, id)) {
+            return Err(Error::DuplicateClientOrderId);
+        }
+        let key = st.generate_request_key();
+        st.client_order_ids.insert((caller, id), key);
+        Ok(())
+    }
+
+    /// A targeted position must be the caller's open position of the order's market, collateral
+    /// and side. In sub-position mode an untargeted increase is given a fresh sub-position.
+    fn resolve_target_position(caller: ActorId, params: &mut CreateOrderParams) -> Result<(), Error> {
+        let is_long = matches!(params.side, OrderSide::Long);
+        let mut st = PerpetualDEXState::get_mut();
+        match params.position_key {
+            Some(key) => {
+                let pos = st.positions.get(&key).ok_or(Error::PositionNotFound)?;
+                if pos.account != caller
+                    || pos.market != params.market
+                    || pos.collateral_token != params.collateral_token
+                    || pos.is_long != is_long
+                {
+                    return Err(Error::InvalidParameter);
+                }
+            }
+            None if Self::is_increase(&params.order_type)
+                && st.account_settings.get(&caller).is_some_and(|s| s.position_mode == PositionMode::SubPositions) =>
+            {
+                params.position_key =
+                    Some(st.new_sub_position_key(caller, &params.market, &params.collateral_token, is_long));
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Position an order trades: its target, else the canonical position
+    fn target_position_key(caller: ActorId, p: &CreateOrderParams) -> PositionKey {
+        let is_long = matches!(p.side, OrderSide::Long);
+        p.position_key
+            .unwrap_or_else(|| PerpetualDEXState::get_position_key(caller, &p.market, &p.collateral_token, is_long))
+    }
+
+    /// Market decrease of `percent_bps` of the caller's position, with size and collateral deltas
+    /// taken proportionally from the position as stored now (10_000 closes it fully)
+    pub fn close_percent(
+        caller: ActorId,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        percent_bps: u16,
+        acceptable_price: u128,
+    ) -> Result<ExecutionResult, Error> {
+        let is_long = matches!(side, OrderSide::Long);
+        let key = PerpetualDEXState::get_position_key(caller, &market, &collateral_token, is_long);
+        Self::close_position_percent(caller, key, percent_bps, acceptable_price)
+    }
+
+    /// `close_percent` of the caller's position stored under `key`, canonical or a sub-position
+    pub fn close_position_percent(
+        caller: ActorId,
+        key: PositionKey,
+        percent_bps: u16,
+        acceptable_price: u128,
+    ) -> Result<ExecutionResult, Error> {
+        if percent_bps == 0 || percent_bps > 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        let pos = PositionModule::get_position(&key)?;
+        let size_delta_usd = pos.size_usd.saturating_mul(percent_bps as u128) / 10_000;
+
+        let params = CreateOrderParams {
+            market: pos.market.clone(),
+            collateral_token: pos.collateral_token.clone(),
+            order_type: OrderType::MarketDecrease,
+            side: if pos.is_long { OrderSide::Long } else { OrderSide::Short },
+            size_delta_usd,
+            collateral_delta_amount: pos.collateral_usd.saturating_mul(percent_bps as u128) / 10_000,
+            trigger_price: acceptable_price,
+            acceptable_price,
+            execution_fee: 0,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: Some(key),
+        };
+        Self::create_order(caller, params)
+    }
+
+    /// Close the caller's whole `side` position and open the opposite side, atomically.
+    /// Both legs trade in the same direction, so `acceptable_price` bounds both fills.
+    /// Returns the close and open results.
+    pub fn flip_position(
+        caller: ActorId,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        size_delta_usd: u128,
+        collateral_amount: u128,
+        acceptable_price: u128,
+    ) -> Result<Vec<ExecutionResult>, Error> {
+        let is_long = matches!(side, OrderSide::Long);
+        let key = PerpetualDEXState::get_position_key(caller, &market, &collateral_token, is_long);
+        let pos = PositionModule::get_position(&key)?;
+        let opposite = if is_long { OrderSide::Short } else { OrderSide::Long };
+
+        let close = CreateOrderParams {
+            market: market.clone(),
+            collateral_token: collateral_token.clone(),
+            order_type: OrderType::MarketDecrease,
+            side,
+            size_delta_usd: pos.size_usd,
+            collateral_delta_amount: pos.collateral_usd,
+            trigger_price: acceptable_price,
+            acceptable_price,
+            execution_fee: 0,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
+        };
+        let open = CreateOrderParams {
+            market,
+            collateral_token,
+            order_type: OrderType::MarketIncrease,
+            side: opposite,
+            size_delta_usd,
+            collateral_delta_amount: collateral_amount,
+            trigger_price: acceptable_price,
+            acceptable_price,
+            execution_fee: 0,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
+        };
+        Self::create_basket(caller, vec![close, open])
+    }
+
+    /// Add collateral from the caller's balance to a position without changing its size
+    pub fn add_collateral(
+        caller: ActorId,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        amount_usd: u128,
+    ) -> Result<Position, Error> {
+        Self::adjust_collateral(caller, market, collateral_token, side, amount_usd, true)
+    }
+
+    /// Withdraw collateral from a position to the caller's balance without changing its size.
+    /// The remaining position is checked at its liquidation price.
+    pub fn remove_collateral(
+        caller: ActorId,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        amount_usd: u128,
+    ) -> Result<Position, Error> {
+        Self::adjust_collateral(caller, market, collateral_token, side, amount_usd, false)
+    }
+
+    /// Add or remove collateral on one of the caller's positions so its leverage becomes
+    /// `leverage_x`; size is unchanged. The remaining position is checked at its liquidation price.
+    pub fn set_target_leverage(caller: ActorId, position_key: PositionKey, leverage_x: u8) -> Result<Position, Error> {
+        Self::with_collateral_change(position_key, |price| {
+            PositionModule::set_target_leverage(caller, position_key, leverage_x, price)
+        })
+    }
+
+    fn adjust_collateral(
+        caller: ActorId,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        amount_usd: u128,
+        is_add: bool,
+    ) -> Result<Position, Error> {
+        let is_long = matches!(side, OrderSide::Long);
+        let key = PerpetualDEXState::get_position_key(caller, &market, &collateral_token, is_long);
+        Self::with_collateral_change(key, |price| {
+            PositionModule::adjust_collateral(caller, key, amount_usd, is_add, price)
+        })
+    }
+
+    /// Move `amount_usd` of `owner`'s balance into the collateral of position `key`
+    pub fn top_up_collateral(owner: ActorId, key: PositionKey, amount_usd: u128) -> Result<Position, Error> {
+        Self::with_collateral_change(key, |price| PositionModule::adjust_collateral(owner, key, amount_usd, true, price))
+    }
+
+    /// Run a size-preserving collateral change on `key` with a fresh liquidation price and
+    /// accrued funding, rolling back every change if it fails
+    fn with_collateral_change(
+        key: PositionKey,
+        change: impl FnOnce(u128) -> Result<Position, Error>,
+    ) -> Result<Position, Error> {
+        PerpetualDEXState::get().ensure_not_withdraw_only()?;
+        let pos = PositionModule::get_position(&key)?;
+        OracleModule::ensure_fresh(&utils::price_key(&pos.market))?;
+        let price = {
+            let st = PerpetualDEXState::get();
+            let cfg = st.market_configs.get(&pos.market).ok_or(Error::MarketNotFound)?;
+            KeeperModule::liquidation_price(&pos, cfg)?
+        };
+        RiskModule::accrue_pool(&pos.market, exec::block_timestamp())?;
+
+        let snapshot = PerpetualDEXState::snapshot_position(&pos);
+        let result = change(price);
+        if result.is_err() {
+            PerpetualDEXState::restore_position(snapshot);
+        }
+        result
+    }
+
+    /// Market order whose acceptable price is set `max_slippage_bps` from the oracle mid at
+    /// execution, so callers need not pre-compute it against a price that may move first
+    pub fn create_market_order_bps(
+        caller: ActorId,
+        mut params: CreateOrderParams,
+        max_slippage_bps: u16,
+    ) -> Result<ExecutionResult, Error> {
+        if max_slippage_bps >= 10_000
+            || !matches!(params.order_type, OrderType::MarketIncrease | OrderType::MarketDecrease)
+        {
+            return Err(Error::InvalidParameter);
+        }
+        let price_key = utils::price_key(&params.market);
+        OracleModule::ensure_fresh(&price_key)?;
+        let mid = OracleModule::mid(&price_key)?;
+        params.acceptable_price = Self::slippage_bound(&params, mid, max_slippage_bps);
+        params.trigger_price = params.acceptable_price;
+        Self::create_order(caller, params)
+    }
+
+    /// Market open using `collateral_pct_bps` of the caller's balance as collateral, sized at
+    /// `leverage_x` times that collateral, with the acceptable price `max_slippage_bps` from the
+    /// mid. The trading fee is paid out of that collateral.
+    pub fn open_by_balance_pct(
+        caller: ActorId,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        collateral_pct_bps: u16,
+        leverage_x: u8,
+        max_slippage_bps: u16,
+    ) -> Result<ExecutionResult, Error> {
+        if collateral_pct_bps == 0 || collateral_pct_bps > 10_000 || leverage_x == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let balance = PerpetualDEXState::get().balances.get(&caller).copied().unwrap_or(0);
+        let collateral = balance.saturating_mul(collateral_pct_bps as u128) / 10_000;
+        if collateral == 0 {
+            return Err(Error::InsufficientBalance);
+        }
+
+        let params = CreateOrderParams {
+            market,
+            collateral_token,
+            order_type: OrderType::MarketIncrease,
+            side,
+            size_delta_usd: collateral.saturating_mul(leverage_x as u128),
+            collateral_delta_amount: collateral,
+            trigger_price: 0,
+            acceptable_price: 0,
+            execution_fee: 0,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
+        };
+        Self::create_market_order_bps(caller, params, max_slippage_bps)
+    }
+
+    /// Market-close every open position of the caller, each with an acceptable price
+    /// `slippage_bps` from the current mid. Positions are closed independently: a failed close
+    /// is rolled back and reported without affecting the others.
+    pub fn close_all_positions(caller: ActorId, slippage_bps: u16) -> Result<Vec<PositionCloseResult>, Error> {
+        if slippage_bps >= 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        let positions = PositionModule::get_account_positions(caller);
+
+        let mut results = Vec::with_capacity(positions.len());
+        for pos in positions {
+            let mut params = CreateOrderParams {
+                market: pos.market.clone(),
+                collateral_token: pos.collateral_token.clone(),
+                order_type: OrderType::MarketDecrease,
+                side: if pos.is_long { OrderSide::Long } else { OrderSide::Short },
+                size_delta_usd: pos.size_usd,
+                collateral_delta_amount: pos.collateral_usd,
+                trigger_price: 0,
+                acceptable_price: 0,
+                execution_fee: 0,
+                expires_at: None,
+                time_in_force: TimeInForce::Gtc,
+                referral_code: None,
+                receiver: None,
+                callback_contract: None,
+                callback_gas_limit: 0,
+                client_order_id: None,
+                tag: None,
+                position_key: Some(pos.key),
+            };
+            let snapshot = PerpetualDEXState::snapshot_position(&pos);
+            let result = OracleModule::mid(&utils::price_key(&pos.market)).and_then(|mid| {
+                params.acceptable_price = Self::slippage_bound(&params, mid, slippage_bps);
+                params.trigger_price = params.acceptable_price;
+                Self::create_order(caller, params)
+            });
+            if result.is_err() {
+                PerpetualDEXState::restore_position(snapshot);
+            }
+            results.push(PositionCloseResult {
+                position_key: pos.key,
+                result,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Create several orders atomically: if any order fails, every change is rolled back.
+    /// All markets are checked for fresh prices up front so the basket runs on one oracle snapshot.
+    /// Referral codes bind once the whole basket has succeeded.
+    pub fn create_basket(caller: ActorId, orders: Vec<CreateOrderParams>) -> Result<Vec<ExecutionResult>, Error> {
+        if orders.is_empty() {
+            return Err(Error::InvalidParameter);
+        }
+        for params in &orders {
+            OracleModule::ensure_fresh(&utils::price_key(&params.market))?;
+        }
+
+        let mut rollbacks = Vec::with_capacity(orders.len());
+        let mut results = Vec::with_capacity(orders.len());
+        let mut referral_codes = Vec::new();
+        for params in orders {
+            rollbacks.push(Self::rollback_point(caller, &params));
+            referral_codes.extend(params.referral_code.clone());
+            match Self::place_order(caller, params) {
+                Ok(r) => results.push(r),
+                Err(e) => {
+                    Self::roll_back(rollbacks);
+                    return Err(e);
+                }
+            }
+        }
+        for code in &referral_codes {
+            Self::bind_referrer(caller, Some(code));
+        }
+        Ok(results)
+    }
+
+    /// Undo point taken before `params` is placed or executed for `caller`
+    fn rollback_point(caller: ActorId, params: &CreateOrderParams) -> OrderRollback {
+        if params.order_type == OrderType::MarketIncrease
+            && params.position_key.is_none()
+            && OrderBookModule::is_enabled(&params.market)
+        {
+            return OrderRollback::Full(Box::new(PerpetualDEXState::snapshot()));
+        }
+        OrderRollback::Entries(
+            PerpetualDEXState::snapshot_order_placement(caller, params.client_order_id),
+            PerpetualDEXState::snapshot_trade(caller, &params.market)
+                .with_balance(Self::payout_receiver(caller, params)),
+        )
+    }
+
+    /// Restore undo points, newest first
+    fn roll_back(rollbacks: Vec<OrderRollback>) {
+        for rollback in rollbacks.into_iter().rev() {
+            match rollback {
+                OrderRollback::Entries(placement, trade) => {
+                    PerpetualDEXState::restore_position(trade);
+                    PerpetualDEXState::restore_order_placement(placement);
+                }
+                OrderRollback::Full(snapshot) => PerpetualDEXState::restore(*snapshot),
+            }
+        }
+    }
+
+    /// Save a ladder of `LimitIncrease`/`LimitDecrease` orders splitting `params`' size and
+    /// collateral across `scale.order_count` evenly spaced triggers. Every level must still be
+    /// pending; any failure rolls back all of them. Returns the order keys from start to end price.
+    pub fn create_scaled_orders(
+        caller: ActorId,
+        params: CreateOrderParams,
+        scale: ScaledOrderParams,
+    ) -> Result<Vec<RequestKey>, Error> {
+        let snapshot = PerpetualDEXState::snapshot_order_placement(caller, params.client_order_id);
+        let referral_code = params.referral_code.clone();
+        let result = Self::apply_scaled_orders(caller, params, scale);
+        match result {
+            Ok(_) => Self::bind_referrer(caller, referral_code.as_deref()),
+            Err(_) => PerpetualDEXState::restore_order_placement(snapshot),
+        }
+        result
+    }
+
+    fn apply_scaled_orders(
+        caller: ActorId,
+        params: CreateOrderParams,
+        scale: ScaledOrderParams,
+    ) -> Result<Vec<RequestKey>, Error> {
+        if !matches!(params.order_type, OrderType::LimitIncrease | OrderType::LimitDecrease) {
+            return Err(Error::UnsupportedOrderType);
+        }
+        let n = scale.order_count;
+        if !(2..=MAX_SCALED_ORDERS).contains(&n) || scale.max_slippage_bps >= 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        if scale.start_price == 0 || scale.end_price == 0 {
+            return Err(Error::InvalidTriggerPrice);
+        }
+        if params.size_delta_usd < n as u128 {
+            return Err(Error::InvalidOrderSize);
+        }
+        let mid = OracleModule::mid(&utils::price_key(&params.market))?;
+
+        let weights: Vec<u128> = (0..n as u128)
+            .map(|i| match scale.distribution {
+                SizeDistribution::Flat => 1,
+                SizeDistribution::Increasing => i + 1,
+                SizeDistribution::Decreasing => n as u128 - i,
+            })
+            .collect();
+        let total_weight: u128 = weights.iter().sum();
+
+        let mut keys = Vec::with_capacity(n as usize);
+        let mut cumulative_weight = 0;
+        for (i, weight) in weights.into_iter().enumerate() {
+            // Level prices and cumulative shares; the last level takes rounding remainders
+            let step = scale.start_price.abs_diff(scale.end_price).saturating_mul(i as u128) / (n as u128 - 1);
+            let trigger_price = if scale.end_price >= scale.start_price {
+                scale.start_price + step
+            } else {
+                scale.start_price - step
+            };
+            let share = |total: u128, w: u128| total.saturating_mul(w) / total_weight;
+            let size = share(params.size_delta_usd, cumulative_weight + weight)
+                - share(params.size_delta_usd, cumulative_weight);
+            let collateral = share(params.collateral_delta_amount, cumulative_weight + weight)
+                - share(params.collateral_delta_amount, cumulative_weight);
+            cumulative_weight += weight;
+
+            let mut level = CreateOrderParams {
+                size_delta_usd: size,
+                collateral_delta_amount: collateral,
+                trigger_price,
+                acceptable_price: 0,
+                // Only the first level claims the client id
+                client_order_id: params.client_order_id.filter(|_| i == 0),
+                ..params.clone()
+            };
+            level.acceptable_price = Self::slippage_bound(&level, trigger_price, scale.max_slippage_bps);
+            if Self::can_execute_limit_order(&level, mid) {
+                return Err(Error::InvalidTriggerPrice);
+            }
+            Self::prepare_order(caller, &mut level)?;
+            Self::validate_projected_leverage(caller, &level)?;
+            let ExecutionResult::Saved { order_key } = Self::save_order(caller, level)? else {
+                return Err(Error::UnsupportedOrderType);
+            };
+            keys.push(order_key);
+        }
+        Ok(keys)
+    }
+
+    /// Open a market position and register its stop-loss and take-profit in one call, linked so
+    /// that whichever executes first cancels the other. The account's default brackets are not
+    /// added; any failure rolls back the open too.
+    pub fn open_with_brackets(
+        caller: ActorId,
+        params: CreateOrderParams,
+        brackets: BracketParams,
+    ) -> Result<BracketOrderResult, Error> {
+        let rollback = Self::rollback_point(caller, &params);
+        let referral_code = params.referral_code.clone();
+        let result = Self::apply_open_with_brackets(caller, params, brackets);
+        match result {
+            Ok(_) => Self::bind_referrer(caller, referral_code.as_deref()),
+            Err(_) => Self::roll_back(vec![rollback]),
+        }
+        result
+    }
+
+    fn apply_open_with_brackets(
+        caller: ActorId,
+        mut params: CreateOrderParams,
+        brackets: BracketParams,
+    ) -> Result<BracketOrderResult, Error> {
+        if params.order_type != OrderType::MarketIncrease || brackets.max_slippage_bps >= 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        Self::prepare_order(caller, &mut params)?;
+
+        let open = Self::execute_market_order(caller, params.clone())?;
+        let ExecutionResult::Executed { position_key, execution_price } = open else {
+            return Err(Error::UnsupportedOrderType);
+        };
+
+        // Both triggers must still be pending: stop below and target above the fill for longs,
+        // mirrored for shorts. The side is the resulting position's, which a one-way open may flip.
+        let is_long = PositionModule::get_position(&position_key)?.is_long;
+        let (sl, tp) = (brackets.stop_loss_price, brackets.take_profit_price);
+        let ordered = if is_long {
+            sl < execution_price && execution_price < tp
+        } else {
+            tp < execution_price && execution_price < sl
+        };
+        if sl == 0 || tp == 0 || !ordered {
+            return Err(Error::InvalidTriggerPrice);
+        }
+
+        let [stop_loss_key, take_profit_key] = Self::save_brackets(
+            caller,
+            position_key,
+            params.size_delta_usd,
+            [sl, tp],
+            brackets.execution_fee,
+            brackets.max_slippage_bps,
+            params.tag,
+        )?;
+
+        Ok(BracketOrderResult {
+            open,
+            stop_loss_key: stop_loss_key.ok_or(Error::InvalidTriggerPrice)?,
+            take_profit_key: take_profit_key.ok_or(Error::InvalidTriggerPrice)?,
+        })
+    }
+
+    /// Save the stop-loss and take-profit of a position as reduce-only decreases of up to
+    /// `size_delta_usd`, linked one-cancels-other when both are set. A zero trigger skips its leg.
+    /// Returns the stop-loss and take-profit keys.
+    fn save_brackets(
+        caller: ActorId,
+        position_key: PositionKey,
+        size_delta_usd: Usd,
+        [stop_loss_price, take_profit_price]: [u128; 2],
+        execution_fee: u128,
+        max_slippage_bps: u16,
+        tag: Option<u32>,
+    ) -> Result<[Option<RequestKey>; 2], Error> {
+        let pos = PositionModule::get_position(&position_key)?;
+        let mut keys = [None; 2];
+        let legs = [(OrderType::StopLossDecrease, stop_loss_price), (OrderType::LimitDecrease, take_profit_price)];
+        for (slot, (order_type, trigger_price)) in keys.iter_mut().zip(legs) {
+            if trigger_price == 0 {
+                continue;
+            }
+            let mut bracket = CreateOrderParams {
+                market: pos.market.clone(),
+                collateral_token: pos.collateral_token.clone(),
+                order_type,
+                side: if pos.is_long { OrderSide::Long } else { OrderSide::Short },
+                size_delta_usd: size_delta_usd.min(pos.size_usd),
+                collateral_delta_amount: 0,
+                trigger_price,
+                acceptable_price: 0,
+                execution_fee,
+                expires_at: None,
+                time_in_force: TimeInForce::Gtc,
+                referral_code: None,
+                receiver: None,
+                callback_contract: None,
+                callback_gas_limit: 0,
+                client_order_id: None,
+                tag,
+                position_key: Some(position_key),
+            };
+            bracket.acceptable_price = Self::slippage_bound(&bracket, trigger_price, max_slippage_bps);
+            let ExecutionResult::Saved { order_key } = Self::save_order(caller, bracket)? else {
+                return Err(Error::UnsupportedOrderType);
+            };
+            *slot = Some(order_key);
+        }
+
+        if let [Some(stop_loss), Some(take_profit)] = keys {
+            let mut st = PerpetualDEXState::get_mut();
+            for (key, linked) in [(stop_loss, take_profit), (take_profit, stop_loss)] {
+                if let Some(order) = st.orders.get_mut(&key) {
+                    order.linked_order = Some(linked);
+                }
+            }
+        }
+        Ok(keys)
     }
 
     fn execute_market_order(caller: ActorId, params: CreateOrderParams) -> Result<ExecutionResult, Error> {
@@ -50,7 +770,24 @@ impl TradingModule {
         };
 
         Self::validate_execution_price(&params, quote.execution_price)?;
-        let key = Self::execute_position_change(caller, &params, quote.execution_price)?;
+
+        // Resting book liquidity priced better than the pool quote is taken first; book fills
+        // land on the canonical position, so sub-position orders trade against the pool only
+        if params.order_type == OrderType::MarketIncrease
+            && params.position_key.is_none()
+            && OrderBookModule::is_enabled(&params.market)
+            && Self::netting_position(caller, &params).is_none()
+        {
+            let is_long = matches!(params.side, OrderSide::Long);
+            let fills =
+                OrderBookModule::match_fills(&params.market, is_long, quote.execution_price, params.size_delta_usd);
+            if !fills.is_empty() {
+                return OrderBookModule::execute_taker(caller, &params, fills, &quote);
+            }
+        }
+
+        let key = Self::execute_charged(caller, &params, quote.execution_price, |cfg| cfg.trading_fee_bps)?;
+        Self::record_execution(&params, &quote);
         Ok(ExecutionResult::Executed {
             position_key: key,
             execution_price: quote.execution_price,
@@ -69,25 +806,229 @@ impl TradingModule {
         };
 
         Self::validate_execution_price(&params, quote.execution_price)?;
-        let key = Self::execute_position_change(caller, &params, quote.execution_price)?;
+        // Immediate orders fill at or better than their trigger, spread and impact included
+        if !Self::within_price_bound(&params, params.trigger_price, quote.execution_price) {
+            match params.time_in_force {
+                TimeInForce::Gtc => {}
+                TimeInForce::Ioc => return Ok(ExecutionResult::Cancelled),
+                TimeInForce::Fok => return Err(Error::FillOrKillNotFilled),
+            }
+        }
+        let key = Self::execute_charged(caller, &params, quote.execution_price, |cfg| cfg.trading_fee_bps)?;
+        Self::record_execution(&params, &quote);
         Ok(ExecutionResult::Executed {
             position_key: key,
             execution_price: quote.execution_price,
         })
     }
 
+    /// Execute the position change of an order and charge the pool trading fee at the market's
+    /// rate picked by `fee_bps`, less the account's fee discount, into the LP claimable fees of the
+    /// order's side. An increase pays the fee out of the order collateral and a decrease out of its
+    /// payout; the order fails when the fee can't be paid in full.
+    fn execute_charged(
+        account: ActorId,
+        params: &CreateOrderParams,
+        price: u128,
+        fee_bps: impl Fn(&MarketConfig) -> u16,
+    ) -> Result<PositionKey, Error> {
+        let fee = {
+            let st = PerpetualDEXState::get();
+            let bps = st.market_configs.get(&params.market).map_or(0, fee_bps);
+            if Self::is_increase(&params.order_type)
+                && st.balances.get(&account).copied().unwrap_or(0) < params.collateral_delta_amount
+            {
+                return Err(Error::InsufficientBalance);
+            }
+            FeeDiscountModule::trading_fee(account, params.size_delta_usd, bps as u128)
+        };
+        let key = if Self::is_increase(&params.order_type) {
+            let mut net = params.clone();
+            net.collateral_delta_amount =
+                params.collateral_delta_amount.checked_sub(fee).ok_or(Error::InsufficientCollateral)?;
+            Self::execute_position_change(account, &net, price, 0)?
+        } else {
+            Self::execute_position_change(account, params, price, fee)?
+        };
+        OrderBookModule::collect_fee(&params.market, account, matches!(params.side, OrderSide::Long), fee)?;
+        Ok(key)
+    }
+
+    /// Record execution quality; adverse price impact is also tracked as a pool fee inflow
+    pub fn record_execution(params: &CreateOrderParams, quote: &QuoteResult) {
+        let now = exec::block_timestamp();
+        let is_increase = Self::is_increase(&params.order_type);
+        let is_buy = matches!(params.side, OrderSide::Long) == is_increase;
+
+        PricingModule::record_block_flow(
+            &params.market,
+            &params.side,
+            params.size_delta_usd,
+            is_increase,
+            quote.price_impact_usd,
+        );
+
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        StatsModule::record_execution(
+            &mut st.slippage_stats,
+            &params.market,
+            is_buy,
+            params.size_delta_usd,
+            quote.execution_price,
+            quote.mid_price,
+            quote.price_impact_usd,
+            now,
+        );
+        if quote.price_impact_usd < 0 {
+            StatsModule::record_fee(
+                &mut st.market_fee_stats,
+                &params.market,
+                FeeKind::PriceImpact,
+                quote.price_impact_usd.unsigned_abs(),
+                now,
+            );
+        }
+    }
+
+    /// Worst acceptable price `slippage_bps` away from `mid`, in the direction that hurts the trader
+    fn slippage_bound(p: &CreateOrderParams, mid: u128, slippage_bps: u16) -> u128 {
+        let is_long = matches!(p.side, OrderSide::Long);
+        let is_increase = Self::is_increase(&p.order_type);
+        let delta = mid.saturating_mul(slippage_bps as u128) / 10_000;
+        if is_long == is_increase {
+            mid.saturating_add(delta)
+        } else {
+            mid.saturating_sub(delta)
+        }
+    }
+
+    /// Trigger `distance_bps` away from `reference`, on the profit or loss side of the position
+    fn trigger_from_distance(reference: u128, distance_bps: u16, is_long: bool, is_profit: bool) -> u128 {
+        let distance = reference.saturating_mul(distance_bps as u128) / 10_000;
+        // Longs profit above entry, shorts below
+        if is_long == is_profit {
+            reference.saturating_add(distance)
+        } else {
+            reference.saturating_sub(distance)
+        }
+    }
+
+    /// Take-profit (`LimitDecrease`) or stop-loss (`StopLossDecrease`) order whose trigger is
+    /// `distance_bps` from the position's entry or the current mark, with the acceptable price
+    /// `max_slippage_bps` beyond the trigger.
+    pub fn create_pct_trigger_order(
+        caller: ActorId,
+        mut params: CreateOrderParams,
+        distance_bps: u16,
+        reference: TriggerReference,
+        max_slippage_bps: u16,
+    ) -> Result<ExecutionResult, Error> {
+        let is_profit = match params.order_type {
+            OrderType::LimitDecrease => true,
+            OrderType::StopLossDecrease => false,
+            _ => return Err(Error::UnsupportedOrderType),
+        };
+        if distance_bps == 0 || distance_bps >= 10_000 || max_slippage_bps >= 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        let is_long = matches!(params.side, OrderSide::Long);
+        let reference_price = match reference {
+            TriggerReference::Entry => {
+                PositionModule::get_position(&Self::target_position_key(caller, &params))?.entry_price_usd
+            }
+            TriggerReference::Mark => OracleModule::mid(&utils::price_key(&params.market))?,
+        };
+
+        params.trigger_price = Self::trigger_from_distance(reference_price, distance_bps, is_long, is_profit);
+        params.acceptable_price = Self::slippage_bound(&params, params.trigger_price, max_slippage_bps);
+        Self::create_order(caller, params)
+    }
+
+    /// Register the account's default take-profit / stop-loss orders for a freshly executed market open
+    fn register_default_tp_sl(
+        caller: ActorId,
+        params: &CreateOrderParams,
+        result: &ExecutionResult,
+        settings: &AccountSettings,
+    ) -> Result<(), Error> {
+        let ExecutionResult::Executed { position_key, execution_price } = result else {
+            return Ok(());
+        };
+        // A one-way open may flip the position, so the brackets follow the resulting side
+        let is_long = PositionModule::get_position(position_key)?.is_long;
+        let execution_fee = Self::estimate_execution_fee(&OrderType::LimitDecrease, &params.market)?;
+        let trigger = |distance_bps: Option<u16>, is_profit: bool| {
+            distance_bps.map_or(0, |bps| Self::trigger_from_distance(*execution_price, bps, is_long, is_profit))
+        };
+        Self::save_brackets(
+            caller,
+            *position_key,
+            params.size_delta_usd,
+            [trigger(settings.default_stop_loss_bps, false), trigger(settings.default_take_profit_bps, true)],
+            execution_fee,
+            settings.default_max_slippage_bps.unwrap_or(0),
+            params.tag,
+        )?;
+        Ok(())
+    }
+
     fn save_order(caller: ActorId, params: CreateOrderParams) -> Result<ExecutionResult, Error> {
+        Self::save_order_with_callbacks(caller, params, 1)
+    }
+
+    /// Save an order whose callback contract is notified up to `callbacks` times
+    fn save_order_with_callbacks(
+        caller: ActorId,
+        params: CreateOrderParams,
+        callbacks: u32,
+    ) -> Result<ExecutionResult, Error> {
+        // Only good-till-cancelled orders may rest
+        if params.time_in_force != TimeInForce::Gtc {
+            return Err(Error::InvalidParameter);
+        }
         let now_block = exec::block_height();
         let now_time = exec::block_timestamp();
+        let receiver = Self::payout_receiver(caller, &params);
 
         let mut st = PerpetualDEXState::get_mut();
-        let key = st.generate_request_key();
+        st.ensure_order_capacity()?;
+        if Self::is_increase(&params.order_type) {
+            st.ensure_trading_enabled(caller)?;
+        }
+        // An order carrying a client id is saved under the key claimed in `prepare_order`
+        let claimed = params.client_order_id.and_then(|id| st.client_order_ids.get(&(caller, id)).copied());
+        let key = match claimed {
+            Some(key) => key,
+            None => st.generate_request_key(),
+        };
+
+        // Escrow the callback gas in full and the keeper gas refund as far as the native balance goes
+        let native_fee = {
+            let refund = st.execution_fee_config.keeper_gas_refund;
+            let callback_fee = match params.callback_contract {
+                Some(_) => st
+                    .execution_fee_config
+                    .callback_gas_price
+                    .saturating_mul(params.callback_gas_limit as u128)
+                    .saturating_mul(callbacks as u128),
+                None => 0,
+            };
+            let native = st.native_balances.entry(caller).or_insert(0);
+            if *native < callback_fee {
+                return Err(Error::InsufficientBalance);
+            }
+            *native -= callback_fee;
+            let escrow = refund.min(*native);
+            *native -= escrow;
+            callback_fee.saturating_add(escrow)
+        };
 
         let order = Order {
             key,
             account: caller,
-            receiver: caller,
-            callback_contract: None,
+            receiver,
+            callback_contract: params.callback_contract,
             market: params.market,
             collateral_token: params.collateral_token,
             order_type: params.order_type,
@@ -100,84 +1041,485 @@ impl TradingModule {
             is_frozen: false,
             status: OrderStatus::Created,
             execution_fee: params.execution_fee,
-            callback_gas_limit: 0,
+            native_fee,
+            priority_fee: 0,
+            expires_at: params.expires_at,
+            filled_size_usd: 0,
+            twap: None,
+            stop_limit: None,
+            callback_gas_limit: params.callback_gas_limit,
+            client_order_id: params.client_order_id,
+            tag: params.tag,
+            position_key: params.position_key,
+            linked_order: None,
             created_at_block: now_block,
             created_at_time: now_time,
             updated_at_block: now_block,
             updated_at_time: now_time,
         };
 
-        st.orders.insert(key, order);
-        st.account_orders.entry(caller).or_insert_with(Vec::new).push(key);
+        st.orders.insert(key, order);
+        st.open_orders = st.open_orders.saturating_add(1);
+        st.account_orders.entry(caller).or_insert_with(Vec::new).push(key);
+
+        Ok(ExecutionResult::Saved { order_key: key })
+    }
+
+    pub fn execute_saved_order(executor: ActorId, key: RequestKey) -> Result<ExecutionResult, Error> {
+        KeeperModule::ensure_keeper_turn(executor)?;
+        let result = Self::execute_saved(executor, key);
+        if let Ok(ExecutionResult::Executed { .. }) = result {
+            KeeperModule::record_keeper_activity(executor);
+        }
+        result
+    }
+
+    fn execute_saved(executor: ActorId, key: RequestKey) -> Result<ExecutionResult, Error> {
+        let (market, is_twap) = {
+            let st = PerpetualDEXState::get();
+            st.ensure_not_withdraw_only()?;
+            let order = st.orders.get(&key);
+            (order.map(|o| o.market.clone()), order.is_some_and(|o| o.twap.is_some()))
+        };
+        if let Some(market) = market {
+            MarketModule::activate_scheduled_config(&market);
+        }
+        if is_twap {
+            return Self::execute_twap_tranche(executor, key);
+        }
+
+        // --- Snapshot phase (immutable state) ---
+        let (order, params, quote, limit_met) = {
+            let st = PerpetualDEXState::get();
+
+            let order = st.orders.get(&key).cloned().ok_or(Error::OrderNotFound)?;
+
+            if order.status != OrderStatus::Created {
+                return Err(Error::OrderAlreadyProcessed);
+            }
+            if Self::is_expired(&order, exec::block_timestamp()) {
+                return Err(Error::OrderExpired);
+            }
+            st.ensure_market_allows(&order.market, Self::is_increase(&order.order_type))?;
+
+            let price_key = utils::price_key(&order.market);
+            OracleModule::ensure_fresh(&price_key)?;
+            let mid = OracleModule::mid(&price_key)?;
+
+            let params = Self::order_to_params(&order);
+            let triggered = order.stop_limit.as_ref().is_some_and(|s| s.triggered_at.is_some());
+            if !triggered && !Self::can_execute_limit_order(&params, mid) {
+                return Err(Error::OrderCannotBeExecutedYet);
+            }
+
+            let mut quote = match order.order_type {
+                OrderType::LimitIncrease | OrderType::StopLimitIncrease => {
+                    PricingModule::quote_increase(&order.market, &params.side, params.size_delta_usd)?
+                }
+                OrderType::LimitDecrease | OrderType::StopLossDecrease | OrderType::StopLimitDecrease => {
+                    PricingModule::quote_decrease(&order.market, &params.side, params.size_delta_usd)?
+                }
+                _ => return Err(Error::UnsupportedOrderType),
+            };
+            if st.market_configs.get(&order.market).is_some_and(|c| c.limit_fill_at_trigger) {
+                let fill_price = Self::limit_fill_price(&params, quote.execution_price);
+                // A fill at the trigger replaces the quoted spread and impact, so no impact is recorded
+                if fill_price != quote.execution_price {
+                    quote.execution_price = fill_price;
+                    quote.price_impact_usd = 0;
+                }
+            }
+
+            let limit_met = order
+                .stop_limit
+                .as_ref()
+                .is_none_or(|s| Self::within_price_bound(&params, s.limit_price, quote.execution_price));
+            if limit_met {
+                Self::validate_execution_price(&params, quote.execution_price)?;
+                Self::check_execution_price_guard(
+                    st.execution_price_guard_bps,
+                    quote.execution_price,
+                    order.trigger_price,
+                    mid,
+                )?;
+            }
+
+            (order, params, quote, limit_met)
+        };
+        // A triggered stop-limit order stays open until a fill meets its limit price
+        if !limit_met {
+            Self::mark_stop_triggered(key);
+            return Ok(ExecutionResult::Saved { order_key: key });
+        }
+        let execution_price = quote.execution_price;
+
+        // --- Position / pool mutation (handled inside modules) ---
+        // Resting orders filled later by keepers pay the lower maker-like rate
+        let position_key =
+            Self::execute_charged(order.account, &params, execution_price, |cfg| cfg.keeper_order_fee_bps)?;
+        Self::record_execution(&params, &quote);
+
+        // --- Final mutation: execution fee + order status ---
+        {
+            let now_block = exec::block_height();
+            let now_time = exec::block_timestamp();
+            let mut st = PerpetualDEXState::get_mut();
+
+            let mut execution_fee_usd = 0;
+            if executor != order.account && order.execution_fee > 0 {
+                if let Some(b) = st.balances.get_mut(&order.account) {
+                    if *b >= order.execution_fee {
+                        *b = b.saturating_sub(order.execution_fee);
+                        let exb = st.balances.entry(executor).or_insert(0);
+                        *exb = exb.saturating_add(order.execution_fee);
+                        execution_fee_usd = order.execution_fee;
+                    }
+                }
+            }
+
+            let (settled_fees_usd, realized_pnl) = Self::last_fill_outcome(&st, order.account, position_key);
+            Self::store_execution_details(
+                &mut st,
+                ExecutionDetails {
+                    order_key: key,
+                    position_key,
+                    executor,
+                    size_delta_usd: params.size_delta_usd,
+                    execution_price,
+                    mid_price: quote.mid_price,
+                    price_impact_usd: quote.price_impact_usd,
+                    settled_fees_usd,
+                    realized_pnl,
+                    execution_fee_usd,
+                    keeper_native_fee: order.native_fee.saturating_add(order.priority_fee),
+                    timestamp: now_time,
+                    block: now_block,
+                },
+            );
+
+            if let Some(om) = st.orders.get_mut(&key) {
+                if om.status != OrderStatus::Created {
+                    return Err(Error::OrderAlreadyProcessed);
+                }
+                om.status = OrderStatus::Executed;
+                om.updated_at_block = now_block;
+                om.updated_at_time = now_time;
+                om.native_fee = 0;
+                om.priority_fee = 0;
+            } else {
+                return Err(Error::OrderNotFound);
+            }
+            st.open_orders = st.open_orders.saturating_sub(1);
+            StatsModule::record_order_executed(
+                &mut st.order_execution_stats,
+                &order.market,
+                now_time.saturating_sub(order.created_at_time),
+            );
+            NotificationModule::push(
+                &mut st.notifications,
+                order.account,
+                NoticeKind::OrderExecuted {
+                    order_key: key,
+                    position_key,
+                    size_delta_usd: params.size_delta_usd,
+                    execution_price,
+                    tag: order.tag,
+                },
+            );
+        }
+
+        Self::pay_native(executor, order.native_fee.saturating_add(order.priority_fee));
+        // The other leg of a bracket is no longer needed
+        if let Some(linked) = order.linked_order {
+            let _ = Self::cancel_order(order.account, linked);
+        }
+        EquityModule::sample(order.account);
+        Self::send_callback(
+            &order,
+            OrderCallbackOutcome::Executed {
+                position_key,
+                size_delta_usd: params.size_delta_usd,
+                execution_price,
+            },
+        );
+
+        Ok(ExecutionResult::Executed {
+            position_key,
+            execution_price,
+        })
+    }
+
+    /// Save a TWAP order: `size_delta_usd` (and collateral) split into `tranches` equal market
+    /// orders, the first executable right away and each next one `interval_seconds` later.
+    /// Every tranche must fill within `acceptable_price`; the execution fee is split across them.
+    pub fn create_twap_order(
+        caller: ActorId,
+        mut params: CreateOrderParams,
+        tranches: u32,
+        interval_seconds: u64,
+    ) -> Result<ExecutionResult, Error> {
+        if !matches!(params.order_type, OrderType::TwapIncrease | OrderType::TwapDecrease) {
+            return Err(Error::UnsupportedOrderType);
+        }
+        if !(2..=MAX_TWAP_TRANCHES).contains(&tranches) || interval_seconds == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        if params.size_delta_usd < tranches as u128 {
+            return Err(Error::InvalidOrderSize);
+        }
+        Self::prepare_order(caller, &mut params)?;
+
+        // Every tranche notifies the callback contract
+        let referral_code = params.referral_code.clone();
+        let result = Self::save_order_with_callbacks(caller, params, tranches)?;
+        Self::bind_referrer(caller, referral_code.as_deref());
+        if let ExecutionResult::Saved { order_key } = &result
+            && let Some(order) = PerpetualDEXState::get_mut().orders.get_mut(order_key)
+        {
+            order.twap = Some(TwapState {
+                tranches,
+                executed_tranches: 0,
+                interval_seconds,
+                next_execution_at: exec::block_timestamp(),
+            });
+        }
+        Ok(result)
+    }
+
+    /// Save a stop-limit order: once the oracle mid crosses `trigger_price` it rests as a limit
+    /// order that only fills at `limit_price` or better (and within `acceptable_price`).
+    pub fn create_stop_limit_order(
+        caller: ActorId,
+        mut params: CreateOrderParams,
+        limit_price: u128,
+    ) -> Result<ExecutionResult, Error> {
+        if !matches!(params.order_type, OrderType::StopLimitIncrease | OrderType::StopLimitDecrease) {
+            return Err(Error::UnsupportedOrderType);
+        }
+        if limit_price == 0 {
+            return Err(Error::InvalidPrice);
+        }
+        Self::prepare_order(caller, &mut params)?;
+        Self::validate_projected_leverage(caller, &params)?;
+
+        let referral_code = params.referral_code.clone();
+        let result = Self::save_order(caller, params)?;
+        Self::bind_referrer(caller, referral_code.as_deref());
+        if let ExecutionResult::Saved { order_key } = &result
+            && let Some(order) = PerpetualDEXState::get_mut().orders.get_mut(order_key)
+        {
+            order.stop_limit = Some(StopLimitState {
+                limit_price,
+                triggered_at: None,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Record that a stop-limit order's stop has triggered, so it keeps resting at its limit
+    fn mark_stop_triggered(key: RequestKey) {
+        let now_time = exec::block_timestamp();
+        let mut st = PerpetualDEXState::get_mut();
+        if let Some(order) = st.orders.get_mut(&key)
+            && let Some(stop) = order.stop_limit.as_mut()
+            && stop.triggered_at.is_none()
+        {
+            stop.triggered_at = Some(now_time);
+            order.updated_at_block = exec::block_height();
+            order.updated_at_time = now_time;
+        }
+    }
+
+    /// Keep `details` for its order (replacing an earlier tranche's), dropping the oldest beyond
+    /// `MAX_EXECUTION_DETAILS`
+    fn store_execution_details(st: &mut PerpetualDEXState, details: ExecutionDetails) {
+        let key = details.order_key;
+        if st.execution_details.insert(key, details).is_none() {
+            st.execution_detail_keys.push_back(key);
+        }
+        while st.execution_detail_keys.len() > MAX_EXECUTION_DETAILS {
+            if let Some(oldest) = st.execution_detail_keys.pop_front() {
+                st.execution_details.remove(&oldest);
+            }
+        }
+    }
+
+    /// Settled fees and realized PnL of the fill just recorded for `position_key` (the opened
+    /// remainder for netted orders)
+    fn last_fill_outcome(st: &PerpetualDEXState, account: ActorId, position_key: PositionKey) -> (i128, i128) {
+        st.account_fills
+            .get(&account)
+            .and_then(|fills| fills.back())
+            .filter(|fill| fill.position_key == position_key)
+            .map(|fill| (fill.settled_fees_usd, fill.realized_pnl))
+            .unwrap_or_default()
+    }
+
+    /// Market-order parameters for a TWAP order's next tranche. Collateral and execution fee
+    /// are split pro rata so the tranches add up to the order's totals exactly.
+    fn twap_tranche_params(order: &Order, twap: &TwapState) -> CreateOrderParams {
+        let remaining_tranches = twap.tranches.saturating_sub(twap.executed_tranches).max(1) as u128;
+        let remaining_size = order.size_delta_usd.saturating_sub(order.filled_size_usd);
+        let size = remaining_size / remaining_tranches;
+        let size = if remaining_tranches == 1 { remaining_size } else { size };
+
+        let filled_after = order.filled_size_usd.saturating_add(size);
+        let share = |total: u128, filled: u128| total.saturating_mul(filled) / order.size_delta_usd.max(1);
+        let collateral = share(order.collateral_delta_amount, filled_after)
+            .saturating_sub(share(order.collateral_delta_amount, order.filled_size_usd));
+        let tranches = twap.tranches.max(1) as u128;
+        let executed = twap.executed_tranches as u128;
+        let fee = order.execution_fee.saturating_mul(executed + 1) / tranches
+            - order.execution_fee.saturating_mul(executed) / tranches;
 
-        Ok(ExecutionResult::Saved { order_key: key })
+        let mut params = Self::order_to_params(order);
+        params.order_type = match order.order_type {
+            OrderType::TwapIncrease => OrderType::MarketIncrease,
+            _ => OrderType::MarketDecrease,
+        };
+        params.size_delta_usd = size;
+        params.collateral_delta_amount = collateral;
+        params.execution_fee = fee;
+        params
     }
 
-    pub fn execute_saved_order(executor: ActorId, key: RequestKey) -> Result<ExecutionResult, Error> {
-        // --- Snapshot phase (immutable state) ---
-        let (order, params, execution_price) = {
+    /// Execute the next tranche of a TWAP order once its interval has passed. A decrease tranche
+    /// that closes the position (dust included) completes the order.
+    fn execute_twap_tranche(executor: ActorId, key: RequestKey) -> Result<ExecutionResult, Error> {
+        let now_time = exec::block_timestamp();
+        let (order, params, quote) = {
             let st = PerpetualDEXState::get();
-
             let order = st.orders.get(&key).cloned().ok_or(Error::OrderNotFound)?;
-
             if order.status != OrderStatus::Created {
                 return Err(Error::OrderAlreadyProcessed);
             }
-
+            if Self::is_expired(&order, now_time) {
+                return Err(Error::OrderExpired);
+            }
+            st.ensure_market_allows(&order.market, Self::is_increase(&order.order_type))?;
+            let twap = order.twap.as_ref().ok_or(Error::UnsupportedOrderType)?;
+            if now_time < twap.next_execution_at {
+                return Err(Error::OrderCannotBeExecutedYet);
+            }
             let price_key = utils::price_key(&order.market);
             OracleModule::ensure_fresh(&price_key)?;
             let mid = OracleModule::mid(&price_key)?;
 
-            let params = Self::order_to_params(&order);
-            if !Self::can_execute_limit_order(&params, mid) {
-                return Err(Error::OrderCannotBeExecutedYet);
-            }
-
-            let quote = match order.order_type {
-                OrderType::LimitIncrease => {
+            let params = Self::twap_tranche_params(&order, twap);
+            let quote = match params.order_type {
+                OrderType::MarketIncrease => {
                     PricingModule::quote_increase(&order.market, &params.side, params.size_delta_usd)?
                 }
-                OrderType::LimitDecrease | OrderType::StopLossDecrease => {
-                    PricingModule::quote_decrease(&order.market, &params.side, params.size_delta_usd)?
-                }
-                _ => return Err(Error::UnsupportedOrderType),
+                _ => PricingModule::quote_decrease(&order.market, &params.side, params.size_delta_usd)?,
             };
-
             Self::validate_execution_price(&params, quote.execution_price)?;
-
-            (order, params, quote.execution_price)
+            // Tranches have no trigger; the mid is the only reference
+            Self::check_execution_price_guard(st.execution_price_guard_bps, quote.execution_price, mid, mid)?;
+            (order, params, quote)
         };
+        let execution_price = quote.execution_price;
 
-        // --- Position / pool mutation (handled inside modules) ---
-        let position_key = Self::execute_position_change(order.account, &params, execution_price)?;
+        // Keeper-run tranches pay the resting-order rate, like other saved orders
+        let position_key =
+            Self::execute_charged(order.account, &params, execution_price, |cfg| cfg.keeper_order_fee_bps)?;
+        Self::record_execution(&params, &quote);
 
-        // --- Final mutation: execution fee + order status ---
-        {
+        let completed = {
             let now_block = exec::block_height();
-            let now_time = exec::block_timestamp();
             let mut st = PerpetualDEXState::get_mut();
 
-            if executor != order.account && order.execution_fee > 0 {
+            let mut execution_fee_usd = 0;
+            if executor != order.account
+                && params.execution_fee > 0
+                && st.balances.get(&order.account).is_some_and(|b| *b >= params.execution_fee)
+            {
                 if let Some(b) = st.balances.get_mut(&order.account) {
-                    if *b >= order.execution_fee {
-                        *b = b.saturating_sub(order.execution_fee);
-                        let exb = st.balances.entry(executor).or_insert(0);
-                        *exb = exb.saturating_add(order.execution_fee);
-                    }
+                    *b -= params.execution_fee;
                 }
+                let exb = st.balances.entry(executor).or_insert(0);
+                *exb = exb.saturating_add(params.execution_fee);
+                execution_fee_usd = params.execution_fee;
             }
 
-            if let Some(om) = st.orders.get_mut(&key) {
-                if om.status != OrderStatus::Created {
-                    return Err(Error::OrderAlreadyProcessed);
+            // A decrease that left dust closes the whole position; nothing is left for later tranches
+            let position_closed = !Self::is_increase(&params.order_type) && !st.positions.contains_key(&position_key);
+            let om = st.orders.get_mut(&key).ok_or(Error::OrderNotFound)?;
+            om.filled_size_usd = om.filled_size_usd.saturating_add(params.size_delta_usd);
+            om.updated_at_block = now_block;
+            om.updated_at_time = now_time;
+            let completed = match om.twap.as_mut() {
+                Some(twap) => {
+                    twap.executed_tranches += 1;
+                    twap.next_execution_at = now_time.saturating_add(twap.interval_seconds);
+                    twap.executed_tranches >= twap.tranches || position_closed
                 }
-                om.status = OrderStatus::Executed;
-                om.updated_at_block = now_block;
-                om.updated_at_time = now_time;
-            } else {
-                return Err(Error::OrderNotFound);
+                None => true,
+            };
+
+            // Details of the latest tranche
+            let (settled_fees_usd, realized_pnl) = Self::last_fill_outcome(&st, order.account, position_key);
+            Self::store_execution_details(
+                &mut st,
+                ExecutionDetails {
+                    order_key: key,
+                    position_key,
+                    executor,
+                    size_delta_usd: params.size_delta_usd,
+                    execution_price,
+                    mid_price: quote.mid_price,
+                    price_impact_usd: quote.price_impact_usd,
+                    settled_fees_usd,
+                    realized_pnl,
+                    execution_fee_usd,
+                    keeper_native_fee: if completed {
+                        order.native_fee.saturating_add(order.priority_fee)
+                    } else {
+                        0
+                    },
+                    timestamp: now_time,
+                    block: now_block,
+                },
+            );
+            if completed {
+                if let Some(om) = st.orders.get_mut(&key) {
+                    om.status = OrderStatus::Executed;
+                    om.native_fee = 0;
+                    om.priority_fee = 0;
+                }
+                st.open_orders = st.open_orders.saturating_sub(1);
+                StatsModule::record_order_executed(
+                    &mut st.order_execution_stats,
+                    &order.market,
+                    now_time.saturating_sub(order.created_at_time),
+                );
             }
+            NotificationModule::push(
+                &mut st.notifications,
+                order.account,
+                NoticeKind::OrderExecuted {
+                    order_key: key,
+                    position_key,
+                    size_delta_usd: params.size_delta_usd,
+                    execution_price,
+                    tag: order.tag,
+                },
+            );
+            completed
+        };
+
+        // The keeper that completes the order collects its escrowed native fees
+        if completed {
+            Self::pay_native(executor, order.native_fee.saturating_add(order.priority_fee));
         }
+        EquityModule::sample(order.account);
+        Self::send_callback(
+            &order,
+            OrderCallbackOutcome::Executed {
+                position_key,
+                size_delta_usd: params.size_delta_usd,
+                execution_price,
+            },
+        );
 
         Ok(ExecutionResult::Executed {
             position_key,
@@ -197,6 +1539,10 @@ impl TradingModule {
         if o.status != OrderStatus::Created {
             return Err(Error::OrderAlreadyProcessed);
         }
+        // Tranche sizes are derived from the original order; cancel and recreate instead
+        if o.twap.is_some() {
+            return Err(Error::UnsupportedOrderType);
+        }
 
         if let Some(v) = params.size_delta_usd {
             o.size_delta_usd = v;
@@ -228,9 +1574,321 @@ impl TradingModule {
         o.status = OrderStatus::Cancelled;
         o.updated_at_block = now_block;
         o.updated_at_time = now_time;
+        let refund = core::mem::take(&mut o.native_fee).saturating_add(core::mem::take(&mut o.priority_fee));
+        let order = o.clone();
+        st.open_orders = st.open_orders.saturating_sub(1);
+        drop(st);
+
+        Self::credit_native(order.account, refund);
+        Self::send_callback(&order, OrderCallbackOutcome::Cancelled);
+        Ok(())
+    }
+
+    /// Move `amount` of the caller's native balance into the order's priority fee.
+    /// Returns the order's total priority fee.
+    pub fn add_priority_fee(caller: ActorId, key: RequestKey, amount: u128) -> Result<u128, Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        let o = st.orders.get_mut(&key).ok_or(Error::OrderNotFound)?;
+        if o.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        if o.status != OrderStatus::Created {
+            return Err(Error::OrderAlreadyProcessed);
+        }
+        let native = st.native_balances.entry(caller).or_insert(0);
+        if *native < amount {
+            return Err(Error::InsufficientBalance);
+        }
+        *native -= amount;
+        o.priority_fee = o.priority_fee.saturating_add(amount);
+        Ok(o.priority_fee)
+    }
+
+    /// Fully close a position at the last committed oracle mid while the market's feed is down.
+    /// Only available once the price is stale beyond the market's outage grace period; a safety
+    /// fee is charged to the pool on top of normal fee settlement.
+    pub fn close_at_last_price(caller: ActorId, key: PositionKey) -> Result<ExecutionResult, Error> {
+        let pos = PositionModule::get_position(&key)?;
+        if pos.account != caller {
+            return Err(Error::Unauthorized);
+        }
+        let cfg = {
+            let st = PerpetualDEXState::get();
+            st.ensure_not_withdraw_only()?;
+            st.market_configs.get(&pos.market).ok_or(Error::MarketNotFound)?.clone()
+        };
+        let price_key = utils::price_key(&pos.market);
+        if cfg.outage_grace_seconds == 0 || !OracleModule::is_in_outage(&price_key, cfg.outage_grace_seconds) {
+            return Err(Error::MarketNotInOutage);
+        }
+        let last_price = OracleModule::mid(&price_key)?;
+
+        // Covers the position, the pool and the caller's balance the fee is debited from
+        let snapshot = PerpetualDEXState::snapshot_position(&pos);
+        if let Err(e) = Self::apply_last_price_close(caller, &pos, last_price, cfg.outage_close_fee_bps) {
+            PerpetualDEXState::restore_position(snapshot);
+            return Err(e);
+        }
+
+        Ok(ExecutionResult::Executed {
+            position_key: key,
+            execution_price: last_price,
+        })
+    }
+
+    fn apply_last_price_close(caller: ActorId, pos: &Position, last_price: u128, fee_bps: u16) -> Result<(), Error> {
+        RiskModule::accrue_pool(&pos.market, exec::block_timestamp())?;
+        PositionModule::decrease_position_at(pos.key, caller, pos.size_usd, pos.collateral_usd, last_price)?;
+
+        let fee = pos.size_usd.saturating_mul(fee_bps as u128) / 10_000;
+        let mut st = PerpetualDEXState::get_mut();
+        let bal = st.balances.entry(caller).or_insert(0);
+        if *bal < fee {
+            return Err(Error::InsufficientBalance);
+        }
+        *bal -= fee;
+        let pool = st.pool_amounts.get_mut(&pos.market).ok_or(Error::MarketNotFound)?;
+        pool.liquidity_usd = pool.liquidity_usd.saturating_add(fee);
         Ok(())
     }
 
+    /// Cancel saved orders created before `cutoff_time`, refunding escrowed native and priority fees,
+    /// with the bounded sweep walk over up to `limit` request ids. Returns the number of orders expired.
+    pub fn expire_orders(cutoff_time: u64, limit: u32) -> u32 {
+        let sweep = Self::sweep_orders(&mut PerpetualDEXState::get_mut(), cutoff_time, limit, 0);
+        Self::finish_sweep(sweep).len() as u32
+    }
+
+    /// Cancel saved orders past their good-till-time, refunding escrowed native fees to their
+    /// owners (callable by keepers/admin). Returns the number of orders cancelled.
+    pub fn cancel_expired_orders(caller: ActorId) -> Result<u32, Error> {
+        {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+        }
+        let now = exec::block_timestamp();
+        let expired = Self::cancel_orders_where(|_, o| Self::is_expired(o, now));
+        Self::record_expired(&expired);
+        Ok(expired.len() as u32)
+    }
+
+    /// Cancel every pending order on `market`, refunding escrowed native fees
+    pub fn cancel_market_orders(market: &str) -> u32 {
+        Self::cancel_orders_where(|_, o| o.market == market).len() as u32
+    }
+
+    /// Sweep saved orders created more than `max_age_secs` ago in creation order, examining up to
+    /// `limit` request ids from the sweep cursor (keepers/admin). `max_age_secs` may not undercut
+    /// the configured stale age. Stale orders are cancelled and removed together with already
+    /// cancelled ones; the keeper earns `stale_sweep_reward_bps` of each swept order's escrowed
+    /// native fee and the rest goes back to the owner. Returns the swept keys.
+    pub fn sweep_stale_orders(caller: ActorId, max_age_secs: u64, limit: u32) -> Result<Vec<RequestKey>, Error> {
+        let now = exec::block_timestamp();
+        let sweep = {
+            let mut st = PerpetualDEXState::get_mut();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+            let cfg = &st.execution_fee_config;
+            if cfg.stale_order_min_age_seconds == 0 || max_age_secs < cfg.stale_order_min_age_seconds {
+                return Err(Error::InvalidParameter);
+            }
+            let reward_bps = cfg.stale_sweep_reward_bps as u128;
+            Self::sweep_orders(&mut st, now.saturating_sub(max_age_secs), limit, reward_bps)
+        };
+        Self::pay_native(caller, sweep.reward);
+        Ok(Self::finish_sweep(sweep))
+    }
+
+    /// Walk up to `limit` request ids from the sweep cursor, cancelling pending orders created
+    /// before `cutoff` and removing them together with already cancelled ones. `reward_bps` of
+    /// each cancelled order's escrowed native fee is set aside for the sweeper.
+    fn sweep_orders(st: &mut PerpetualDEXState, cutoff: u64, limit: u32, reward_bps: u128) -> OrderSweep {
+        let mut sweep = OrderSweep::default();
+
+        // Request ids grow with creation time, so the sweep stops at the first order too young
+        let mut removed = Vec::new();
+        let mut id = st.order_sweep_cursor;
+        let end = st.next_request_id.min(id.saturating_add(limit as u64));
+        while id < end {
+            let key = utils::request_key(id);
+            let Some(o) = st.orders.get_mut(&key) else {
+                id += 1;
+                continue;
+            };
+            if o.created_at_time >= cutoff {
+                break;
+            }
+            match o.status {
+                // TWAP orders run past the TTL by design; they end on completion, cancellation or expiry
+                OrderStatus::Created if o.twap.is_some() => {}
+                OrderStatus::Created => {
+                    o.status = OrderStatus::Cancelled;
+                    let escrow = core::mem::take(&mut o.native_fee);
+                    let paid = escrow.saturating_mul(reward_bps) / 10_000;
+                    sweep.reward = sweep.reward.saturating_add(paid);
+                    sweep
+                        .refunds
+                        .push((o.account, (escrow - paid).saturating_add(core::mem::take(&mut o.priority_fee))));
+                    if o.callback_contract.is_some() {
+                        sweep.callbacks.push(o.clone());
+                    }
+                    StatsModule::record_orders_expired(&mut st.order_execution_stats, &o.market, 1);
+                    sweep.swept.push(key);
+                    removed.push((o.account, key));
+                }
+                OrderStatus::Cancelled => removed.push((o.account, key)),
+                _ => {}
+            }
+            id += 1;
+        }
+        st.order_sweep_cursor = id;
+        st.open_orders = st.open_orders.saturating_sub(sweep.swept.len() as u64);
+
+        for (account, key) in removed {
+            st.orders.remove(&key);
+            if let Some(keys) = st.account_orders.get_mut(&account) {
+                keys.retain(|k| k != &key);
+            }
+        }
+        sweep
+    }
+
+    /// Refund a sweep's owners and notify its callback contracts; returns the swept keys
+    fn finish_sweep(sweep: OrderSweep) -> Vec<RequestKey> {
+        for (account, refund) in sweep.refunds {
+            Self::credit_native(account, refund);
+        }
+        for order in &sweep.callbacks {
+            Self::send_callback(order, OrderCallbackOutcome::Cancelled);
+        }
+        sweep.swept
+    }
+
+    /// Cancel all of the caller's pending orders, optionally only on `market`; returns the cancelled keys
+    pub fn cancel_all_orders(caller: ActorId, market: Option<String>) -> Vec<RequestKey> {
+        Self::cancel_orders_where(|_, o| o.account == caller && market.as_ref().is_none_or(|m| &o.market == m))
+    }
+
+    fn is_increase(order_type: &OrderType) -> bool {
+        matches!(
+            order_type,
+            OrderType::MarketIncrease
+                | OrderType::LimitIncrease
+                | OrderType::TwapIncrease
+                | OrderType::StopLimitIncrease
+        )
+    }
+
+    /// Count cancelled-by-age orders in their markets' execution stats
+    fn record_expired(keys: &[RequestKey]) {
+        let mut st = PerpetualDEXState::get_mut();
+        let st = &mut *st;
+        for key in keys {
+            if let Some(order) = st.orders.get(key) {
+                StatsModule::record_orders_expired(&mut st.order_execution_stats, &order.market, 1);
+            }
+        }
+    }
+
+    pub fn is_expired(order: &Order, now: u64) -> bool {
+        order.expires_at.is_some_and(|t| t <= now)
+    }
+
+    /// Cancel every pending order matching `pred` and refund its escrowed native fees;
+    /// returns the cancelled keys
+    fn cancel_orders_where(pred: impl Fn(&RequestKey, &Order) -> bool) -> Vec<RequestKey> {
+        let now_block = exec::block_height();
+        let now_time = exec::block_timestamp();
+
+        let mut refunds = Vec::new();
+        let mut cancelled = Vec::new();
+        let mut callbacks = Vec::new();
+        {
+            let mut st = PerpetualDEXState::get_mut();
+            for (key, o) in st.orders.iter_mut() {
+                if o.status != OrderStatus::Created || !pred(key, o) {
+                    continue;
+                }
+                o.status = OrderStatus::Cancelled;
+                o.updated_at_block = now_block;
+                o.updated_at_time = now_time;
+                let refund = core::mem::take(&mut o.native_fee);
+                refunds.push((o.account, refund.saturating_add(core::mem::take(&mut o.priority_fee))));
+                cancelled.push(*key);
+                if o.callback_contract.is_some() {
+                    callbacks.push(o.clone());
+                }
+            }
+        }
+
+        {
+            let mut st = PerpetualDEXState::get_mut();
+            st.open_orders = st.open_orders.saturating_sub(cancelled.len() as u64);
+        }
+        for (account, refund) in refunds {
+            Self::credit_native(account, refund);
+        }
+        for order in &callbacks {
+            Self::send_callback(order, OrderCallbackOutcome::Cancelled);
+        }
+        cancelled
+    }
+
+    /// Credit native value attached to a message to the account's gas-refund balance
+    pub fn credit_native(account: ActorId, amount: u128) {
+        if amount == 0 {
+            return;
+        }
+        let mut st = PerpetualDEXState::get_mut();
+        let native = st.native_balances.entry(account).or_insert(0);
+        *native = native.saturating_add(amount);
+    }
+
+    /// Send native value to a keeper; if the transfer cannot be sent it stays claimable in-program
+    pub fn pay_native(recipient: ActorId, amount: u128) {
+        if amount == 0 {
+            return;
+        }
+        if msg::send_bytes(recipient, b"", amount).is_err() {
+            Self::credit_native(recipient, amount);
+        }
+    }
+
+    /// Notify the order's callback contract, if any, with `gas_limit` gas paid for out of the
+    /// native fee escrowed at order creation. The message is
+    /// `("PerpDexCallback", "OnOrderUpdate", OrderCallback)`; a failed send is dropped.
+    fn send_callback(order: &Order, outcome: OrderCallbackOutcome) {
+        let Some(contract) = order.callback_contract else { return };
+        let callback = OrderCallback {
+            order_key: order.key,
+            account: order.account,
+            market: order.market.clone(),
+            tag: order.tag,
+            outcome,
+        };
+        let payload = ("PerpDexCallback", "OnOrderUpdate", callback).encode();
+        let _ = msg::send_bytes_with_gas(contract, payload, order.callback_gas_limit, 0);
+    }
+
+    /// Refund liquidation gas to the liquidator out of the liquidated owner's native balance
+    pub fn refund_liquidation_gas(owner: ActorId, liquidator: ActorId) {
+        let refund = {
+            let mut st = PerpetualDEXState::get_mut();
+            let cfg_refund = st.execution_fee_config.liquidation_gas_refund;
+            let Some(native) = st.native_balances.get_mut(&owner) else {
+                return;
+            };
+            let refund = cfg_refund.min(*native);
+            *native -= refund;
+            refund
+        };
+        Self::pay_native(liquidator, refund);
+    }
+
     fn validate_order_params(p: &CreateOrderParams) -> Result<(), Error> {
         if p.size_delta_usd == 0 {
             return Err(Error::InvalidOrderSize);
@@ -240,19 +1898,72 @@ impl TradingModule {
         }
         if matches!(
             p.order_type,
-            OrderType::LimitIncrease | OrderType::LimitDecrease | OrderType::StopLossDecrease
+            OrderType::LimitIncrease
+                | OrderType::LimitDecrease
+                | OrderType::StopLossDecrease
+                | OrderType::StopLimitIncrease
+                | OrderType::StopLimitDecrease
         ) && p.trigger_price == 0
         {
             return Err(Error::InvalidTriggerPrice);
         }
-        if matches!(p.order_type, OrderType::MarketIncrease | OrderType::LimitIncrease)
-            && p.collateral_delta_amount == 0
+        if Self::is_increase(&p.order_type) && p.collateral_delta_amount == 0
+        {
+            return Err(Error::InvalidCollateralAmount);
+        }
+        if p.expires_at.is_some_and(|t| t <= exec::block_timestamp()) {
+            return Err(Error::OrderExpired);
+        }
+        if p.callback_gas_limit > MAX_CALLBACK_GAS_LIMIT || p.receiver.is_some_and(|r| r == ActorId::zero()) {
+            return Err(Error::InvalidParameter);
+        }
+        Ok(())
+    }
+
+    /// Reject saved increase orders that would breach max leverage when executed against the
+    /// position as it stands now. Leverage is size over collateral in USD, so it does not depend
+    /// on the trigger price. One-way orders that net an opposite position are left to execution.
+    fn validate_projected_leverage(caller: ActorId, p: &CreateOrderParams) -> Result<(), Error> {
+        if !matches!(p.order_type, OrderType::LimitIncrease | OrderType::StopLimitIncrease)
+            || Self::netting_position(caller, p).is_some()
         {
+            return Ok(());
+        }
+        let st = PerpetualDEXState::get();
+        let cfg = st.market_configs.get(&p.market).ok_or(Error::MarketNotFound)?;
+        let key = Self::target_position_key(caller, p);
+        let (size_usd, collateral_usd) = st
+            .positions
+            .get(&key)
+            .map(|pos| (pos.size_usd, pos.collateral_usd))
+            .unwrap_or_default();
+
+        let size_usd = size_usd.saturating_add(p.size_delta_usd);
+        let collateral_usd = collateral_usd.saturating_add(p.collateral_delta_amount);
+        if collateral_usd == 0 {
             return Err(Error::InvalidCollateralAmount);
         }
+        let leverage_bps = size_usd.saturating_mul(10_000) / collateral_usd;
+        if leverage_bps > (cfg.max_leverage as u128).saturating_mul(10_000) {
+            return Err(Error::MaxLeverageExceeded);
+        }
         Ok(())
     }
 
+    /// Limit orders fill at their trigger when the quote is worse: buys (long increases, short
+    /// decreases) at no more than the trigger, sells at no less. Other order types keep the quote.
+    fn limit_fill_price(p: &CreateOrderParams, quote_price: u128) -> u128 {
+        if !matches!(p.order_type, OrderType::LimitIncrease | OrderType::LimitDecrease) {
+            return quote_price;
+        }
+        let is_buy = matches!(p.side, OrderSide::Long) == Self::is_increase(&p.order_type);
+        if is_buy {
+            quote_price.min(p.trigger_price)
+        } else {
+            quote_price.max(p.trigger_price)
+        }
+    }
+
     fn can_execute_limit_order(p: &CreateOrderParams, current_price: u128) -> bool {
         let is_long = matches!(p.side, OrderSide::Long);
         match p.order_type {
@@ -270,28 +1981,58 @@ impl TradingModule {
                     current_price <= p.trigger_price
                 }
             }
-            OrderType::StopLossDecrease => {
+            OrderType::StopLossDecrease | OrderType::StopLimitDecrease => {
                 if is_long {
                     current_price <= p.trigger_price
                 } else {
                     current_price >= p.trigger_price
                 }
             }
+            OrderType::StopLimitIncrease => {
+                if is_long {
+                    current_price >= p.trigger_price
+                } else {
+                    current_price <= p.trigger_price
+                }
+            }
             _ => false,
         }
     }
 
     fn validate_execution_price(p: &CreateOrderParams, execution_price: u128) -> Result<(), Error> {
+        if !Self::within_price_bound(p, p.acceptable_price, execution_price) {
+            return Err(Error::PriceNotAcceptable);
+        }
+        Ok(())
+    }
+
+    /// `execution_price` is no worse for the trader than `bound`
+    fn within_price_bound(p: &CreateOrderParams, bound: u128, execution_price: u128) -> bool {
         let is_long = matches!(p.side, OrderSide::Long);
-        let is_increase = matches!(p.order_type, OrderType::MarketIncrease | OrderType::LimitIncrease);
-        let ok = match (is_long, is_increase) {
-            (true, true) => execution_price <= p.acceptable_price,
-            (true, false) => execution_price >= p.acceptable_price,
-            (false, true) => execution_price >= p.acceptable_price,
-            (false, false) => execution_price <= p.acceptable_price,
+        match (is_long, Self::is_increase(&p.order_type)) {
+            (true, true) => execution_price <= bound,
+            (true, false) => execution_price >= bound,
+            (false, true) => execution_price >= bound,
+            (false, false) => execution_price <= bound,
+        }
+    }
+
+    /// Saved-order execution price must sit within `guard_bps` of both the trigger and the
+    /// oracle mid. Bounds the damage a compromised keeper/oracle pair can do.
+    fn check_execution_price_guard(
+        guard_bps: u16,
+        execution_price: u128,
+        trigger_price: u128,
+        mid: u128,
+    ) -> Result<(), Error> {
+        if guard_bps == 0 {
+            return Ok(());
+        }
+        let within = |reference: u128| {
+            execution_price.abs_diff(reference).saturating_mul(10_000) <= reference.saturating_mul(guard_bps as u128)
         };
-        if !ok {
-            return Err(Error::PriceNotAcceptable);
+        if !within(trigger_price) || !within(mid) {
+            return Err(Error::ExecutionPriceOutOfBounds);
         }
         Ok(())
     }
@@ -307,26 +2048,49 @@ impl TradingModule {
             trigger_price: o.trigger_price,
             acceptable_price: o.acceptable_price,
             execution_fee: o.execution_fee,
+            expires_at: o.expires_at,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: Some(o.receiver),
+            callback_contract: o.callback_contract,
+            callback_gas_limit: o.callback_gas_limit,
+            client_order_id: None,
+            tag: o.tag,
+            position_key: o.position_key,
         }
     }
 
-    fn execute_position_change(caller: ActorId, p: &CreateOrderParams, price: u128) -> Result<PositionKey, Error> {
+    /// Who a decrease order pays out to: its own receiver, else the account's default receiver
+    fn payout_receiver(caller: ActorId, p: &CreateOrderParams) -> ActorId {
+        p.receiver.or_else(|| AccountModule::default_receiver(caller)).unwrap_or(caller)
+    }
+
+    /// Apply an order's position change; a decrease withholds `fee_usd` from its payout
+    fn execute_position_change(
+        caller: ActorId,
+        p: &CreateOrderParams,
+        price: u128,
+        fee_usd: u128,
+    ) -> Result<PositionKey, Error> {
         let now = exec::block_timestamp();
         RiskModule::accrue_pool(&p.market, now)?;
 
         let is_long = matches!(p.side, OrderSide::Long);
         match p.order_type {
-            OrderType::MarketIncrease | OrderType::LimitIncrease => PositionModule::increase_position(
-                caller,
-                p.market.clone(),
-                p.collateral_token.clone(),
-                is_long,
-                p.size_delta_usd,
-                p.collateral_delta_amount,
-                price,
-            ),
-            OrderType::MarketDecrease | OrderType::LimitDecrease | OrderType::StopLossDecrease => {
-                PositionModule::decrease_position(
+            OrderType::MarketIncrease | OrderType::LimitIncrease | OrderType::StopLimitIncrease
+                if Self::netting_position(caller, p).is_some() =>
+            {
+                // Covers both the opposite position and the new-side one
+                let snapshot = PerpetualDEXState::snapshot_trade(caller, &p.market);
+                let result = Self::net_against_opposite(caller, p, price);
+                if result.is_err() {
+                    PerpetualDEXState::restore_position(snapshot);
+                }
+                result
+            }
+            OrderType::MarketIncrease | OrderType::LimitIncrease | OrderType::StopLimitIncrease => {
+                PositionModule::increase_position_at(
+                    Self::target_position_key(caller, p),
                     caller,
                     p.market.clone(),
                     p.collateral_token.clone(),
@@ -334,17 +2098,191 @@ impl TradingModule {
                     p.size_delta_usd,
                     p.collateral_delta_amount,
                     price,
+                    p.tag,
                 )
             }
+            OrderType::MarketDecrease
+            | OrderType::LimitDecrease
+            | OrderType::StopLossDecrease
+            | OrderType::StopLimitDecrease => PositionModule::decrease_position_charged(
+                Self::target_position_key(caller, p),
+                Self::payout_receiver(caller, p),
+                p.size_delta_usd,
+                p.collateral_delta_amount,
+                price,
+                fee_usd,
+                p.tag,
+            ),
+            _ => Err(Error::UnsupportedOrderType),
+        }
+    }
+
+    /// In one-way mode, the caller's open opposite-side position that an increase would net against.
+    fn netting_position(caller: ActorId, p: &CreateOrderParams) -> Option<Position> {
+        if AccountModule::position_mode(caller) != PositionMode::OneWay {
+            return None;
+        }
+        let st = PerpetualDEXState::get();
+        let is_long = matches!(p.side, OrderSide::Long);
+        let key = PerpetualDEXState::get_position_key(caller, &p.market, &p.collateral_token, !is_long);
+        st.positions.get(&key).filter(|pos| pos.size_usd > 0).cloned()
+    }
+
+    /// One-way mode: reduce the opposite position first, then open the remainder on the requested side.
+    /// Collateral is attributed pro-rata to the part of the order that actually opens exposure.
+    fn net_against_opposite(caller: ActorId, p: &CreateOrderParams, price: u128) -> Result<PositionKey, Error> {
+        let opposite = Self::netting_position(caller, p).ok_or(Error::PositionNotFound)?;
+        let is_long = matches!(p.side, OrderSide::Long);
+
+        let close_size = p.size_delta_usd.min(opposite.size_usd);
+        let close_collateral = if close_size == opposite.size_usd {
+            opposite.collateral_usd
+        } else {
+            opposite.collateral_usd.saturating_mul(close_size) / opposite.size_usd
+        };
+        let opposite_key = PositionModule::decrease_position_charged(
+            opposite.key,
+            caller,
+            close_size,
+            close_collateral,
+            price,
+            0,
+            p.tag,
+        )?;
+
+        let remaining = p.size_delta_usd.saturating_sub(close_size);
+        if remaining == 0 {
+            return Ok(opposite_key);
+        }
+        PositionModule::increase_position(
+            caller,
+            p.market.clone(),
+            p.collateral_token.clone(),
+            is_long,
+            remaining,
+            p.collateral_delta_amount.saturating_mul(remaining) / p.size_delta_usd,
+            price,
+            p.tag,
+        )
+    }
+
+    /// Recommended `execution_fee` for an order type on a market.
+    /// Market orders execute in the creating call, so no keeper needs to be paid.
+    pub fn estimate_execution_fee(order_type: &OrderType, market: &str) -> Result<u128, Error> {
+        let st = PerpetualDEXState::get();
+        if !st.markets.contains_key(market) {
+            return Err(Error::MarketNotFound);
+        }
+
+        match order_type {
+            OrderType::MarketIncrease | OrderType::MarketDecrease => Ok(0),
+            OrderType::LimitIncrease
+            | OrderType::LimitDecrease
+            | OrderType::StopLossDecrease
+            | OrderType::StopLimitIncrease
+            | OrderType::StopLimitDecrease => {
+                let cfg = &st.execution_fee_config;
+                let bounty = cfg.keeper_gas_cost_usd.saturating_mul(cfg.keeper_bounty_bps as u128) / 10_000;
+                Ok(cfg.keeper_gas_cost_usd.saturating_add(bounty))
+            }
             _ => Err(Error::UnsupportedOrderType),
         }
     }
 
+    pub fn set_execution_fee_config(caller: ActorId, cfg: ExecutionFeeConfig) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if cfg.stale_sweep_reward_bps > 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        st.execution_fee_config = cfg;
+        Ok(())
+    }
+
+    pub fn set_capacity_limits(caller: ActorId, limits: CapacityLimits) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        st.capacity_limits = limits;
+        Ok(())
+    }
+
+    pub fn set_execution_price_guard(caller: ActorId, guard_bps: u16) -> Result<(), Error> {
+        let mut st = PerpetualDEXState::get_mut();
+        if !st.is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if guard_bps >= 10_000 {
+            return Err(Error::InvalidParameter);
+        }
+        st.execution_price_guard_bps = guard_bps;
+        Ok(())
+    }
+
+    pub fn get_execution_details(key: &RequestKey) -> Result<ExecutionDetails, Error> {
+        let st = PerpetualDEXState::get();
+        if !st.orders.contains_key(key) {
+            return Err(Error::OrderNotFound);
+        }
+        st.execution_details.get(key).cloned().ok_or(Error::OrderNotExecuted)
+    }
+
     pub fn get_order(key: &RequestKey) -> Result<Order, Error> {
         let st = PerpetualDEXState::get();
         st.orders.get(key).cloned().ok_or(Error::OrderNotFound)
     }
 
+    /// Saved order of `account` created with `client_order_id`
+    pub fn get_order_by_client_id(account: ActorId, client_order_id: u64) -> Result<(RequestKey, Order), Error> {
+        let st = PerpetualDEXState::get();
+        let key = st.client_order_ids.get(&(account, client_order_id)).ok_or(Error::OrderNotFound)?;
+        let order = st.orders.get(key).cloned().ok_or(Error::OrderNotFound)?;
+        Ok((*key, order))
+    }
+
+    /// Market, config, pool, price and the account's balance, positions and pending orders on
+    /// `market`, all from one state borrow so they are mutually consistent
+    pub fn trading_context(account: ActorId, market: &str) -> Result<TradingContext, Error> {
+        let now = exec::block_timestamp();
+        let st = PerpetualDEXState::get();
+        let market_info = st.markets.get(market).cloned().ok_or(Error::MarketNotFound)?;
+        let config = MarketModule::config_schedule_in(&st, market, now)?.active;
+        let pool = st.pool_amounts.get(market).cloned().ok_or(Error::MarketNotFound)?;
+
+        let positions = st
+            .account_positions
+            .get(&account)
+            .into_iter()
+            .flatten()
+            .filter_map(|k| st.positions.get(k))
+            .filter(|p| p.market == market)
+            .cloned()
+            .collect();
+        let orders = st
+            .account_orders
+            .get(&account)
+            .into_iter()
+            .flatten()
+            .filter_map(|k| st.orders.get(k).map(|o| (*k, o)))
+            .filter(|(_, o)| o.market == market && o.status == OrderStatus::Created)
+            .map(|(k, o)| (k, o.clone()))
+            .collect();
+
+        Ok(TradingContext {
+            timestamp: now,
+            market: market_info,
+            config,
+            pool,
+            price: st.oracle.price(&utils::price_key(market)),
+            balance: st.balances.get(&account).copied().unwrap_or(0),
+            positions,
+            orders,
+        })
+    }
+
     pub fn get_account_orders(account: ActorId) -> Vec<(RequestKey, Order)> {
         let st = PerpetualDEXState::get();
         st.account_orders
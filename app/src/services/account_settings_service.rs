@@ -0,0 +1,147 @@
+use sails_rs::{prelude::*, gstd::msg};
+use crate::{
+    errors::Error,
+    types::*,
+    modules::{
+        account::AccountModule, discount::FeeDiscountModule, notification::NotificationModule,
+        referral::ReferralModule,
+    },
+    PerpetualDEXState,
+};
+
+/// Per-account protocol preferences (slippage, position mode, receiver, TP/SL defaults) and notices inbox
+#[derive(Default)]
+pub struct AccountSettingsService;
+
+impl AccountSettingsService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[service]
+impl AccountSettingsService {
+    /// Replace the caller's settings, except the position and margin modes
+    #[export]
+    pub fn set_settings(&mut self, settings: AccountSettings) -> Result<(), Error> {
+        let caller = msg::source();
+        AccountModule::set_settings(caller, settings)
+    }
+
+    /// Choose hedge (default) or one-way position mode for the caller
+    #[export]
+    pub fn set_position_mode(&mut self, mode: PositionMode) {
+        let caller = msg::source();
+        AccountModule::set_position_mode(caller, mode)
+    }
+
+    /// Choose isolated (default) or cross margin for the caller; not while positions are open
+    #[export]
+    pub fn set_margin_mode(&mut self, mode: MarginMode) -> Result<(), Error> {
+        let caller = msg::source();
+        AccountModule::set_margin_mode(caller, mode)
+    }
+
+    /// Send realized profits of at least `min_profit_usd` out as `token` after each close,
+    /// or stop doing so with `None`
+    #[export]
+    pub fn set_auto_withdraw(&mut self, auto_withdraw: Option<AutoWithdraw>) -> Result<(), Error> {
+        let caller = msg::source();
+        AccountModule::set_auto_withdraw(caller, auto_withdraw)
+    }
+
+    /// Post a `FundingCharged` notice whenever one funding settlement charges at least
+    /// `threshold_usd`, or stop doing so with `None`
+    #[export]
+    pub fn set_funding_alert_threshold(&mut self, threshold_usd: Option<u128>) -> Result<(), Error> {
+        let caller = msg::source();
+        AccountModule::set_funding_alert_threshold(caller, threshold_usd)
+    }
+
+    /// Pause the caller's trading: no new or increased positions until re-enabled.
+    /// Closing and decreasing positions keep working.
+    #[export]
+    pub fn disable_trading(&mut self) {
+        let caller = msg::source();
+        AccountModule::disable_trading(caller)
+    }
+
+    /// Lift the caller's trading pause after a fixed delay; returns when opening resumes
+    #[export]
+    pub fn enable_trading(&mut self) -> u64 {
+        let caller = msg::source();
+        AccountModule::enable_trading(caller)
+    }
+
+    #[export]
+    pub fn get_trading_disabled_until(&self, account: ActorId) -> Option<u64> {
+        AccountModule::trading_disabled_until(account)
+    }
+
+    /// Register a referral code owned by the caller (up to `MAX_REFERRAL_CODES_PER_ACCOUNT`);
+    /// successful orders carrying it bind their account to the caller, who then earns a share
+    /// of that account's trading fees
+    #[export]
+    pub fn register_referral_code(&mut self, code: String) -> Result<(), Error> {
+        let caller = msg::source();
+        ReferralModule::register_code(caller, code)
+    }
+
+    #[export]
+    pub fn get_referral_code_owner(&self, code: String) -> Option<ActorId> {
+        ReferralModule::code_owner(&code)
+    }
+
+    #[export]
+    pub fn get_referrer(&self, account: ActorId) -> Option<ActorId> {
+        ReferralModule::referrer_of(account)
+    }
+
+    /// Referred accounts and fee shares earned by a referrer
+    #[export]
+    pub fn get_referral_earnings(&self, referrer: ActorId) -> ReferralStats {
+        ReferralModule::stats(referrer)
+    }
+
+    /// Re-check the caller's external balance and cache its fee discount tier
+    #[export]
+    pub async fn refresh_fee_discount(&mut self) -> Result<CachedDiscount, Error> {
+        let caller = msg::source();
+        FeeDiscountModule::refresh(caller).await
+    }
+
+    #[export]
+    pub fn get_fee_discount(&self, account: ActorId) -> Option<CachedDiscount> {
+        FeeDiscountModule::cached(account)
+    }
+
+    #[export]
+    pub fn get_fee_discount_config(&self) -> FeeDiscountConfig {
+        PerpetualDEXState::get().fee_discount_config.clone()
+    }
+
+    /// Clear the caller's notices with id up to `up_to_id`; returns how many were removed
+    #[export]
+    pub fn acknowledge_notices(&mut self, up_to_id: u64) -> u32 {
+        let caller = msg::source();
+        NotificationModule::acknowledge(caller, up_to_id)
+    }
+
+    /// The caller's unacknowledged notices, most recent first
+    #[export]
+    pub fn my_notices(&self, limit: u32) -> Vec<Notice> {
+        let caller = msg::source();
+        NotificationModule::notices(caller, limit)
+    }
+
+    #[export]
+    pub fn get_settings(&self, account: ActorId) -> AccountSettings {
+        AccountModule::settings(account)
+    }
+
+    #[export]
+    pub fn my_settings(&self) -> AccountSettings {
+        let caller = msg::source();
+        AccountModule::settings(caller)
+    }
+}
@@ -2,15 +2,31 @@ use sails_rs::{prelude::*, gstd::msg};
 use crate::{
     errors::Error,
     types::*,
-    modules::{market::MarketModule, oracle::OracleModule},
+    modules::{
+        checkpoint::CheckpointModule, discount::FeeDiscountModule, emergency::WithdrawOnlyModule, equity::EquityModule,
+        keeper::KeeperModule, lifecycle::LifecycleModule, market::MarketModule,
+        oracle::OracleModule, orderbook::OrderBookModule, recovery::RecoveryModule, referral::ReferralModule, risk::RiskModule,
+        solvency::SolvencyModule, trading::TradingModule,
+    },
     PerpetualDEXState,
 };
 
+/// Upper bound on the actions of one admin batch
+pub const MAX_ADMIN_BATCH_ACTIONS: usize = 32;
+
 #[derive(Default)]
 pub struct AdminService;
 
 impl AdminService {
     pub fn new() -> Self { Self::default() }
+
+    /// Reset the admin inactivity timer once an admin action has succeeded
+    fn touched<T>(caller: ActorId, result: Result<T, Error>) -> Result<T, Error> {
+        if result.is_ok() {
+            PerpetualDEXState::get_mut().touch_admin(caller);
+        }
+        result
+    }
 }
 
 #[service]
@@ -25,51 +41,394 @@ impl AdminService {
         short_token: String,
         market_token: ActorId,
         config: MarketConfig,
+        metadata: MarketMetadata,
     ) -> Result<(), Error> {
         let caller = msg::source();
-        MarketModule::create_market(
-            caller, market_id, index_token, long_token, short_token, market_token, config,
-        )
+        let result = MarketModule::create_market(
+            caller, market_id, index_token, long_token, short_token, market_token, config, metadata,
+        );
+        Self::touched(caller, result)
+    }
+
+    /// Apply `actions` in order as one update: if any action fails, state is restored and none
+    /// of them take effect, so a multi-market rollout never lands half-applied (admin only).
+    #[export]
+    pub fn batch(&mut self, actions: Vec<AdminAction>) -> Result<(), Error> {
+        let caller = msg::source();
+        if !PerpetualDEXState::get().is_admin(caller) {
+            return Err(Error::Unauthorized);
+        }
+        if actions.is_empty() || actions.len() > MAX_ADMIN_BATCH_ACTIONS {
+            return Err(Error::InvalidParameter);
+        }
+        let snapshot = PerpetualDEXState::snapshot();
+        let result = actions.into_iter().try_for_each(|action| match action {
+            AdminAction::CreateMarket {
+                market_id,
+                index_token,
+                long_token,
+                short_token,
+                market_token,
+                config,
+                metadata,
+            } => MarketModule::create_market(
+                caller, market_id, index_token, long_token, short_token, market_token, config, metadata,
+            ),
+            AdminAction::SetMarketConfig { market_id, config } => {
+                MarketModule::set_market_config(caller, market_id, config)
+            }
+            AdminAction::UpdateMarketConfig { market_id, patch } => {
+                MarketModule::update_market_config(caller, market_id, patch)
+            }
+            AdminAction::AddKeeper(keeper) => KeeperModule::add_keeper(caller, keeper),
+            AdminAction::RemoveKeeper(keeper) => KeeperModule::remove_keeper(caller, keeper),
+        });
+        if result.is_err() {
+            PerpetualDEXState::restore(snapshot);
+        }
+        Self::touched(caller, result)
+    }
+
+    /// Create a new market using a copy of the named config template (admin only).
+    #[export]
+    pub fn create_market_from_template(
+        &mut self,
+        market_id: String,
+        index_token: String,
+        long_token: String,
+        short_token: String,
+        market_token: ActorId,
+        template: String,
+        metadata: MarketMetadata,
+    ) -> Result<(), Error> {
+        let caller = msg::source();
+        let result = MarketModule::create_market_from_template(
+            caller, market_id, index_token, long_token, short_token, market_token, &template, metadata,
+        );
+        Self::touched(caller, result)
+    }
+
+    /// Add or replace a named market config template, e.g. "bluechip" (admin only).
+    #[export]
+    pub fn set_market_template(&mut self, name: String, config: MarketConfig) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::set_market_template(caller, name, config))
+    }
+
+    /// Delete a market config template (admin only).
+    #[export]
+    pub fn remove_market_template(&mut self, name: String) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::remove_market_template(caller, name))
     }
 
     /// Update market config (admin only).
     #[export]
     pub fn set_market_config(&mut self, market_id: String, config: MarketConfig) -> Result<(), Error> {
         let caller = msg::source();
-        MarketModule::set_market_config(caller, market_id, config)
+        Self::touched(caller, MarketModule::set_market_config(caller, market_id, config))
+    }
+
+    /// Update only the market config fields set in `patch` (admin only).
+    #[export]
+    pub fn update_market_config(&mut self, market_id: String, patch: MarketConfigPatch) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::update_market_config(caller, market_id, patch))
+    }
+
+    /// Schedule a market config update taking effect at `effective_at` (admin only).
+    #[export]
+    pub fn schedule_market_config(
+        &mut self,
+        market_id: String,
+        config: MarketConfig,
+        effective_at: u64,
+    ) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::schedule_market_config(caller, market_id, config, effective_at))
+    }
+
+    /// Cancel a scheduled market config update (admin only).
+    #[export]
+    pub fn cancel_scheduled_market_config(&mut self, market_id: String) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::cancel_scheduled_market_config(caller, market_id))
+    }
+
+    /// Start a market's bootstrap phase with tightened leverage/OI caps and raised fees (admin only).
+    #[export]
+    pub fn start_market_bootstrap(&mut self, market_id: String, params: BootstrapParams) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::start_bootstrap(caller, market_id, params))
+    }
+
+    /// End a market's bootstrap phase before its thresholds are met (admin only).
+    #[export]
+    pub fn end_market_bootstrap(&mut self, market_id: String) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::end_bootstrap(caller, market_id))
+    }
+
+    /// Update market display metadata (admin only).
+    #[export]
+    pub fn set_market_metadata(&mut self, market_id: String, metadata: MarketMetadata) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::set_market_metadata(caller, market_id, metadata))
+    }
+
+    /// Allow an address to add liquidity to a permissioned market (admin only).
+    #[export]
+    pub fn add_whitelisted_lp(&mut self, market_id: String, lp: ActorId) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::set_lp_whitelisted(caller, market_id, lp, true))
+    }
+
+    /// Remove an address from a market's LP whitelist (admin only).
+    #[export]
+    pub fn remove_whitelisted_lp(&mut self, market_id: String, lp: ActorId) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, MarketModule::set_lp_whitelisted(caller, market_id, lp, false))
+    }
+
+    /// Create or replace a correlation group for cross-margin offsets (admin only).
+    #[export]
+    pub fn set_correlation_group(&mut self, group: CorrelationGroup) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, RiskModule::set_correlation_group(caller, group))
+    }
+
+    /// Remove a correlation group (admin only).
+    #[export]
+    pub fn remove_correlation_group(&mut self, group_id: String) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, RiskModule::remove_correlation_group(caller, &group_id))
     }
 
     /// Update oracle config (admin only).
     #[export]
     pub fn set_oracle_config(&mut self, cfg: OracleConfig) -> Result<(), Error> {
         let caller = msg::source();
-        OracleModule::set_config(caller, cfg)
+        Self::touched(caller, OracleModule::set_config(caller, cfg))
     }
 
-    /// Add keeper (admin only).
+    /// Register a synthetic ratio feed, e.g. "ETH/BTC" = ETH / BTC (admin only).
     #[export]
-    pub fn add_keeper(&mut self, keeper: ActorId) -> Result<(), Error> {
+    pub fn set_ratio_feed(&mut self, token: String, base: String, quote: String) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, OracleModule::set_ratio_feed(caller, token, base, quote))
+    }
+
+    /// Remove a synthetic ratio feed (admin only).
+    #[export]
+    pub fn remove_ratio_feed(&mut self, token: String) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, OracleModule::remove_ratio_feed(caller, &token))
+    }
+
+    /// Blend a spot DEX pool TWAP into a direct feed, or stop blending with `None` (admin only).
+    #[export]
+    pub fn set_spot_twap_source(&mut self, token: String, source: Option<SpotTwapSource>) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, OracleModule::set_spot_source(caller, token, source))
+    }
+
+    /// Declare the decimals a feed's prices are published in, so pushes are rescaled to
+    /// micro-USD on ingestion; `None` expects pre-scaled prices (admin only).
+    #[export]
+    pub fn set_price_decimals(&mut self, token: String, decimals: Option<u8>) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, OracleModule::set_price_decimals(caller, token, decimals))
+    }
+
+    /// Update keeper execution fee parameters (admin only).
+    #[export]
+    pub fn set_execution_fee_config(&mut self, cfg: ExecutionFeeConfig) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, TradingModule::set_execution_fee_config(caller, cfg))
+    }
+
+    /// Global caps on open positions and pending orders; 0 means unlimited (admin only).
+    #[export]
+    pub fn set_capacity_limits(&mut self, limits: CapacityLimits) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, TradingModule::set_capacity_limits(caller, limits))
+    }
+
+    /// Max deviation (bps) of saved-order execution prices from trigger and oracle mid; 0 disables (admin only).
+    #[export]
+    pub fn set_execution_price_guard(&mut self, guard_bps: u16) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, TradingModule::set_execution_price_guard(caller, guard_bps))
+    }
+
+    /// Set the minimum interval between balances root publications (admin only).
+    #[export]
+    pub fn set_solvency_epoch(&mut self, epoch_seconds: u64) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, SolvencyModule::set_epoch(caller, epoch_seconds))
+    }
+
+    /// Set the minimum interval between state checkpoints (admin only).
+    #[export]
+    pub fn set_checkpoint_interval(&mut self, interval_seconds: u64) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, CheckpointModule::set_interval(caller, interval_seconds))
+    }
+
+    /// Set the share of trading fees paid to referrers (admin only).
+    #[export]
+    pub fn set_referral_fee_share(&mut self, share_bps: u16) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, ReferralModule::set_fee_share(caller, share_bps))
+    }
+
+    /// Move treasury fees into `receiver`'s balance (admin only).
+    #[export]
+    pub fn withdraw_treasury(&mut self, receiver: ActorId, amount: u128) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, OrderBookModule::withdraw_treasury(caller, receiver, amount))
+    }
+
+    /// Set the minimum interval between equity samples of one account (admin only).
+    #[export]
+    pub fn set_equity_sample_interval(&mut self, interval_seconds: u64) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, EquityModule::set_interval(caller, interval_seconds))
+    }
+
+    /// Reserve gas from this message for scheduled keeper runs (admin only).
+    /// The message must carry `amount` gas on top of its own execution cost.
+    #[export]
+    pub fn reserve_gas(&mut self, amount: u64, duration_blocks: u32) -> Result<GasReservationInfo, Error> {
+        let caller = msg::source();
+        Self::touched(caller, KeeperModule::reserve_gas(caller, amount, duration_blocks))
+    }
+
+    /// Configure the self-scheduled keeper loop (admin only).
+    #[export]
+    pub fn set_scheduler_config(&mut self, config: SchedulerConfig) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, KeeperModule::set_config(caller, config))
+    }
+
+    /// Accept deposit notifications from a fungible token contract (admin only).
+    #[export]
+    pub fn register_collateral_token(&mut self, token: ActorId, decimals: u8) -> Result<(), Error> {
         let caller = msg::source();
         let mut st = PerpetualDEXState::get_mut();
         if !st.is_admin(caller) { return Err(Error::Unauthorized); }
-        if !st.keepers.contains(&keeper) {
-            st.keepers.push(keeper);
-        }
+        st.collateral_tokens.insert(token, CollateralToken { token, decimals });
+        st.touch_admin(caller);
         Ok(())
     }
 
-    /// Remove keeper (admin only).
+    /// Stop accepting deposit notifications from a token contract (admin only).
     #[export]
-    pub fn remove_keeper(&mut self, keeper: ActorId) -> Result<(), Error> {
+    pub fn remove_collateral_token(&mut self, token: ActorId) -> Result<(), Error> {
         let caller = msg::source();
         let mut st = PerpetualDEXState::get_mut();
         if !st.is_admin(caller) { return Err(Error::Unauthorized); }
-        if let Some(i) = st.keepers.iter().position(|k| *k == keeper) {
-            st.keepers.swap_remove(i);
-        }
+        st.collateral_tokens.remove(&token).ok_or(Error::UnknownCollateralToken)?;
+        st.touch_admin(caller);
         Ok(())
     }
 
+    /// Configure the guardian set for admin recovery (admin only).
+    #[export]
+    pub fn set_recovery_config(&mut self, config: RecoveryConfig) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, RecoveryModule::set_config(caller, config))
+    }
+
+    /// Guardian vote to replace an inactive admin. Returns true once the threshold rotates the admin.
+    #[export]
+    pub fn vote_admin_recovery(&mut self, new_admin: ActorId) -> Result<bool, Error> {
+        let caller = msg::source();
+        RecoveryModule::vote(caller, new_admin)
+    }
+
+    /// Propose withdraw-only mode with optional per-market reference settlement prices, each within
+    /// a band of the oracle mid (admin only, timelocked).
+    #[export]
+    pub fn propose_withdraw_only(
+        &mut self,
+        settlement_prices: Vec<(String, u128)>,
+    ) -> Result<WithdrawOnlyState, Error> {
+        let caller = msg::source();
+        Self::touched(caller, WithdrawOnlyModule::propose(caller, settlement_prices))
+    }
+
+    /// Cancel a withdraw-only proposal before it takes effect (admin only).
+    #[export]
+    pub fn cancel_withdraw_only(&mut self) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, WithdrawOnlyModule::cancel(caller))
+    }
+
+    /// Guardian approval activating withdraw-only mode early. Returns true once active.
+    #[export]
+    pub fn approve_withdraw_only(&mut self) -> Result<bool, Error> {
+        let caller = msg::source();
+        WithdrawOnlyModule::approve(caller)
+    }
+
+    /// Announce a market delisting: reduce-only now, positions settled after `deadline` (admin only).
+    #[export]
+    pub fn announce_delisting(&mut self, market_id: String, deadline: u64) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, LifecycleModule::announce_delisting(caller, market_id, deadline))
+    }
+
+    /// Withdraw a delisting announcement before its deadline (admin only).
+    #[export]
+    pub fn cancel_delisting(&mut self, market_id: String) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, LifecycleModule::cancel_delisting(caller, market_id))
+    }
+
+    /// Fix the settlement price of a settling market whose sampling window passed without a
+    /// sample (admin only).
+    #[export]
+    pub fn attest_settlement_price(&mut self, market_id: String, price: u128) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, LifecycleModule::attest_settlement_price(caller, market_id, price))
+    }
+
+    /// Configure external-balance fee discount tiers (admin only). Clears cached discounts.
+    #[export]
+    pub fn set_fee_discount_config(&mut self, config: FeeDiscountConfig) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, FeeDiscountModule::set_config(caller, config))
+    }
+
+    /// Add keeper (admin only).
+    #[export]
+    pub fn add_keeper(&mut self, keeper: ActorId) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, KeeperModule::add_keeper(caller, keeper))
+    }
+
+    /// Configure primary-keeper rotation and the backup delay (admin only).
+    #[export]
+    pub fn set_keeper_rotation(&mut self, config: KeeperRotationConfig) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, KeeperModule::set_rotation(caller, config))
+    }
+
+    /// Enable/disable the keeper-run liquidation price audit and set its tolerance (admin only).
+    #[export]
+    pub fn set_liquidation_audit(&mut self, config: LiquidationAuditConfig) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, KeeperModule::set_liquidation_audit(caller, config))
+    }
+
+    /// Remove keeper (admin only).
+    #[export]
+    pub fn remove_keeper(&mut self, keeper: ActorId) -> Result<(), Error> {
+        let caller = msg::source();
+        Self::touched(caller, KeeperModule::remove_keeper(caller, keeper))
+    }
+
     /// (Optional) Liquidator management — mirror keepers if you use separate role.
     #[export]
     pub fn add_liquidator(&mut self, liquidator: ActorId) -> Result<(), Error> {
@@ -79,6 +438,7 @@ impl AdminService {
         if !st.liquidators.contains(&liquidator) {
             st.liquidators.push(liquidator);
         }
+        st.touch_admin(caller);
         Ok(())
     }
 
@@ -90,6 +450,7 @@ impl AdminService {
         if let Some(i) = st.liquidators.iter().position(|k| *k == liquidator) {
             st.liquidators.swap_remove(i);
         }
+        st.touch_admin(caller);
         Ok(())
     }
 }
\ No newline at end of file
@@ -1,12 +1,27 @@
 use crate::{
     PerpetualDEXState,
     errors::Error,
-    modules::{oracle::OracleModule, position::PositionModule, risk::RiskModule, trading::TradingModule},
+    modules::{
+        account::AccountModule, checkpoint::CheckpointModule, conditional::ConditionalCloseModule,
+        deadman::DeadManSwitchModule, keeper::KeeperModule, lifecycle::LifecycleModule, oracle::OracleModule,
+        position::PositionModule, risk::RiskModule, solvency::SolvencyModule, topup::AutoTopUpModule,
+        trading::TradingModule,
+    },
     types::*,
     utils,
 };
 use sails_rs::{gstd::msg, prelude::*};
 
+#[derive(Encode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum ExecutorEvent {
+    /// Position entered its market's margin-call band
+    MarginCall(MarginCall),
+    /// Cached liquidation price drifted from the recomputed one beyond the audit tolerance
+    LiquidationPriceMismatch(LiquidationPriceMismatch),
+}
+
 pub struct ExecutorService;
 
 impl ExecutorService {
@@ -21,60 +36,37 @@ impl Default for ExecutorService {
     }
 }
 
-#[service]
+#[service(events = ExecutorEvent)]
 impl ExecutorService {
     /// Execute a saved limit/stop order (callable by keepers)
     #[export]
     pub fn execute_order(&mut self, order_key: RequestKey) -> Result<ExecutionResult, Error> {
         let executor = msg::source();
-        TradingModule::execute_saved_order(executor, order_key)
+        let result = TradingModule::execute_saved_order(executor, order_key);
+        AccountModule::flush_profit_sweeps();
+        result
     }
 
-    /// Liquidate an underwater position (callable by keepers/liquidators)
+    /// Liquidate an underwater position (callable by keepers/liquidators, or anyone on
+    /// markets with permissionless liquidation)
     #[export]
     pub fn liquidate_position(&mut self, position_key: PositionKey) -> Result<(), Error> {
         let liquidator = msg::source();
-        let current_time = sails_rs::gstd::exec::block_timestamp();
 
         // Check liquidator permissions
         {
             let st = PerpetualDEXState::get();
-            if !st.is_keeper(liquidator) && !st.is_liquidator(liquidator) {
+            let market = st
+                .positions
+                .get(&position_key)
+                .map(|p| p.market.as_str())
+                .ok_or(Error::PositionNotFound)?;
+            if !st.can_liquidate(liquidator, market) {
                 return Err(Error::NotLiquidator);
             }
         }
 
-        // Get position and market data
-        let position = PositionModule::get_position(&position_key)?;
-        let price_key = utils::price_key(&position.market);
-        let current_price = OracleModule::mid(&price_key)?;
-
-        // CRITICAL: Accrue pool fees before checking liquidation
-        RiskModule::accrue_pool(&position.market, current_time)?;
-
-        // Check if liquidatable WITH pending fees
-        let (config, pool) = {
-            let st = PerpetualDEXState::get();
-            let config = st
-                .market_configs
-                .get(&position.market)
-                .ok_or(Error::MarketNotFound)?
-                .clone();
-            let pool = st
-                .pool_amounts
-                .get(&position.market)
-                .ok_or(Error::MarketNotFound)?
-                .clone();
-            (config, pool)
-        };
-
-        if !RiskModule::is_liquidatable(&position, &pool, &config, current_price, current_time)? {
-            return Err(Error::PositionNotLiquidatable);
-        }
-
-        // Execute liquidation with liquidator reward
-        let (_, liquidation_fee) =
-            PositionModule::liquidate_position(liquidator, position_key, current_price, config.liquidation_fee_bps)?;
+        let liquidation_fee = KeeperModule::liquidate(liquidator, position_key)?;
 
         // Emit liquidation event
         sails_rs::gstd::msg::send_bytes(
@@ -87,58 +79,193 @@ impl ExecutorService {
         Ok(())
     }
 
+    /// Cancel saved orders past their good-till-time and refund their escrowed fees to the owners
+    /// (callable by keepers). Returns the number of orders cancelled.
+    #[export]
+    pub fn cancel_expired_orders(&mut self) -> Result<u32, Error> {
+        let caller = msg::source();
+        TradingModule::cancel_expired_orders(caller)
+    }
+
+    /// Cancel and remove saved orders older than `max_age_secs` (at least the configured stale
+    /// age), examining up to `limit` request ids, paying the keeper a share of their escrowed
+    /// native fees. Returns the cancelled keys.
+    #[export]
+    pub fn sweep_stale_orders(&mut self, max_age_secs: u64, limit: u32) -> Result<Vec<RequestKey>, Error> {
+        let caller = msg::source();
+        TradingModule::sweep_stale_orders(caller, max_age_secs, limit)
+    }
+
+    /// Settle funding/borrowing on the next `max_positions` positions of `market`
+    #[export]
+    pub fn settle_market_fees(&mut self, market: String, max_positions: u32) -> Result<MarketFeeSettlement, Error> {
+        KeeperModule::settle_market_fees(msg::source(), market, max_positions)
+    }
+
+    /// Audit the cached liquidation prices of the next `max_positions` positions, emitting an
+    /// event per mismatch
+    #[export]
+    pub fn audit_liquidation_prices(&mut self, max_positions: u32) -> Result<LiquidationAuditReport, Error> {
+        let report = KeeperModule::audit_liquidation_prices(msg::source(), max_positions)?;
+        for mismatch in &report.mismatches {
+            self.emit_event(ExecutorEvent::LiquidationPriceMismatch(mismatch.clone())).ok();
+        }
+        Ok(report)
+    }
+
+    /// Cancel the resting orders of up to `limit` accounts that missed their heartbeat
+    #[export]
+    pub fn trigger_dead_man_switches(&mut self, limit: u32) -> Result<Vec<DeadManSwitchTrigger>, Error> {
+        DeadManSwitchModule::trigger(msg::source(), limit)
+    }
+
+    /// Apply up to `limit` due auto-top-up rules, least healthy positions first
+    #[export]
+    pub fn apply_auto_topups(&mut self, limit: u32) -> Result<Vec<AutoTopUpApplied>, Error> {
+        AutoTopUpModule::apply(msg::source(), limit)
+    }
+
+    /// Execute the due conditional closes of up to `limit` positions, resuming after the previous call
+    #[export]
+    pub fn execute_conditional_closes(&mut self, limit: u32) -> Result<Vec<ConditionalCloseExecuted>, Error> {
+        let result = ConditionalCloseModule::execute(msg::source(), limit);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Accrue funding, expire stale orders and liquidate underwater positions.
+    /// Normally self-sent by the program from a gas reservation; keepers may also call it.
+    #[export]
+    pub fn run_scheduled_tasks(&mut self) -> Result<ScheduledRunReport, Error> {
+        let caller = msg::source();
+        let report = KeeperModule::run_scheduled(caller)?;
+        self.emit_margin_calls(&report.margin_calls);
+        Ok(report)
+    }
+
+    /// Check up to `max_positions` positions, resuming after the last scan, and emit `MarginCall`
+    /// for those that entered the warning band (callable by keepers). Returns the new margin calls.
+    #[export]
+    pub fn check_margin_calls(&mut self, max_positions: u32) -> Result<Vec<MarginCall>, Error> {
+        let caller = msg::source();
+        {
+            let st = PerpetualDEXState::get();
+            if !st.is_keeper(caller) && !st.is_admin(caller) {
+                return Err(Error::NotKeeper);
+            }
+        }
+        if max_positions == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let calls = KeeperModule::scan_margin_calls(max_positions);
+        self.emit_margin_calls(&calls);
+        Ok(calls)
+    }
+
+    /// Positions currently inside their margin-call band
+    #[export]
+    pub fn get_margin_call_positions(&self) -> Vec<MarginCall> {
+        KeeperModule::margin_calls()
+    }
+
+    /// Close up to `max_positions` remaining positions of a market past its delisting deadline
+    /// at its settlement price (callable by keepers). Returns the number settled.
+    #[export]
+    pub fn settle_delisted_positions(&mut self, market_id: String, max_positions: u32) -> Result<u32, Error> {
+        let caller = msg::source();
+        let result = LifecycleModule::settle_positions(caller, market_id, max_positions);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Sample a settling market's oracle mid into its settlement TWAP during the sampling window
+    /// (callable by keepers)
+    #[export]
+    pub fn sample_settlement_price(&mut self, market_id: String) -> Result<(), Error> {
+        let caller = msg::source();
+        LifecycleModule::sample_settlement_price(caller, market_id)
+    }
+
+    /// Work through up to `max_closes` queued settlement closes of a settling market in FIFO order,
+    /// as far as pool liquidity allows (callable by keepers). Returns the number executed.
+    #[export]
+    pub fn process_settlement_closes(&mut self, market_id: String, max_closes: u32) -> Result<u32, Error> {
+        let caller = msg::source();
+        let result = LifecycleModule::process_settlement_closes(caller, market_id, max_closes);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Mark a fully settled market delisted and cancel its pending orders (callable by keepers)
+    #[export]
+    pub fn finalize_delisting(&mut self, market_id: String) -> Result<u32, Error> {
+        let caller = msg::source();
+        LifecycleModule::finalize_delisting(caller, market_id)
+    }
+
+    /// Current primary keeper, its slot, and each keeper's last activity
+    #[export]
+    pub fn get_keeper_liveness(&self) -> KeeperLiveness {
+        KeeperModule::liveness()
+    }
+
+    /// Publish a Merkle root over account balances and position equity (once per epoch)
+    #[export]
+    pub fn publish_balances_root(&mut self) -> Result<BalancesRootInfo, Error> {
+        let caller = msg::source();
+        SolvencyModule::publish(caller)
+    }
+
+    /// Publish a compact state checkpoint (once per checkpoint interval)
+    #[export]
+    pub fn publish_checkpoint(&mut self) -> Result<StateCheckpoint, Error> {
+        let caller = msg::source();
+        CheckpointModule::publish(caller)
+    }
+
+    /// Attest checkpoint `version` as the admin or a keeper; `hash` must match the stored one
+    #[export]
+    pub fn sign_checkpoint(&mut self, version: u64, hash: H256) -> Result<StateCheckpoint, Error> {
+        let caller = msg::source();
+        CheckpointModule::sign(caller, version, hash)
+    }
+
     /// Check if a position can be liquidated
     #[export]
     pub fn can_liquidate(&self, position_key: PositionKey) -> Result<bool, Error> {
         let current_time = sails_rs::gstd::exec::block_timestamp();
 
         let position = PositionModule::get_position(&position_key)?;
-        let price_key = utils::price_key(&position.market);
-        let current_price = OracleModule::mid(&price_key)?;
-
-        // Get config and pool (need both for fee calculation)
         let st = PerpetualDEXState::get();
-        let config = st.market_configs.get(&position.market).ok_or(Error::MarketNotFound)?;
-        let pool = st.pool_amounts.get(&position.market).ok_or(Error::MarketNotFound)?;
+        let cfg = st.market_configs.get(&position.market).ok_or(Error::MarketNotFound)?;
+        let current_price = KeeperModule::liquidation_price(&position, cfg)?;
+        RiskModule::is_liquidatable_for_owner(&st, &position, current_price, current_time)
+    }
 
-        RiskModule::is_liquidatable(&position, pool, config, current_price, current_time)
+    /// Dry-run a liquidation: liquidatable after virtual fee settlement, price used, expected fee,
+    /// owner payout and insurance-fund draw. No state is changed.
+    #[export]
+    pub fn simulate_liquidation(&self, position_key: PositionKey) -> Result<LiquidationSimulation, Error> {
+        KeeperModule::simulate_liquidation(position_key)
     }
 
     /// Get all positions that can be liquidated
     #[export]
     pub fn get_liquidatable_positions(&self) -> Vec<PositionKey> {
-        let st = PerpetualDEXState::get();
-        let current_time = sails_rs::gstd::exec::block_timestamp();
-        let mut liquidatable = Vec::new();
-
-        for (position_key, position) in st.positions.iter() {
-            let price_key = utils::price_key(&position.market);
-            if let Ok(current_price) = OracleModule::mid(&price_key) {
-                if let Some(config) = st.market_configs.get(&position.market) {
-                    if let Some(pool) = st.pool_amounts.get(&position.market) {
-                        // Check with pending fees included
-                        if let Ok(is_liq) =
-                            RiskModule::is_liquidatable(position, pool, config, current_price, current_time)
-                        {
-                            if is_liq {
-                                liquidatable.push(*position_key);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        liquidatable
+        KeeperModule::liquidatable_positions()
     }
 
-    /// Get all orders that can be executed
+    /// Get all orders that can be executed, highest priority fee first
     #[export]
     pub fn get_executable_orders(&self) -> Vec<RequestKey> {
         let orders = TradingModule::get_pending_orders();
+        let now = sails_rs::gstd::exec::block_timestamp();
         let mut executable = Vec::new();
 
         for (order_key, order) in orders {
+            if TradingModule::is_expired(&order, now) {
+                continue;
+            }
             let price_key = utils::price_key(&order.market);
             if let Ok(mid) = OracleModule::mid(&price_key) {
                 let can_execute = match order.order_type {
@@ -163,15 +290,39 @@ impl ExecutorService {
                             mid >= order.trigger_price
                         }
                     }
+                    OrderType::TwapIncrease | OrderType::TwapDecrease => {
+                        order.twap.as_ref().is_some_and(|t| t.next_execution_at <= now)
+                    }
+                    OrderType::StopLimitIncrease | OrderType::StopLimitDecrease => {
+                        let triggered = order.stop_limit.as_ref().is_some_and(|s| s.triggered_at.is_some());
+                        // Increase longs and decrease shorts stop on a rising price
+                        let rising = (order.order_type == OrderType::StopLimitIncrease) == order.is_long;
+                        triggered
+                            || if rising {
+                                mid >= order.trigger_price
+                            } else {
+                                mid <= order.trigger_price
+                            }
+                    }
                     _ => false,
                 };
 
                 if can_execute {
-                    executable.push(order_key);
+                    executable.push((order.priority_fee, order_key));
                 }
             }
         }
 
-        executable
+        executable.sort_by_key(|(fee, _)| core::cmp::Reverse(*fee));
+        executable.into_iter().map(|(_, key)| key).collect()
+    }
+}
+
+impl ExecutorService {
+    fn emit_margin_calls(&mut self, calls: &[MarginCall]) {
+        for call in calls {
+            // Notification only; never fail the keeper run over it
+            self.emit_event(ExecutorEvent::MarginCall(call.clone())).ok();
+        }
     }
 }
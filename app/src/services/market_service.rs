@@ -1,5 +1,5 @@
 use sails_rs::{prelude::*, gstd::msg};
-use crate::{errors::Error, types::*, modules::market::MarketModule};
+use crate::{errors::Error, types::*, modules::{backstop::BackstopModule, market::MarketModule}};
 
 #[derive(Default)]
 pub struct MarketService;
@@ -47,6 +47,58 @@ impl MarketService {
         )
     }
 
+    /// Deposit into the market's backstop tranche, which pays trader profits only after the
+    /// main pool is exhausted and earns a share of trading fees. Permissioned markets accept
+    /// whitelisted LPs only. Returns the shares minted.
+    #[export]
+    pub fn deposit_backstop(
+        &mut self,
+        market_id: String,
+        long_token_amount: u128,
+        short_token_amount: u128,
+        min_shares: u128,
+    ) -> Result<u128, Error> {
+        let lp = msg::source();
+        BackstopModule::deposit(lp, market_id, long_token_amount, short_token_amount, min_shares)
+    }
+
+    /// Burn backstop shares for (long, short) token amounts; blocked while the main pool is
+    /// utilized above the market's `backstop_withdraw_max_utilization_bps`
+    #[export]
+    pub fn withdraw_backstop(
+        &mut self,
+        market_id: String,
+        shares: u128,
+        min_long_out: u128,
+        min_short_out: u128,
+    ) -> Result<(u128, u128), Error> {
+        let lp = msg::source();
+        BackstopModule::withdraw(lp, market_id, shares, min_long_out, min_short_out)
+    }
+
+    /// Move accrued backstop fees into the caller's balance
+    #[export]
+    pub fn claim_backstop_fees(&mut self, market_id: String) -> Result<u128, Error> {
+        let lp = msg::source();
+        BackstopModule::claim_fees(lp, market_id)
+    }
+
+    #[export]
+    pub fn get_backstop_pool(&self, market_id: String) -> BackstopPool {
+        BackstopModule::get_pool(&market_id)
+    }
+
+    #[export]
+    pub fn get_backstop_stake(&self, market_id: String, account: ActorId) -> BackstopStake {
+        BackstopModule::get_stake(market_id, account)
+    }
+
+    /// Approved LPs of a permissioned market (enforced only when `lp_whitelist_enabled`)
+    #[export]
+    pub fn get_lp_whitelist(&self, market_id: String) -> Vec<ActorId> {
+        MarketModule::get_lp_whitelist(&market_id)
+    }
+
     #[export]
     pub fn get_pool(&self, market_id: String) -> Result<PoolAmounts, Error> {
         MarketModule::get_pool(&market_id)
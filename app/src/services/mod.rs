@@ -5,6 +5,7 @@ pub mod oracle_service;
 pub mod market_service;
 pub mod wallet_service;
 pub mod executor_service;
+pub mod account_settings_service;
 
 pub use trading_service::TradingService;
 pub use view_service::ViewService;
@@ -12,4 +13,5 @@ pub use admin_service::AdminService;
 pub use oracle_service::OracleService;
 pub use market_service::MarketService;
 pub use wallet_service::WalletService;
-pub use executor_service::ExecutorService;
\ No newline at end of file
+pub use executor_service::ExecutorService;
+pub use account_settings_service::AccountSettingsService;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use sails_rs::prelude::*;
+use sails_rs::{prelude::*, gstd::msg};
 use crate::{
     modules::oracle::{OracleModule, SignedPrice},
     errors::Error,
@@ -41,9 +41,39 @@ impl OracleService {
         OracleModule::last_update(&token)
     }
 
+    /// Get the legs of a synthetic ratio feed
+    #[export]
+    pub fn get_ratio_feed(&self, token: String) -> Option<RatioFeed> {
+        OracleModule::get_ratio_feed(&token)
+    }
+
     /// Get last signer who updated the price
     #[export]
     pub fn last_signer(&self, token: String) -> Option<ActorId> {
         OracleModule::last_signer(&token)
     }
+
+    /// Fetch the token's spot DEX TWAP and cache it for blending into the index price (keepers/admin)
+    #[export]
+    pub async fn refresh_spot_twap(&mut self, token: String) -> Result<CachedSpotTwap, Error> {
+        OracleModule::refresh_spot_twap(msg::source(), token).await
+    }
+
+    /// Last fetched spot DEX TWAP of a token
+    #[export]
+    pub fn get_spot_twap(&self, token: String) -> Option<CachedSpotTwap> {
+        OracleModule::get_spot_twap(&token)
+    }
+
+    /// Decimals a feed's prices are published in (`None` = micro-USD)
+    #[export]
+    pub fn get_price_decimals(&self, token: String) -> Option<u8> {
+        OracleModule::get_price_decimals(&token)
+    }
+
+    /// Age, last signer and staleness of every feed, with the markets each one blocks when stale
+    #[export]
+    pub fn get_feed_statuses(&self) -> Vec<FeedStatus> {
+        OracleModule::feed_statuses()
+    }
 }
\ No newline at end of file
@@ -1,5 +1,13 @@
 use sails_rs::{prelude::*, gstd::msg};
-use crate::{types::*, errors::Error, modules::trading::TradingModule};
+use crate::{
+    types::*,
+    errors::Error,
+    modules::{
+        account::AccountModule, conditional::ConditionalCloseModule, deadman::DeadManSwitchModule,
+        emergency::WithdrawOnlyModule, keeper::KeeperModule, lifecycle::LifecycleModule, orderbook::OrderBookModule,
+        topup::AutoTopUpModule, trading::TradingModule,
+    },
+};
 
 #[derive(Default)]
 pub struct TradingService;
@@ -15,7 +23,74 @@ impl TradingService {
     #[export]
     pub fn create_order(&mut self, params: CreateOrderParams) -> Result<ExecutionResult, Error> {
         let caller = msg::source();
-        TradingModule::create_order(caller, params)
+        TradingModule::credit_native(caller, msg::value());
+        let result = TradingModule::create_order(caller, params);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Open/adjust positions across several markets atomically (all succeed or all fail)
+    #[export]
+    pub fn create_basket(&mut self, orders: Vec<CreateOrderParams>) -> Result<Vec<ExecutionResult>, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let result = TradingModule::create_basket(caller, orders);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Market open plus stop-loss and take-profit orders, all or nothing
+    #[export]
+    pub fn open_with_brackets(
+        &mut self,
+        params: CreateOrderParams,
+        brackets: BracketParams,
+    ) -> Result<BracketOrderResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let result = TradingModule::open_with_brackets(caller, params, brackets);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Ladder of limit orders between `scale.start_price` and `scale.end_price`, splitting the
+    /// order's size and collateral by `scale.distribution`. Returns the order keys.
+    #[export]
+    pub fn create_scaled_orders(
+        &mut self,
+        params: CreateOrderParams,
+        scale: ScaledOrderParams,
+    ) -> Result<Vec<RequestKey>, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        TradingModule::create_scaled_orders(caller, params, scale)
+    }
+
+    /// Split a `TwapIncrease`/`TwapDecrease` order into `tranches` market orders executed by
+    /// keepers every `interval_seconds`
+    #[export]
+    pub fn create_twap_order(
+        &mut self,
+        params: CreateOrderParams,
+        tranches: u32,
+        interval_seconds: u64,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        TradingModule::create_twap_order(caller, params, tranches, interval_seconds)
+    }
+
+    /// Save a `StopLimitIncrease`/`StopLimitDecrease` order that, once `trigger_price` is crossed,
+    /// only fills at `limit_price` or better
+    #[export]
+    pub fn create_stop_limit_order(
+        &mut self,
+        params: CreateOrderParams,
+        limit_price: u128,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        TradingModule::create_stop_limit_order(caller, params, limit_price)
     }
 
     #[export]
@@ -39,6 +114,15 @@ impl TradingService {
             trigger_price: acceptable_price,
             acceptable_price,
             execution_fee,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
         };
         self.create_order(params)
     }
@@ -64,10 +148,254 @@ impl TradingService {
             trigger_price: acceptable_price,
             acceptable_price,
             execution_fee,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
         };
         self.create_order(params)
     }
 
+    /// `market_open` with the acceptable price taken `max_slippage_bps` from the current oracle mid
+    #[export]
+    pub fn market_open_bps(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        size_delta_usd: u128,
+        collateral_amount: u128,
+        max_slippage_bps: u16,
+        execution_fee: u128,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let params = CreateOrderParams {
+            market,
+            collateral_token,
+            order_type: OrderType::MarketIncrease,
+            side,
+            size_delta_usd,
+            collateral_delta_amount: collateral_amount,
+            trigger_price: 0,
+            acceptable_price: 0,
+            execution_fee,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
+        };
+        let result = TradingModule::create_market_order_bps(caller, params, max_slippage_bps);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// `market_close` with the acceptable price taken `max_slippage_bps` from the current oracle mid
+    #[export]
+    pub fn market_close_bps(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        size_delta_usd: u128,
+        collateral_amount: u128,
+        max_slippage_bps: u16,
+        execution_fee: u128,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let params = CreateOrderParams {
+            market,
+            collateral_token,
+            order_type: OrderType::MarketDecrease,
+            side,
+            size_delta_usd,
+            collateral_delta_amount: collateral_amount,
+            trigger_price: 0,
+            acceptable_price: 0,
+            execution_fee,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
+        };
+        let result = TradingModule::create_market_order_bps(caller, params, max_slippage_bps);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Open with `collateral_pct_bps` of the caller's balance as collateral at `leverage_x`
+    #[export]
+    pub fn market_open_pct(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        collateral_pct_bps: u16,
+        leverage_x: u8,
+        max_slippage_bps: u16,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let result = TradingModule::open_by_balance_pct(
+            caller,
+            market,
+            collateral_token,
+            side,
+            collateral_pct_bps,
+            leverage_x,
+            max_slippage_bps,
+        );
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Close `percent_bps` of the caller's position (size and collateral pro rata)
+    #[export]
+    pub fn market_close_percent(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        percent_bps: u16,
+        acceptable_price: u128,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        let result =
+            TradingModule::close_percent(caller, market, collateral_token, side, percent_bps, acceptable_price);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Close the caller's `side` position and open the opposite side in one call
+    #[export]
+    pub fn flip_position(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        size_delta_usd: u128,
+        collateral_amount: u128,
+        acceptable_price: u128,
+    ) -> Result<Vec<ExecutionResult>, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let result = TradingModule::flip_position(
+            caller,
+            market,
+            collateral_token,
+            side,
+            size_delta_usd,
+            collateral_amount,
+            acceptable_price,
+        );
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Top up a position's collateral from the caller's balance; size is unchanged
+    #[export]
+    pub fn add_collateral(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        amount_usd: u128,
+    ) -> Result<Position, Error> {
+        let caller = msg::source();
+        TradingModule::add_collateral(caller, market, collateral_token, side, amount_usd)
+    }
+
+    /// Withdraw collateral from a position to the caller's balance; size is unchanged
+    #[export]
+    pub fn remove_collateral(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        amount_usd: u128,
+    ) -> Result<Position, Error> {
+        let caller = msg::source();
+        TradingModule::remove_collateral(caller, market, collateral_token, side, amount_usd)
+    }
+
+    /// Add or remove collateral so the position's leverage becomes `leverage_x`; size is unchanged
+    #[export]
+    pub fn set_target_leverage(&mut self, position_key: PositionKey, leverage_x: u8) -> Result<Position, Error> {
+        let caller = msg::source();
+        TradingModule::set_target_leverage(caller, position_key, leverage_x)
+    }
+
+    /// Let keepers top up this position's collateral from the caller's balance whenever its
+    /// health factor (bps of the liquidation threshold) drops below `trigger_health_bps`,
+    /// adding at most `max_amount_usd` over the rule's lifetime
+    #[export]
+    pub fn set_auto_topup(
+        &mut self,
+        position_key: PositionKey,
+        trigger_health_bps: u32,
+        max_amount_usd: u128,
+    ) -> Result<AutoTopUpRule, Error> {
+        AutoTopUpModule::set_rule(msg::source(), position_key, trigger_health_bps, max_amount_usd)
+    }
+
+    #[export]
+    pub fn remove_auto_topup(&mut self, position_key: PositionKey) -> Result<(), Error> {
+        AutoTopUpModule::remove_rule(msg::source(), position_key)
+    }
+
+    #[export]
+    pub fn get_auto_topup(&self, position_key: PositionKey) -> Option<AutoTopUpRule> {
+        AutoTopUpModule::get_rule(position_key)
+    }
+
+    /// Let keepers market-close `close_bps` of this position once `condition` holds on its
+    /// unrealized PnL or leverage, filling within `max_slippage_bps` of the oracle mid
+    #[export]
+    pub fn create_conditional_close(
+        &mut self,
+        position_key: PositionKey,
+        condition: PositionCondition,
+        close_bps: u16,
+        max_slippage_bps: u16,
+    ) -> Result<ConditionalClose, Error> {
+        ConditionalCloseModule::create(msg::source(), position_key, condition, close_bps, max_slippage_bps)
+    }
+
+    #[export]
+    pub fn cancel_conditional_close(&mut self, key: RequestKey) -> Result<(), Error> {
+        ConditionalCloseModule::cancel(msg::source(), key)
+    }
+
+    #[export]
+    pub fn get_conditional_closes(&self, account: ActorId) -> Vec<ConditionalClose> {
+        ConditionalCloseModule::account_closes(account)
+    }
+
+    /// Market-close all of the caller's positions; returns each position's outcome
+    #[export]
+    pub fn close_all_positions(&mut self, acceptable_slippage_bps: u16) -> Result<Vec<PositionCloseResult>, Error> {
+        let caller = msg::source();
+        let result = TradingModule::close_all_positions(caller, acceptable_slippage_bps);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
     #[export]
     pub fn set_stop_loss(
         &mut self,
@@ -89,10 +417,130 @@ impl TradingService {
             trigger_price,
             acceptable_price,
             execution_fee,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
         };
         self.create_order(params)
     }
 
+    /// Stop-loss `distance_bps` below (long) / above (short) the entry or mark price
+    #[export]
+    pub fn set_stop_loss_pct(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        size_delta_usd: u128,
+        distance_bps: u16,
+        reference: TriggerReference,
+        max_slippage_bps: u16,
+        execution_fee: u128,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let params = CreateOrderParams {
+            market,
+            collateral_token,
+            order_type: OrderType::StopLossDecrease,
+            side,
+            size_delta_usd,
+            collateral_delta_amount: 0,
+            trigger_price: 0,
+            acceptable_price: 0,
+            execution_fee,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
+        };
+        TradingModule::create_pct_trigger_order(caller, params, distance_bps, reference, max_slippage_bps)
+    }
+
+    /// Take-profit `distance_bps` above (long) / below (short) the entry or mark price
+    #[export]
+    pub fn set_take_profit_pct(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        size_delta_usd: u128,
+        distance_bps: u16,
+        reference: TriggerReference,
+        max_slippage_bps: u16,
+        execution_fee: u128,
+    ) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        TradingModule::credit_native(caller, msg::value());
+        let params = CreateOrderParams {
+            market,
+            collateral_token,
+            order_type: OrderType::LimitDecrease,
+            side,
+            size_delta_usd,
+            collateral_delta_amount: 0,
+            trigger_price: 0,
+            acceptable_price: 0,
+            execution_fee,
+            expires_at: None,
+            time_in_force: TimeInForce::Gtc,
+            referral_code: None,
+            receiver: None,
+            callback_contract: None,
+            callback_gas_limit: 0,
+            client_order_id: None,
+            tag: None,
+            position_key: None,
+        };
+        TradingModule::create_pct_trigger_order(caller, params, distance_bps, reference, max_slippage_bps)
+    }
+
+    /// Close a whole position at the last committed price during an oracle outage
+    /// (price stale beyond the market's grace period). Charges the market's outage fee.
+    #[export]
+    pub fn close_at_last_price(&mut self, position_key: PositionKey) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        let result = TradingModule::close_at_last_price(caller, position_key);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Queue a close of the caller's position in a settling market. Keepers execute queued closes
+    /// in submission order at the market's fixed settlement price, as pool liquidity allows.
+    #[export]
+    pub fn submit_settlement_close(&mut self, position_key: PositionKey) -> Result<(), Error> {
+        let caller = msg::source();
+        LifecycleModule::submit_settlement_close(caller, position_key)
+    }
+
+    /// Liquidate the caller's own underwater position at the liquidation price without waiting for
+    /// keepers. The liquidation fee goes to the insurance fund. Returns the fee.
+    #[export]
+    pub fn self_liquidate(&mut self, position_key: PositionKey) -> Result<u128, Error> {
+        let caller = msg::source();
+        KeeperModule::self_liquidate(caller, position_key)
+    }
+
+    /// Close a whole position at the settlement price while withdraw-only mode is active
+    #[export]
+    pub fn settle_position(&mut self, position_key: PositionKey) -> Result<ExecutionResult, Error> {
+        let caller = msg::source();
+        let result = WithdrawOnlyModule::close_position(caller, position_key);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
     #[export]
     pub fn update_order(
         &mut self,
@@ -103,16 +551,91 @@ impl TradingService {
         TradingModule::update_order(caller, key, params)
     }
 
+    /// Bid a native priority fee (attached value plus `amount` from the native balance) for
+    /// keepers to execute this order first. Returns the order's total priority fee.
+    #[export]
+    pub fn add_priority_fee(&mut self, key: RequestKey, amount: u128) -> Result<u128, Error> {
+        let caller = msg::source();
+        let attached = msg::value();
+        TradingModule::credit_native(caller, attached);
+        TradingModule::add_priority_fee(caller, key, amount.saturating_add(attached))
+    }
+
     #[export]
     pub fn cancel_order(&mut self, key: RequestKey) -> Result<(), Error> {
         let caller = msg::source();
         TradingModule::cancel_order(caller, key)
     }
 
+    /// Cancel all of the caller's pending orders, optionally only on `market`
+    #[export]
+    pub fn cancel_all_orders(&mut self, market: Option<String>) -> Vec<RequestKey> {
+        let caller = msg::source();
+        TradingModule::cancel_all_orders(caller, market)
+    }
+
+    /// Arm the caller's dead man's switch: unless `heartbeat` is called at least every
+    /// `interval_seconds`, keepers cancel all of the caller's resting orders
+    #[export]
+    pub fn enable_dead_man_switch(&mut self, interval_seconds: u64) -> Result<DeadManSwitch, Error> {
+        DeadManSwitchModule::enable(msg::source(), interval_seconds)
+    }
+
+    #[export]
+    pub fn disable_dead_man_switch(&mut self) {
+        DeadManSwitchModule::disable(msg::source())
+    }
+
+    /// Keep the caller's resting orders alive; returns the next deadline
+    #[export]
+    pub fn heartbeat(&mut self) -> Result<u64, Error> {
+        DeadManSwitchModule::heartbeat(msg::source())
+    }
+
+    #[export]
+    pub fn get_dead_man_switch(&self, account: ActorId) -> Option<DeadManSwitch> {
+        DeadManSwitchModule::get(account)
+    }
+
     #[export]
     pub fn execute_saved_order(&mut self, key: RequestKey) -> Result<ExecutionResult, Error> {
         let executor = msg::source();
-        TradingModule::execute_saved_order(executor, key)
+        let result = TradingModule::execute_saved_order(executor, key);
+        AccountModule::flush_profit_sweeps();
+        result
+    }
+
+    /// Choose hedge (default) or one-way position mode for the caller
+    #[export]
+    pub fn set_position_mode(&mut self, mode: PositionMode) {
+        let caller = msg::source();
+        AccountModule::set_position_mode(caller, mode)
+    }
+
+    #[export]
+    pub fn get_position_mode(&self, account: ActorId) -> PositionMode {
+        AccountModule::position_mode(account)
+    }
+
+    /// Rest a post-only maker order on the market's order book
+    #[export]
+    pub fn place_book_order(
+        &mut self,
+        market: String,
+        collateral_token: String,
+        side: OrderSide,
+        price: u128,
+        size_delta_usd: u128,
+        collateral_amount: u128,
+    ) -> Result<u64, Error> {
+        let caller = msg::source();
+        OrderBookModule::place_order(caller, market, collateral_token, side, price, size_delta_usd, collateral_amount)
+    }
+
+    #[export]
+    pub fn cancel_book_order(&mut self, market: String, order_id: u64) -> Result<(), Error> {
+        let caller = msg::source();
+        OrderBookModule::cancel_order(caller, &market, order_id)
     }
 
     #[export]
@@ -120,6 +643,16 @@ impl TradingService {
         TradingModule::get_order(&key)
     }
 
+    /// Look up a saved order by the client id it was created with
+    #[export]
+    pub fn get_order_by_client_id(
+        &self,
+        account: ActorId,
+        client_order_id: u64,
+    ) -> Result<(RequestKey, Order), Error> {
+        TradingModule::get_order_by_client_id(account, client_order_id)
+    }
+
     #[export]
     pub fn get_my_orders(&self) -> Vec<(RequestKey, Order)> {
         let caller = msg::source();
@@ -1,8 +1,14 @@
-use sails_rs::{prelude::*, gstd::msg};
+use sails_rs::{prelude::*, gstd::{exec, msg}};
 use crate::{
     types::*,
     errors::Error,
-    modules::{position::PositionModule, market::MarketModule, oracle::OracleModule},
+    modules::{
+        position::PositionModule, market::MarketModule, pricing::PricingModule, oracle::OracleModule,
+        orderbook::OrderBookModule, solvency::SolvencyModule, trading::TradingModule, keeper::KeeperModule,
+        stats::StatsModule, history::HistoryModule, recovery::RecoveryModule, risk::RiskModule,
+        emergency::WithdrawOnlyModule, lifecycle::LifecycleModule, checkpoint::CheckpointModule,
+        notification::NotificationModule, equity::EquityModule,
+    },
     utils,
     PerpetualDEXState,
 };
@@ -22,8 +28,55 @@ impl ViewService {
 
     #[export]
     pub fn get_market_config(&self, market_id: String) -> Result<MarketConfig, Error> {
-        let st = PerpetualDEXState::get();
-        st.market_configs.get(&market_id).cloned().ok_or(Error::MarketNotFound)
+        MarketModule::config_schedule(&market_id).map(|s| s.active)
+    }
+
+    /// Names of the market config templates available for new listings
+    #[export]
+    pub fn get_market_templates(&self) -> Vec<String> {
+        MarketModule::market_template_names()
+    }
+
+    #[export]
+    pub fn get_market_template(&self, name: String) -> Result<MarketConfig, Error> {
+        MarketModule::get_market_template(&name)
+    }
+
+    /// Active config and any scheduled update with its effective time
+    #[export]
+    pub fn get_market_config_schedule(&self, market_id: String) -> Result<MarketConfigSchedule, Error> {
+        MarketModule::config_schedule(&market_id)
+    }
+
+    /// Delisting lifecycle stage (with the reduce-only deadline, if announced)
+    #[export]
+    pub fn get_market_stage(&self, market_id: String) -> Result<MarketStage, Error> {
+        LifecycleModule::stage(&market_id)
+    }
+
+    /// Fixed settlement price of a settling market (`None` until its sampling window has passed)
+    #[export]
+    pub fn get_settlement_price(&self, market_id: String) -> Option<u128> {
+        LifecycleModule::get_settlement_price(&market_id)
+    }
+
+    /// Queued settlement closes of a market, in execution order
+    #[export]
+    pub fn get_settlement_queue(&self, market_id: String) -> Vec<SettlementClose> {
+        LifecycleModule::settlement_queue(&market_id)
+    }
+
+    /// Bootstrap phase in progress for the market, if any
+    #[export]
+    pub fn get_market_bootstrap(&self, market_id: String) -> Option<MarketBootstrap> {
+        MarketModule::get_bootstrap(&market_id)
+    }
+
+    /// Size executable within each of `levels_bps` of mid on either side (5/10/25/50 bps if empty)
+    #[export]
+    pub fn get_synthetic_depth(&self, market_id: String, levels_bps: Vec<u16>) -> Result<Vec<DepthLevel>, Error> {
+        let levels = if levels_bps.is_empty() { vec![5, 10, 25, 50] } else { levels_bps };
+        PricingModule::synthetic_depth(&market_id, &levels)
     }
 
     #[export]
@@ -31,10 +84,16 @@ impl ViewService {
         MarketModule::get_pool(&market_id)
     }
 
+    /// Markets sorted by id, starting after `after`; `next_cursor` is set when `limits` cut the reply
     #[export]
-    pub fn get_all_markets(&self) -> Vec<(String, Market)> {
+    pub fn get_all_markets(&self, after: Option<String>, limits: ViewLimits) -> MarketListingPage {
+        MarketModule::get_all_markets(after, limits)
+    }
+
+    #[export]
+    pub fn get_market_metadata(&self, market_id: String) -> Result<MarketMetadata, Error> {
         let st = PerpetualDEXState::get();
-        st.markets.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        st.market_metadata.get(&market_id).cloned().ok_or(Error::MarketNotFound)
     }
 
     #[export]
@@ -43,6 +102,13 @@ impl ViewService {
         st.market_tokens.get(&market_id).cloned().ok_or(Error::MarketNotFound)
     }
 
+    /// Market, config, pool, oracle price and the account's balance, positions and pending
+    /// orders on the market, taken from one consistent state read
+    #[export]
+    pub fn get_trading_context(&self, account: ActorId, market_id: String) -> Result<TradingContext, Error> {
+        TradingModule::trading_context(account, &market_id)
+    }
+
     // Position views
     #[export]
     pub fn get_position(&self, key: PositionKey) -> Result<Position, Error> {
@@ -68,10 +134,15 @@ impl ViewService {
         PositionModule::get_position_pnl(&key, current_price)
     }
 
+    /// Market positions sorted by key, starting after `after`; `next_cursor` is set when `limits` cut the reply
     #[export]
-    pub fn get_market_positions(&self, market_id: String) -> Vec<Position> {
-        let st = PerpetualDEXState::get();
-        st.positions.values().filter(|p| p.market == market_id).cloned().collect()
+    pub fn get_market_positions(
+        &self,
+        market_id: String,
+        after: Option<PositionKey>,
+        limits: ViewLimits,
+    ) -> PositionPage {
+        PositionModule::get_market_positions(&market_id, after, limits)
     }
 
     // Order views
@@ -81,6 +152,12 @@ impl ViewService {
         st.orders.get(&key).cloned().ok_or(Error::OrderNotFound)
     }
 
+    /// Execution price, impact, fees and resulting position of one of the latest executed saved orders
+    #[export]
+    pub fn get_execution_details(&self, order_key: RequestKey) -> Result<ExecutionDetails, Error> {
+        TradingModule::get_execution_details(&order_key)
+    }
+
     #[export]
     pub fn get_account_orders(&self, account: ActorId) -> Vec<(RequestKey, Order)> {
         let st = PerpetualDEXState::get();
@@ -101,6 +178,40 @@ impl ViewService {
         st.orders.iter().filter(|(_, o)| o.status == OrderStatus::Created).map(|(k, o)| (*k, o.clone())).collect()
     }
 
+    // Order book views
+    #[export]
+    pub fn get_order_book(&self, market_id: String, levels: u32) -> OrderBookDepth {
+        OrderBookModule::get_depth(&market_id, levels)
+    }
+
+    #[export]
+    pub fn get_account_book_orders(&self, account: ActorId) -> Vec<BookOrder> {
+        OrderBookModule::get_account_orders(account)
+    }
+
+    /// Recommended execution_fee for an order type on a market
+    #[export]
+    pub fn estimate_execution_fee(&self, order_type: OrderType, market: String) -> Result<u128, Error> {
+        TradingModule::estimate_execution_fee(&order_type, &market)
+    }
+
+    #[export]
+    pub fn get_execution_fee_config(&self) -> ExecutionFeeConfig {
+        PerpetualDEXState::get().execution_fee_config.clone()
+    }
+
+    /// Global caps on open positions and pending orders (0 = unlimited)
+    #[export]
+    pub fn get_capacity_limits(&self) -> CapacityLimits {
+        PerpetualDEXState::get().capacity_limits.clone()
+    }
+
+    /// Max deviation (bps) of saved-order execution prices from trigger and oracle mid (0 = off)
+    #[export]
+    pub fn get_execution_price_guard(&self) -> u16 {
+        PerpetualDEXState::get().execution_price_guard_bps
+    }
+
     // Oracle views
     #[export]
     pub fn get_oracle_price(&self, token: String) -> Result<Price, Error> {
@@ -131,12 +242,188 @@ impl ViewService {
         self.get_balance(caller)
     }
 
+    /// Lifetime realized PnL, fees by category, volume, and current unrealized PnL
+    #[export]
+    pub fn get_trader_stats(&self, account: ActorId) -> TraderStatsView {
+        StatsModule::trader_stats(account)
+    }
+
+    /// Funding and borrowing accrued on a market's open positions but not yet settled
+    #[export]
+    pub fn get_market_pending_fees(&self, market_id: String) -> Result<MarketPendingFees, Error> {
+        RiskModule::market_pending_fees(&market_id, exec::block_timestamp())
+    }
+
+    /// `get_market_pending_fees` for every market
+    #[export]
+    pub fn get_all_market_pending_fees(&self) -> Vec<MarketPendingFees> {
+        let now = exec::block_timestamp();
+        let markets: Vec<String> = PerpetualDEXState::get().market_configs.keys().cloned().collect();
+        markets
+            .iter()
+            .filter_map(|m| RiskModule::market_pending_fees(m, now).ok())
+            .collect()
+    }
+
+    /// Cumulative funding paid by each side of a market, lifetime and over the recent window
+    #[export]
+    pub fn get_funding_stats(&self, market_id: String) -> Result<FundingStatsView, Error> {
+        if !PerpetualDEXState::get().market_configs.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+        Ok(StatsModule::funding_stats(&market_id, exec::block_timestamp()))
+    }
+
+    /// Protocol-wide TVL, open interest, fees, open positions/orders and insurance fund
+    #[export]
+    pub fn get_protocol_stats(&self) -> ProtocolStats {
+        StatsModule::protocol_stats(exec::block_timestamp())
+    }
+
+    /// Per-market fee inflows by source, lifetime and over the recent window
+    #[export]
+    pub fn get_market_fee_revenue(&self, market_id: String) -> Result<MarketFeeRevenue, Error> {
+        if !PerpetualDEXState::get().market_configs.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+        Ok(StatsModule::fee_revenue(&market_id, exec::block_timestamp()))
+    }
+
+    /// Realized slippage vs oracle mid and price impact paid on pool executions
+    #[export]
+    pub fn get_slippage_stats(&self, market_id: String) -> Result<SlippageStatsView, Error> {
+        if !PerpetualDEXState::get().market_configs.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+        Ok(StatsModule::slippage_stats(&market_id, exec::block_timestamp()))
+    }
+
+    /// Saved-order pipeline of a market: pending/frozen now, executed/expired and average
+    /// time-to-execution over its lifetime
+    #[export]
+    pub fn get_order_execution_stats(&self, market_id: String) -> Result<OrderExecutionStats, Error> {
+        if !PerpetualDEXState::get().market_configs.contains_key(&market_id) {
+            return Err(Error::MarketNotFound);
+        }
+        Ok(StatsModule::order_execution_stats(&market_id))
+    }
+
+    /// Canonical position key with its hash preimage, for verifying off-chain derivations
+    #[export]
+    pub fn derive_position_key(&self, params: PositionKeyParams) -> PositionKeyDerivation {
+        let preimage =
+            utils::position_key_preimage(params.account, &params.market, &params.collateral_token, params.is_long);
+        let key = utils::position_key(params.account, &params.market, &params.collateral_token, params.is_long);
+        PositionKeyDerivation { params, preimage, key }
+    }
+
+    #[export]
+    pub fn derive_position_keys(&self, params: Vec<PositionKeyParams>) -> Vec<PositionKeyDerivation> {
+        params.into_iter().map(|p| self.derive_position_key(p)).collect()
+    }
+
+    /// True when the market's price is stale beyond its outage grace period,
+    /// i.e. positions can only be closed at the last committed price
+    #[export]
+    pub fn is_market_in_outage(&self, market_id: String) -> Result<bool, Error> {
+        let grace = PerpetualDEXState::get()
+            .market_configs
+            .get(&market_id)
+            .ok_or(Error::MarketNotFound)?
+            .outage_grace_seconds;
+        Ok(grace > 0 && OracleModule::is_in_outage(&utils::price_key(&market_id), grace))
+    }
+
+    /// Account-level equity and maintenance with correlation-group offsets (cross-margin health)
+    #[export]
+    pub fn get_account_margin(&self, account: ActorId) -> Result<AccountMargin, Error> {
+        let st = PerpetualDEXState::get();
+        RiskModule::account_margin(&st, account, exec::block_timestamp())
+    }
+
+    #[export]
+    pub fn get_correlation_groups(&self) -> Vec<CorrelationGroup> {
+        PerpetualDEXState::get().correlation_groups.values().cloned().collect()
+    }
+
+    /// Recent fills of an account, most recent first
+    #[export]
+    pub fn get_account_fills(&self, account: ActorId, limit: u32) -> Vec<FillRecord> {
+        HistoryModule::account_fills(account, limit)
+    }
+
+    /// Recent liquidations in a market, most recent first
+    #[export]
+    pub fn get_market_liquidations(&self, market_id: String, limit: u32) -> Vec<LiquidationRecord> {
+        HistoryModule::market_liquidations(&market_id, limit)
+    }
+
+    /// Recent liquidations of an account's positions, most recent first
+    #[export]
+    pub fn get_account_liquidations(&self, account: ActorId, limit: u32) -> Vec<LiquidationRecord> {
+        HistoryModule::account_liquidations(account, limit)
+    }
+
+    /// Unacknowledged notices of an account (margin calls, executions, liquidations, funding), most recent first
+    #[export]
+    pub fn get_notices(&self, account: ActorId, limit: u32) -> Vec<Notice> {
+        NotificationModule::notices(account, limit)
+    }
+
+    /// Sampled equity of an account (wallet balance + position equity), most recent first
+    #[export]
+    pub fn get_equity_history(&self, account: ActorId, limit: u32) -> Vec<EquitySample> {
+        EquityModule::history(account, limit)
+    }
+
+    // Solvency
+    #[export]
+    pub fn get_balances_root(&self) -> Option<BalancesRootInfo> {
+        SolvencyModule::latest_root()
+    }
+
+    #[export]
+    pub fn get_balance_proof(&self, account: ActorId) -> Result<BalanceProof, Error> {
+        SolvencyModule::proof(account)
+    }
+
+    // State checkpoints
+    #[export]
+    pub fn get_latest_checkpoint(&self) -> Option<StateCheckpoint> {
+        CheckpointModule::latest()
+    }
+
+    #[export]
+    pub fn get_checkpoint(&self, version: u64) -> Result<StateCheckpoint, Error> {
+        CheckpointModule::get(version)
+    }
+
+    /// Stored checkpoints, most recent first
+    #[export]
+    pub fn get_checkpoints(&self, limit: u32) -> Vec<StateCheckpoint> {
+        CheckpointModule::recent(limit)
+    }
+
+    #[export]
+    pub fn get_scheduler_status(&self) -> SchedulerStatus {
+        KeeperModule::status()
+    }
+
+    #[export]
+    pub fn get_collateral_tokens(&self) -> Vec<CollateralToken> {
+        PerpetualDEXState::get().collateral_tokens.values().cloned().collect()
+    }
+
     // Admin views
     #[export]
     pub fn get_admin(&self) -> ActorId { PerpetualDEXState::get().admin }
     #[export]
     pub fn get_keepers(&self) -> Vec<ActorId> { PerpetualDEXState::get().keepers.clone() }
     #[export]
+    pub fn get_recovery_status(&self) -> RecoveryStatus { RecoveryModule::status() }
+    #[export]
+    pub fn get_withdraw_only_status(&self) -> Option<WithdrawOnlyState> { WithdrawOnlyModule::status() }
+    #[export]
     pub fn get_liquidators(&self) -> Vec<ActorId> { PerpetualDEXState::get().liquidators.clone() }
 
     // Stats
@@ -146,4 +433,8 @@ impl ViewService {
     pub fn get_total_orders(&self) -> u64 { PerpetualDEXState::get().orders.len() as u64 }
     #[export]
     pub fn get_total_markets(&self) -> u64 { PerpetualDEXState::get().markets.len() as u64 }
+    #[export]
+    pub fn get_insurance_fund(&self) -> u128 { PerpetualDEXState::get().insurance_fund_usd }
+    #[export]
+    pub fn get_treasury(&self) -> u128 { PerpetualDEXState::get().treasury_usd }
 }
\ No newline at end of file
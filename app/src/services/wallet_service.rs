@@ -1,5 +1,5 @@
 use sails_rs::{prelude::*, gstd::msg};
-use crate::{errors::Error, PerpetualDEXState, types::Usd};
+use crate::{errors::Error, PerpetualDEXState, types::Usd, modules::trading::TradingModule, utils};
 
 /// Internal USD wallet (micro-USD). This is a temporary in-program balance.
 /// In production this would be backed by real FT transfers.
@@ -21,11 +21,43 @@ impl WalletService {
         }
         let caller = msg::source();
         let mut st = PerpetualDEXState::get_mut();
+        st.ensure_not_withdraw_only()?;
         let bal = st.balances.entry(caller).or_insert(0);
         *bal = bal.saturating_add(amount);
         Ok(*bal)
     }
 
+    /// Transfer notification from a registered collateral token contract (the caller).
+    /// `nonce` identifies the transfer on the token side; each (token, nonce) is credited once.
+    #[export]
+    pub fn on_token_transfer(&mut self, from: ActorId, amount: u128, nonce: u64) -> Result<Usd, Error> {
+        let token = msg::source();
+        let mut st = PerpetualDEXState::get_mut();
+        let decimals = st
+            .collateral_tokens
+            .get(&token)
+            .ok_or(Error::UnknownCollateralToken)?
+            .decimals;
+        if st.processed_transfers.contains(&(token, nonce)) {
+            return Err(Error::DuplicateTransferNotification);
+        }
+        let credit = utils::token_amount_to_usd(amount, decimals).ok_or(Error::MathOverflow)?;
+        if credit == 0 {
+            return Err(Error::InvalidParameter);
+        }
+
+        st.processed_transfers.insert((token, nonce));
+        let bal = st.balances.entry(from).or_insert(0);
+        *bal = bal.saturating_add(credit);
+        Ok(*bal)
+    }
+
+    #[export]
+    pub fn is_transfer_processed(&self, token: ActorId, nonce: u64) -> bool {
+        let st = PerpetualDEXState::get();
+        st.processed_transfers.contains(&(token, nonce))
+    }
+
     #[export]
     pub fn withdraw(&mut self, amount: Usd) -> Result<Usd, Error> {
         if amount == 0 {
@@ -52,4 +84,41 @@ impl WalletService {
         let caller = msg::source();
         self.balance_of(caller)
     }
+
+    /// Deposit native VARA (attached value) used to fund keeper gas refunds
+    #[export]
+    pub fn deposit_native(&mut self) -> Result<u128, Error> {
+        let amount = msg::value();
+        if amount == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let caller = msg::source();
+        TradingModule::credit_native(caller, amount);
+        Ok(self.native_balance_of(caller))
+    }
+
+    #[export]
+    pub fn withdraw_native(&mut self, amount: u128) -> Result<u128, Error> {
+        if amount == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let caller = msg::source();
+        let remaining = {
+            let mut st = PerpetualDEXState::get_mut();
+            let native = st.native_balances.get_mut(&caller).ok_or(Error::InsufficientBalance)?;
+            if *native < amount {
+                return Err(Error::InsufficientBalance);
+            }
+            *native -= amount;
+            *native
+        };
+        TradingModule::pay_native(caller, amount);
+        Ok(remaining)
+    }
+
+    #[export]
+    pub fn native_balance_of(&self, account: ActorId) -> u128 {
+        let st = PerpetualDEXState::get();
+        st.native_balances.get(&account).copied().unwrap_or(0)
+    }
 }
\ No newline at end of file
@@ -1,4 +1,10 @@
-use sails_rs::{collections::BTreeMap, prelude::*};
+use sails_rs::{
+    collections::{BTreeMap, VecDeque},
+    gstd::ReservationId,
+    prelude::*,
+};
+
+use crate::errors::Error;
 
 pub type RequestKey = H256;
 pub type PositionKey = H256;
@@ -18,6 +24,55 @@ pub struct Market {
     pub short_token: String,
 }
 
+/// Display metadata for frontends; not used by protocol logic
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketMetadata {
+    /// Human readable name (e.g. "Bitcoin Perpetual")
+    pub name: String,
+    pub base_symbol: String,
+    pub quote_symbol: String,
+    /// Decimals to use when displaying prices
+    pub price_decimals: u8,
+    /// Category tags (e.g. "L1", "meme")
+    pub tags: Vec<String>,
+    pub icon_uri: String,
+}
+
+/// Market listing entry (market + display metadata)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketListing {
+    pub market_id: String,
+    pub market: Market,
+    pub metadata: MarketMetadata,
+}
+
+/// Size hints for large views. The reply stops at whichever limit is hit first and always
+/// holds at least one item; `max_bytes` is capped at `MAX_VIEW_REPLY_BYTES`.
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ViewLimits {
+    pub max_items: Option<u32>,
+    pub max_bytes: Option<u32>,
+}
+
+/// Reply payload budget for large views, well under the runtime message size limit
+pub const MAX_VIEW_REPLY_BYTES: u32 = 1_000_000;
+
+/// Page of market listings sorted by market id
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketListingPage {
+    pub markets: Vec<MarketListing>,
+    /// Pass as `after` to fetch the rest; `None` when nothing was truncated
+    pub next_cursor: Option<String>,
+}
+
 /// Market configuration (risk, fees, limits)
 #[derive(Encode, Decode, TypeInfo, Clone, Debug)]
 #[codec(crate = sails_rs::scale_codec)]
@@ -42,16 +97,107 @@ pub struct MarketConfig {
     pub skip_borrowing_for_smaller_side: bool,
 
     // Trading & risk
+    /// Fee on pool executions of market orders (taker-like flow)
     pub trading_fee_bps: u16,
+    /// Fee on saved conditional orders executed later by keepers (maker-like flow)
+    pub keeper_order_fee_bps: u16,
     pub max_leverage: u8,        // x
     pub min_collateral_usd: Usd, // fixed-point
     pub liquidation_threshold_bps: u16,
     pub liquidation_fee_bps: u16, // Liquidator reward (e.g. 500 = 5%)
+    /// Absolute bounds on the liquidation fee (USD; 0 = no floor / no cap)
+    pub min_liquidation_fee_usd: Usd,
+    pub max_liquidation_fee_usd: Usd,
+    /// Extra adverse slippage beyond bid (longs) / ask (shorts) for liquidation checks and fills
+    pub liquidation_slippage_bps: u16,
     pub reserve_factor_bps: u16,
+    /// Per-side overrides of `reserve_factor_bps` (0 = use the shared factor)
+    pub long_reserve_factor_bps: u16,
+    pub short_reserve_factor_bps: u16,
 
     // OI caps (in USD)
     pub max_long_oi: Usd,
     pub max_short_oi: Usd,
+    /// Per-side OI caps in index token units at USD_SCALE precision, checked against the pool's
+    /// token OI alongside the USD caps (0 = no token cap)
+    pub max_long_oi_tokens: u128,
+    pub max_short_oi_tokens: u128,
+    /// Max notional of any single position (0 = unlimited)
+    pub max_position_size_usd: Usd,
+
+    // Order book
+    pub orderbook_enabled: bool,
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+    /// Max distance of a maker price from the oracle mid (bps), checked when the order is placed
+    /// and again when it is filled; must be set on order book markets
+    pub maker_price_band_bps: u16,
+
+    // Liquidity
+    /// Only whitelisted addresses may add liquidity
+    pub lp_whitelist_enabled: bool,
+
+    // Oracle outage
+    /// Seconds past the oracle max age after which positions may be closed at the
+    /// last committed price (0 = disabled)
+    pub outage_grace_seconds: u64,
+    /// Safety fee on last-price closes, paid to the pool (bps of size)
+    pub outage_close_fee_bps: u16,
+
+    // Dust
+    /// Decreases leaving less than this notional are turned into full closes (0 = disabled)
+    pub min_position_size_usd: Usd,
+
+    // Liquidation access
+    /// Anyone may liquidate eligible positions for the fee, not only keepers/liquidators
+    pub permissionless_liquidation: bool,
+
+    // Funding spread
+    /// Share of funding paid that receivers do not get (bps); it accrues to the insurance fund
+    pub funding_spread_bps: u16,
+
+    // Margin call
+    /// Warning band above the liquidation threshold (bps of collateral); positions inside it
+    /// get a margin call (0 = disabled)
+    pub margin_call_buffer_bps: u16,
+
+    // Hedged accounts
+    /// Charge borrowing only on an account's net exposure when it holds both sides of this market
+    pub net_borrowing_for_hedges: bool,
+
+    // Backstop tranche
+    /// Share of trading fees paid to the backstop tranche while it holds liquidity (bps)
+    pub backstop_fee_share_bps: u16,
+    /// Main-pool utilization (open interest over liquidity, bps) above which backstop LPs
+    /// cannot withdraw (0 = only blocked once the main pool is empty)
+    pub backstop_withdraw_max_utilization_bps: u16,
+
+    // Limit fills
+    /// Keeper-executed limit orders fill at the better of their trigger and the pool quote
+    /// (min for buys, max for sells) instead of at the quote
+    pub limit_fill_at_trigger: bool,
+
+    // Fee routing
+    /// Destinations of collected trading fees; all zero keeps the default routing
+    /// (referrer share, backstop share, rest to LPs)
+    pub fee_split: TradingFeeSplit,
+
+    // Leverage bands
+    /// Notional caps on positions levered above a band's threshold, enforced on increases
+    /// alongside `max_leverage` (at most `MAX_LEVERAGE_BANDS`)
+    pub leverage_bands: Vec<LeverageBand>,
+}
+
+/// Upper bound on `MarketConfig::leverage_bands`
+pub const MAX_LEVERAGE_BANDS: usize = 8;
+
+/// Positions levered above `above_leverage_x` may not exceed `max_position_size_usd` of notional
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct LeverageBand {
+    pub above_leverage_x: u8,
+    pub max_position_size_usd: Usd,
 }
 
 impl Default for MarketConfig {
@@ -69,18 +215,510 @@ impl Default for MarketConfig {
             borrowing_exponent: 0,
             skip_borrowing_for_smaller_side: false,
             trading_fee_bps: 0,
+            keeper_order_fee_bps: 0,
             max_leverage: 0,
             min_collateral_usd: 0,
             liquidation_threshold_bps: 0,
             liquidation_fee_bps: 0,
+            min_liquidation_fee_usd: 0,
+            max_liquidation_fee_usd: 0,
+            liquidation_slippage_bps: 0,
             reserve_factor_bps: 0,
+            long_reserve_factor_bps: 0,
+            short_reserve_factor_bps: 0,
             max_long_oi: 0,
             max_short_oi: 0,
+            max_long_oi_tokens: 0,
+            max_short_oi_tokens: 0,
+            max_position_size_usd: 0,
+            orderbook_enabled: false,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            maker_price_band_bps: 0,
+            lp_whitelist_enabled: false,
+            outage_grace_seconds: 0,
+            outage_close_fee_bps: 0,
+            min_position_size_usd: 0,
+            permissionless_liquidation: false,
+            funding_spread_bps: 0,
+            margin_call_buffer_bps: 0,
+            net_borrowing_for_hedges: false,
+            backstop_fee_share_bps: 0,
+            backstop_withdraw_max_utilization_bps: 8_000,
+            limit_fill_at_trigger: false,
+            fee_split: TradingFeeSplit::default(),
+            leverage_bands: Vec::new(),
+        }
+    }
+}
+
+impl MarketConfig {
+    /// Max OI on one side as bps of pool liquidity
+    pub fn reserve_factor_bps_for(&self, is_long: bool) -> u16 {
+        let side = if is_long { self.long_reserve_factor_bps } else { self.short_reserve_factor_bps };
+        if side > 0 { side } else { self.reserve_factor_bps }
+    }
+
+    /// Overwrite the fields set in `patch`
+    pub fn apply_patch(&mut self, patch: MarketConfigPatch) {
+        if let Some(v) = patch.pi_factor_positive {
+            self.pi_factor_positive = v;
+        }
+        if let Some(v) = patch.pi_factor_negative {
+            self.pi_factor_negative = v;
+        }
+        if let Some(v) = patch.pi_exponent {
+            self.pi_exponent = v;
+        }
+        if let Some(v) = patch.funding_factor {
+            self.funding_factor = v;
+        }
+        if let Some(v) = patch.funding_exponent {
+            self.funding_exponent = v;
+        }
+        if let Some(v) = patch.funding_factor_above_kink {
+            self.funding_factor_above_kink = v;
+        }
+        if let Some(v) = patch.optimal_imbalance_ratio {
+            self.optimal_imbalance_ratio = v;
+        }
+        if let Some(v) = patch.borrowing_factor {
+            self.borrowing_factor = v;
+        }
+        if let Some(v) = patch.borrowing_exponent {
+            self.borrowing_exponent = v;
+        }
+        if let Some(v) = patch.skip_borrowing_for_smaller_side {
+            self.skip_borrowing_for_smaller_side = v;
+        }
+        if let Some(v) = patch.trading_fee_bps {
+            self.trading_fee_bps = v;
+        }
+        if let Some(v) = patch.keeper_order_fee_bps {
+            self.keeper_order_fee_bps = v;
+        }
+        if let Some(v) = patch.max_leverage {
+            self.max_leverage = v;
+        }
+        if let Some(v) = patch.min_collateral_usd {
+            self.min_collateral_usd = v;
+        }
+        if let Some(v) = patch.liquidation_threshold_bps {
+            self.liquidation_threshold_bps = v;
+        }
+        if let Some(v) = patch.liquidation_fee_bps {
+            self.liquidation_fee_bps = v;
+        }
+        if let Some(v) = patch.min_liquidation_fee_usd {
+            self.min_liquidation_fee_usd = v;
+        }
+        if let Some(v) = patch.max_liquidation_fee_usd {
+            self.max_liquidation_fee_usd = v;
+        }
+        if let Some(v) = patch.liquidation_slippage_bps {
+            self.liquidation_slippage_bps = v;
+        }
+        if let Some(v) = patch.reserve_factor_bps {
+            self.reserve_factor_bps = v;
+        }
+        if let Some(v) = patch.long_reserve_factor_bps {
+            self.long_reserve_factor_bps = v;
+        }
+        if let Some(v) = patch.short_reserve_factor_bps {
+            self.short_reserve_factor_bps = v;
+        }
+        if let Some(v) = patch.max_long_oi {
+            self.max_long_oi = v;
+        }
+        if let Some(v) = patch.max_short_oi {
+            self.max_short_oi = v;
+        }
+        if let Some(v) = patch.max_long_oi_tokens {
+            self.max_long_oi_tokens = v;
+        }
+        if let Some(v) = patch.max_short_oi_tokens {
+            self.max_short_oi_tokens = v;
+        }
+        if let Some(v) = patch.max_position_size_usd {
+            self.max_position_size_usd = v;
+        }
+        if let Some(v) = patch.orderbook_enabled {
+            self.orderbook_enabled = v;
+        }
+        if let Some(v) = patch.maker_fee_bps {
+            self.maker_fee_bps = v;
+        }
+        if let Some(v) = patch.taker_fee_bps {
+            self.taker_fee_bps = v;
+        }
+        if let Some(v) = patch.maker_price_band_bps {
+            self.maker_price_band_bps = v;
+        }
+        if let Some(v) = patch.lp_whitelist_enabled {
+            self.lp_whitelist_enabled = v;
+        }
+        if let Some(v) = patch.outage_grace_seconds {
+            self.outage_grace_seconds = v;
+        }
+        if let Some(v) = patch.outage_close_fee_bps {
+            self.outage_close_fee_bps = v;
+        }
+        if let Some(v) = patch.min_position_size_usd {
+            self.min_position_size_usd = v;
+        }
+        if let Some(v) = patch.permissionless_liquidation {
+            self.permissionless_liquidation = v;
+        }
+        if let Some(v) = patch.funding_spread_bps {
+            self.funding_spread_bps = v;
+        }
+        if let Some(v) = patch.margin_call_buffer_bps {
+            self.margin_call_buffer_bps = v;
+        }
+        if let Some(v) = patch.net_borrowing_for_hedges {
+            self.net_borrowing_for_hedges = v;
+        }
+        if let Some(v) = patch.backstop_fee_share_bps {
+            self.backstop_fee_share_bps = v;
+        }
+        if let Some(v) = patch.backstop_withdraw_max_utilization_bps {
+            self.backstop_withdraw_max_utilization_bps = v;
+        }
+        if let Some(v) = patch.limit_fill_at_trigger {
+            self.limit_fill_at_trigger = v;
+        }
+        if let Some(v) = patch.fee_split {
+            self.fee_split = v;
+        }
+        if let Some(v) = patch.leverage_bands {
+            self.leverage_bands = v;
+        }
+    }
+
+    /// Sanity bounds: bps fees, thresholds and reserve factors at most 100%, non-zero max leverage,
+    /// liquidation fee floor not above its cap, a configured fee split summing to 100%,
+    /// leverage bands below max leverage with non-zero caps, a maker price band on order
+    /// book markets
+    pub fn validate(&self) -> Result<(), Error> {
+        let bps = [
+            self.trading_fee_bps,
+            self.keeper_order_fee_bps,
+            self.liquidation_threshold_bps,
+            self.liquidation_fee_bps,
+            self.liquidation_slippage_bps,
+            self.maker_fee_bps,
+            self.taker_fee_bps,
+            self.outage_close_fee_bps,
+            self.funding_spread_bps,
+            self.margin_call_buffer_bps,
+            self.backstop_fee_share_bps,
+            self.backstop_withdraw_max_utilization_bps,
+            self.reserve_factor_bps,
+            self.long_reserve_factor_bps,
+            self.short_reserve_factor_bps,
+        ];
+        if bps.into_iter().any(|b| b > 10_000) || self.max_leverage == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        if self.max_liquidation_fee_usd > 0 && self.min_liquidation_fee_usd > self.max_liquidation_fee_usd {
+            return Err(Error::InvalidParameter);
+        }
+        if self.orderbook_enabled && (self.maker_price_band_bps == 0 || self.maker_price_band_bps >= 10_000) {
+            return Err(Error::InvalidParameter);
+        }
+        if self.fee_split.is_set() && self.fee_split.total_bps() != 10_000 {
+            return Err(Error::InvalidFeeSplit);
+        }
+        let invalid_band = |b: &LeverageBand| {
+            b.above_leverage_x == 0 || b.above_leverage_x >= self.max_leverage || b.max_position_size_usd == 0
+        };
+        if self.leverage_bands.len() > MAX_LEVERAGE_BANDS || self.leverage_bands.iter().any(invalid_band) {
+            return Err(Error::InvalidParameter);
+        }
+        Ok(())
+    }
+
+    /// Per-side OI caps: USD and index tokens (0 = no token cap)
+    pub fn oi_caps(&self, is_long: bool) -> (Usd, u128) {
+        if is_long {
+            (self.max_long_oi, self.max_long_oi_tokens)
+        } else {
+            (self.max_short_oi, self.max_short_oi_tokens)
+        }
+    }
+
+    /// A side's OI of `oi_usd` and `oi_tokens` index tokens stays within both of its caps
+    pub fn oi_within_caps(&self, is_long: bool, oi_usd: Usd, oi_tokens: u128) -> bool {
+        let (cap_usd, cap_tokens) = self.oi_caps(is_long);
+        oi_usd <= cap_usd && (cap_tokens == 0 || oi_tokens <= cap_tokens)
+    }
+
+    /// Leverage bands allow a position of `size_usd` at `leverage_bps` (10_000 = 1x)
+    pub fn leverage_bands_allow(&self, size_usd: Usd, leverage_bps: u128) -> bool {
+        self.leverage_bands.iter().all(|band| {
+            leverage_bps <= (band.above_leverage_x as u128).saturating_mul(10_000)
+                || size_usd <= band.max_position_size_usd
+        })
+    }
+
+    /// `liquidation_fee_bps` of collateral, raised to the floor and lowered to the cap, and never
+    /// more than the collateral itself
+    pub fn liquidation_fee_usd(&self, collateral_usd: Usd) -> Usd {
+        let mut fee = collateral_usd.saturating_mul(self.liquidation_fee_bps as u128) / 10_000;
+        fee = fee.max(self.min_liquidation_fee_usd);
+        if self.max_liquidation_fee_usd > 0 {
+            fee = fee.min(self.max_liquidation_fee_usd);
+        }
+        fee.min(collateral_usd)
+    }
+}
+
+/// Per-market routing of collected trading fees (bps of the fee; sum to 10_000 when set)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct TradingFeeSplit {
+    /// LP claimable fees of the payer's side (the backstop tranche takes its share from this part)
+    pub lp_bps: u16,
+    pub insurance_bps: u16,
+    /// Credited to the protocol treasury, withdrawn by the admin
+    pub treasury_bps: u16,
+    /// Credited to the payer's referrer; goes to LPs when the payer has none
+    pub referrer_bps: u16,
+}
+
+impl TradingFeeSplit {
+    pub fn is_set(&self) -> bool {
+        self.total_bps() > 0
+    }
+
+    pub fn total_bps(&self) -> u32 {
+        [self.lp_bps, self.insurance_bps, self.treasury_bps, self.referrer_bps]
+            .into_iter()
+            .map(u32::from)
+            .sum()
+    }
+
+    pub fn share(&self, bps: u16, fee: Usd) -> Usd {
+        fee.saturating_mul(bps as u128) / 10_000
+    }
+
+    /// Insurance, treasury and referrer parts of `fee`, each clamped to what the parts before
+    /// it leave, so together they never exceed the fee
+    pub fn parts(&self, fee: Usd) -> (Usd, Usd, Usd) {
+        let insurance = self.share(self.insurance_bps, fee).min(fee);
+        let treasury = self.share(self.treasury_bps, fee).min(fee - insurance);
+        let referrer = self.share(self.referrer_bps, fee).min(fee - insurance - treasury);
+        (insurance, treasury, referrer)
+    }
+}
+
+/// Partial market config update: `Some` fields replace the current values, `None` keeps them
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketConfigPatch {
+    pub pi_factor_positive: Option<u128>,
+    pub pi_factor_negative: Option<u128>,
+    pub pi_exponent: Option<u128>,
+    pub funding_factor: Option<u128>,
+    pub funding_exponent: Option<u128>,
+    pub funding_factor_above_kink: Option<u128>,
+    pub optimal_imbalance_ratio: Option<u128>,
+    pub borrowing_factor: Option<u128>,
+    pub borrowing_exponent: Option<u128>,
+    pub skip_borrowing_for_smaller_side: Option<bool>,
+    pub trading_fee_bps: Option<u16>,
+    pub keeper_order_fee_bps: Option<u16>,
+    pub max_leverage: Option<u8>,
+    pub min_collateral_usd: Option<Usd>,
+    pub liquidation_threshold_bps: Option<u16>,
+    pub liquidation_fee_bps: Option<u16>,
+    pub min_liquidation_fee_usd: Option<Usd>,
+    pub max_liquidation_fee_usd: Option<Usd>,
+    pub liquidation_slippage_bps: Option<u16>,
+    pub reserve_factor_bps: Option<u16>,
+    pub long_reserve_factor_bps: Option<u16>,
+    pub short_reserve_factor_bps: Option<u16>,
+    pub max_long_oi: Option<Usd>,
+    pub max_short_oi: Option<Usd>,
+    pub max_long_oi_tokens: Option<u128>,
+    pub max_short_oi_tokens: Option<u128>,
+    pub max_position_size_usd: Option<Usd>,
+    pub orderbook_enabled: Option<bool>,
+    pub maker_fee_bps: Option<u16>,
+    pub taker_fee_bps: Option<u16>,
+    pub maker_price_band_bps: Option<u16>,
+    pub lp_whitelist_enabled: Option<bool>,
+    pub outage_grace_seconds: Option<u64>,
+    pub outage_close_fee_bps: Option<u16>,
+    pub min_position_size_usd: Option<Usd>,
+    pub permissionless_liquidation: Option<bool>,
+    pub funding_spread_bps: Option<u16>,
+    pub margin_call_buffer_bps: Option<u16>,
+    pub net_borrowing_for_hedges: Option<bool>,
+    pub backstop_fee_share_bps: Option<u16>,
+    pub backstop_withdraw_max_utilization_bps: Option<u16>,
+    pub limit_fill_at_trigger: Option<bool>,
+    pub fee_split: Option<TradingFeeSplit>,
+    pub leverage_bands: Option<Vec<LeverageBand>>,
+}
+
+/// One step of an atomic admin batch (`AdminService::batch`)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum AdminAction {
+    CreateMarket {
+        market_id: String,
+        index_token: String,
+        long_token: String,
+        short_token: String,
+        market_token: ActorId,
+        config: MarketConfig,
+        metadata: MarketMetadata,
+    },
+    SetMarketConfig {
+        market_id: String,
+        config: MarketConfig,
+    },
+    UpdateMarketConfig {
+        market_id: String,
+        patch: MarketConfigPatch,
+    },
+    AddKeeper(ActorId),
+    RemoveKeeper(ActorId),
+}
+
+/// Market config update announced ahead of time, applied once `effective_at` passes
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ScheduledMarketConfig {
+    pub config: MarketConfig,
+    pub effective_at: u64,
+    pub scheduled_at: u64,
+}
+
+/// Delisting lifecycle stage of a market
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum MarketStage {
+    Listed,
+    /// Delisting announced: only decreases and closes until `deadline`
+    ReduceOnly { deadline: u64 },
+    /// Deadline passed: remaining positions are settled at the settlement-window TWAP
+    Settling,
+    /// All positions settled; only LP withdrawals remain
+    Delisted,
+}
+
+/// Trader request to close a position of a settling market at its settlement price
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct SettlementClose {
+    pub position_key: PositionKey,
+    pub account: ActorId,
+    pub submitted_at: u64,
+}
+
+/// Time-weighted oracle mid sampled by keepers over a settling market's settlement window
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct SettlementTwap {
+    pub first_at: u64,
+    pub last_at: u64,
+    pub last_price: u128,
+    /// Sum of price × seconds between samples
+    pub cumulative: u128,
+    pub samples: u32,
+}
+
+impl SettlementTwap {
+    pub fn sample(&mut self, price: u128, now: u64) {
+        if self.samples == 0 {
+            self.first_at = now;
+        } else {
+            let elapsed = now.saturating_sub(self.last_at) as u128;
+            self.cumulative = self.cumulative.saturating_add(self.last_price.saturating_mul(elapsed));
+        }
+        self.last_at = now;
+        self.last_price = price;
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// Average price from the first sample to `end`, the last sample holding until `end`
+    pub fn average(&self, end: u64) -> u128 {
+        let span = end.saturating_sub(self.first_at) as u128;
+        if span == 0 {
+            return self.last_price;
         }
+        let tail = end.saturating_sub(self.last_at) as u128;
+        self.cumulative.saturating_add(self.last_price.saturating_mul(tail)) / span
     }
 }
 
-/// Pool accounting in USD only
+/// Limits for a new market's bootstrap phase
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BootstrapParams {
+    /// Cap on the normal max leverage
+    pub max_leverage: u8,
+    /// Caps on the normal OI caps (USD)
+    pub max_long_oi: Usd,
+    pub max_short_oi: Usd,
+    /// Multiplier on trading, maker and taker fees (10_000 = unchanged)
+    pub fee_multiplier_bps: u32,
+    /// The phase ends once pool liquidity reaches this TVL...
+    pub min_tvl_usd: Usd,
+    /// ...and at least this long has passed since it started
+    pub min_duration_seconds: u64,
+}
+
+/// Bootstrap phase in progress. The market runs on `normal_config` tightened by `params`
+/// until both thresholds are met, then `normal_config` takes over unchanged.
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketBootstrap {
+    pub params: BootstrapParams,
+    pub normal_config: MarketConfig,
+    pub started_at: u64,
+}
+
+/// Active market config together with any scheduled replacement
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketConfigSchedule {
+    pub active: MarketConfig,
+    pub pending: Option<ScheduledMarketConfig>,
+}
+
+/// Everything a frontend needs to trade one market, read from a single state borrow
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct TradingContext {
+    pub timestamp: u64,
+    pub market: Market,
+    /// Effective config (a scheduled update past its effective time counts as active)
+    pub config: MarketConfig,
+    pub pool: PoolAmounts,
+    /// `None` if the oracle has no price for the market yet
+    pub price: Option<Price>,
+    pub balance: Usd,
+    /// The account's positions on this market
+    pub positions: Vec<Position>,
+    /// The account's pending saved orders on this market
+    pub orders: Vec<(RequestKey, Order)>,
+}
+
+/// Pool accounting in USD, plus OI in index tokens for the token caps
 #[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
@@ -90,6 +728,10 @@ pub struct PoolAmounts {
     pub claimable_fee_usd_short: Usd,
     pub long_oi_usd: Usd,
     pub short_oi_usd: Usd,
+    /// Per-side OI in index token units at USD_SCALE precision, each position counted at its
+    /// entry price
+    pub long_oi_tokens: u128,
+    pub short_oi_tokens: u128,
     pub position_impact_pool_usd: Usd,
     pub swap_impact_pool_usd: Usd,
     pub total_borrowing_fees_usd: Usd,
@@ -103,7 +745,7 @@ pub struct PoolAmounts {
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
 pub struct Position {
-    /// Canonical keccak(account, market, collateral_token, is_long)
+    /// Canonical keccak(account, market, collateral_token, is_long), or a sub-position key
     pub key: PositionKey,
     /// Owner of the position
     pub account: ActorId,
@@ -132,6 +774,23 @@ pub struct Position {
     pub increased_at_block: u32,
     pub decreased_at_block: u32,
     pub last_fee_update: u64,
+
+    /// Lifetime funding paid by this position (USD)
+    pub funding_paid: Usd,
+    /// Lifetime funding received by this position (USD)
+    pub funding_received: Usd,
+    /// Lifetime borrowing fees paid by this position (USD)
+    pub borrowing_paid: Usd,
+}
+
+/// Page of a market's positions sorted by position key
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct PositionPage {
+    pub positions: Vec<Position>,
+    /// Pass as `after` to fetch the rest; `None` when nothing was truncated
+    pub next_cursor: Option<PositionKey>,
 }
 
 #[derive(Encode, Decode, TypeInfo, Clone, Debug)]
@@ -181,6 +840,12 @@ pub enum OrderType {
     StopLossDecrease,
     MarketSwap,
     LimitSwap,
+    /// Time-sliced market orders executed by keepers in equal tranches
+    TwapIncrease,
+    TwapDecrease,
+    /// Stop orders that rest as limit orders at `StopLimitState::limit_price` once triggered
+    StopLimitIncrease,
+    StopLimitDecrease,
 }
 
 #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
@@ -202,6 +867,100 @@ pub enum OrderSide {
     Short,
 }
 
+/// Price a percentage-based trigger is measured from
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum TriggerReference {
+    /// Entry price of the caller's open position
+    Entry,
+    /// Current oracle mid
+    Mark,
+}
+
+/// Account position mode.
+/// Hedge: long and short positions on the same market coexist.
+/// OneWay: opening the opposite side first nets against the existing position.
+/// SubPositions: like Hedge, but each increase that targets no position opens a separate
+/// sub-position, so entries on the same market and side keep their own size, entry and orders.
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum PositionMode {
+    #[default]
+    Hedge,
+    OneWay,
+    SubPositions,
+}
+
+/// Isolated: each position is liquidated on its own collateral.
+/// Cross: positions are assessed together against account-level equity,
+/// with maintenance offsets for hedged exposure inside correlation groups.
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum MarginMode {
+    #[default]
+    Isolated,
+    Cross,
+}
+
+/// Markets whose opposing exposures offset each other's maintenance requirement
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct CorrelationGroup {
+    pub group_id: String,
+    pub markets: Vec<String>,
+    /// Share of the group's maintenance waived for fully hedged exposure (bps)
+    pub offset_bps: u16,
+}
+
+/// Cross-margin account health
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct AccountMargin {
+    /// Collateral + unrealized PnL - pending fees across all positions
+    pub equity: i128,
+    /// Sum of per-position maintenance requirements
+    pub maintenance_gross: Usd,
+    /// Reduction from correlation-group offsets
+    pub maintenance_offset: Usd,
+    pub maintenance_net: Usd,
+}
+
+/// Per-account preferences consumed by the protocol
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct AccountSettings {
+    /// Used to derive `acceptable_price` from the oracle mid when an order passes 0
+    pub default_max_slippage_bps: Option<u16>,
+    pub position_mode: PositionMode,
+    /// Receiver of decrease payouts for orders that name none (defaults to the account itself)
+    pub default_receiver: Option<ActorId>,
+    /// Take-profit distance from entry (bps), registered after each market open
+    pub default_take_profit_bps: Option<u16>,
+    /// Stop-loss distance from entry (bps), registered after each market open
+    pub default_stop_loss_bps: Option<u16>,
+    pub margin_mode: MarginMode,
+    /// Funding paid in one settlement at or above which a notice is posted to the inbox
+    pub funding_alert_threshold_usd: Option<Usd>,
+    /// Send realized profits out of the internal balance after each close
+    pub auto_withdraw: Option<AutoWithdraw>,
+}
+
+/// Profit sweep preference: a close realizing at least `min_profit_usd` sends that profit
+/// to the account as `token` (a registered collateral token)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct AutoWithdraw {
+    pub token: ActorId,
+    pub min_profit_usd: Usd,
+}
+
 #[derive(Encode, Decode, TypeInfo, Clone, Debug)]
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
@@ -222,13 +981,51 @@ pub struct Order {
     pub is_frozen: bool,
     pub status: OrderStatus,
     pub execution_fee: u128,
+    /// Native VARA escrowed for the keeper gas refund and the callback gas
+    pub native_fee: u128,
+    /// Native VARA bid paid to whichever keeper executes the order; keepers serve highest bids first
+    pub priority_fee: u128,
+    /// Good-till-time: the order can no longer execute at or after this timestamp
+    pub expires_at: Option<u64>,
+    /// Size executed so far (TWAP orders fill in several tranches)
+    pub filled_size_usd: u128,
+    pub twap: Option<TwapState>,
+    pub stop_limit: Option<StopLimitState>,
     pub callback_gas_limit: u64,
+    pub client_order_id: Option<u64>,
+    pub tag: Option<u32>,
+    pub position_key: Option<PositionKey>,
+    /// One-cancels-other sibling (the other leg of a bracket), cancelled once this order executes
+    pub linked_order: Option<RequestKey>,
     pub created_at_block: u32,
     pub created_at_time: u64,
     pub updated_at_block: u32,
     pub updated_at_time: u64,
 }
 
+/// Tranche schedule and progress of a TWAP order
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct TwapState {
+    pub tranches: u32,
+    pub executed_tranches: u32,
+    pub interval_seconds: u64,
+    /// Earliest time keepers may execute the next tranche
+    pub next_execution_at: u64,
+}
+
+/// Limit leg of a stop-limit order
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct StopLimitState {
+    /// Worst fill price once triggered; the order stays open while the fill would be worse
+    pub limit_price: u128,
+    /// Set when the stop first triggers; from then on the trigger no longer has to hold
+    pub triggered_at: Option<u64>,
+}
+
 /// Simplified parameters for creating orders
 #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
 #[codec(crate = sails_rs::scale_codec)]
@@ -239,50 +1036,1074 @@ pub struct CreateOrderParams {
     pub order_type: OrderType,
     pub side: OrderSide,
     pub size_delta_usd: u128,
+    /// Collateral added by an increase or released by a decrease (0 on a partial decrease =
+    /// the closed share of the position's collateral)
     pub collateral_delta_amount: u128,
     pub trigger_price: u128,
     pub acceptable_price: u128,
     pub execution_fee: u128,
+    /// Good-till-time for saved orders (`None` = until cancelled or swept by the order TTL)
+    pub expires_at: Option<u64>,
+    pub time_in_force: TimeInForce,
+    /// Registered referral code; binds the account to the code's owner on first use
+    pub referral_code: Option<String>,
+    /// Credited with decrease payouts (`None` = the account's default receiver, else the account
+    /// itself); escrowed native fees are always refunded to the account
+    pub receiver: Option<ActorId>,
+    /// Contract notified when the saved order executes or is cancelled
+    pub callback_contract: Option<ActorId>,
+    /// Gas forwarded with each callback message; escrowed from the native balance at `callback_gas_price`
+    pub callback_gas_limit: u64,
+    /// Client-chosen id, unique per account; a saved order reusing one is rejected, so a
+    /// retried message cannot rest the same order twice
+    pub client_order_id: Option<u64>,
+    /// Strategy label carried to the saved order, its fills, notices and callbacks
+    pub tag: Option<u32>,
+    /// Open position the order trades (`None` = the canonical position of market, collateral
+    /// and side; in sub-position mode an increase then opens a new sub-position)
+    pub position_key: Option<PositionKey>,
 }
 
-/// Parameters for updating orders
-#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
-#[codec(crate = sails_rs::scale_codec)]
-#[scale_info(crate = sails_rs::scale_info)]
-pub struct UpdateOrderParams {
-    pub size_delta_usd: Option<u128>,
-    pub trigger_price: Option<u128>,
-    pub acceptable_price: Option<u128>,
-}
-
-/// Result of order creation
+/// Outcome of a saved order reported to its callback contract
 #[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
-pub enum ExecutionResult {
+pub enum OrderCallbackOutcome {
+    /// The order (or one TWAP tranche of it) executed
     Executed {
         position_key: PositionKey,
+        size_delta_usd: Usd,
         execution_price: u128,
     },
-    Saved {
-        order_key: RequestKey,
-    },
+    Cancelled,
 }
 
-/// USD price, scaled by USD_SCALE (micro-USD per 1 index unit)
-#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
-pub struct Price {
-    pub min: u128,
-    pub max: u128,
+pub struct OrderCallback {
+    pub order_key: RequestKey,
+    pub account: ActorId,
+    pub market: String,
+    pub tag: Option<u32>,
+    pub outcome: OrderCallbackOutcome,
 }
 
-#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+/// How long a limit order may wait for its price
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[codec(crate = sails_rs::scale_codec)]
 #[scale_info(crate = sails_rs::scale_info)]
-pub struct OracleConfig {
-    pub max_age_seconds: u64,
+pub enum TimeInForce {
+    /// Saved until executed, cancelled or expired
+    #[default]
+    Gtc,
+    /// Executed now at or better than the trigger, otherwise cancelled without being saved
+    Ioc,
+    /// Executed now in full, otherwise the call fails
+    Fok,
+}
+
+/// Parameters for updating orders
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct UpdateOrderParams {
+    pub size_delta_usd: Option<u128>,
+    pub trigger_price: Option<u128>,
+    pub acceptable_price: Option<u128>,
+}
+
+/// Stop-loss / take-profit orders registered together with a market open
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BracketParams {
+    pub stop_loss_price: u128,
+    pub take_profit_price: u128,
+    /// Acceptable price distance beyond each trigger
+    pub max_slippage_bps: u16,
+    pub execution_fee: u128,
+}
+
+/// How a scaled order's total size is split across its price levels
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum SizeDistribution {
+    /// Equal size at every level
+    Flat,
+    /// Linearly growing from the start price to the end price
+    Increasing,
+    /// Linearly shrinking from the start price to the end price
+    Decreasing,
+}
+
+/// Ladder of limit orders spread evenly between `start_price` and `end_price` (inclusive)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ScaledOrderParams {
+    pub order_count: u32,
+    pub start_price: u128,
+    pub end_price: u128,
+    pub distribution: SizeDistribution,
+    /// Acceptable price distance beyond each level's trigger
+    pub max_slippage_bps: u16,
+}
+
+/// Synthetic book depth at one distance from mid
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct DepthLevel {
+    pub bps: u16,
+    /// Largest long (buy) that fills within `bps` of mid
+    pub long_size_usd: Usd,
+    /// Largest short (sell) that fills within `bps` of mid
+    pub short_size_usd: Usd,
+}
+
+/// Result of an atomic open with brackets
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BracketOrderResult {
+    pub open: ExecutionResult,
+    pub stop_loss_key: RequestKey,
+    pub take_profit_key: RequestKey,
+}
+
+/// Result of order creation
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum ExecutionResult {
+    Executed {
+        position_key: PositionKey,
+        execution_price: u128,
+    },
+    Saved {
+        order_key: RequestKey,
+    },
+    /// Immediate-or-cancel order that could not execute; nothing was saved
+    Cancelled,
+}
+
+/// Outcome of closing one position in a close-all request
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct PositionCloseResult {
+    pub position_key: PositionKey,
+    pub result: Result<ExecutionResult, Error>,
+}
+
+/// USD price, scaled by USD_SCALE (micro-USD per 1 index unit)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct Price {
+    pub min: u128,
+    pub max: u128,
+}
+
+/// Resting maker order on a market's order book.
+/// Bids open longs, asks open shorts; collateral is escrowed from the wallet balance.
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BookOrder {
+    pub id: u64,
+    pub account: ActorId,
+    pub market: String,
+    pub collateral_token: String,
+    pub is_long: bool,
+    /// Limit price in USD per 1 index unit
+    pub price: u128,
+    /// Remaining (unfilled) size in USD
+    pub size_usd: Usd,
+    /// Remaining escrowed collateral in USD
+    pub collateral_usd: Usd,
+    pub created_at: u64,
+}
+
+/// Per-market order book: price levels hold maker order ids in FIFO order
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct OrderBook {
+    pub bids: BTreeMap<u128, Vec<u64>>,
+    pub asks: BTreeMap<u128, Vec<u64>>,
+    pub orders: BTreeMap<u64, BookOrder>,
+}
+
+/// Aggregated size at a single price level
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BookLevel {
+    pub price: u128,
+    pub size_usd: Usd,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct OrderBookDepth {
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+/// Published solvency snapshot: Merkle root over (account, balance, position equity) leaves
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BalancesSnapshot {
+    pub epoch: u64,
+    pub root: H256,
+    pub published_at: u64,
+    /// Leaves sorted by account: (account, wallet balance, position equity)
+    pub leaves: Vec<(ActorId, Usd, i128)>,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BalancesRootInfo {
+    pub epoch: u64,
+    pub root: H256,
+    pub published_at: u64,
+    pub leaf_count: u32,
+}
+
+/// Inclusion proof of an account in the latest published balances root
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BalanceProof {
+    pub account: ActorId,
+    pub balance: Usd,
+    /// Collateral plus unrealized PnL across open positions (at publication time)
+    pub equity: i128,
+    pub leaf_index: u32,
+    pub leaf_count: u32,
+    pub leaf: H256,
+    pub proof: Vec<H256>,
+    pub root: H256,
+}
+
+/// Compact summary of protocol state for bridges and external risk systems.
+/// `hash` commits to every field except `signers`.
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct StateCheckpoint {
+    /// Sequential checkpoint number, starting at 1
+    pub version: u64,
+    pub block: u32,
+    pub published_at: u64,
+    /// Sum of pool liquidity across markets
+    pub total_liquidity_usd: Usd,
+    pub total_long_oi_usd: Usd,
+    pub total_short_oi_usd: Usd,
+    pub insurance_fund_usd: Usd,
+    pub market_count: u32,
+    pub hash: H256,
+    /// Admin and keepers that attested to `hash`, in signing order
+    pub signers: Vec<ActorId>,
+}
+
+/// Keeper execution cost parameters used to price `execution_fee`
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ExecutionFeeConfig {
+    /// Estimated keeper gas cost per execution in USD (fixed-point)
+    pub keeper_gas_cost_usd: Usd,
+    /// Keeper bounty on top of the gas cost (bps)
+    pub keeper_bounty_bps: u16,
+    /// Native VARA escrowed per saved order and refunded to the executing keeper (0 = off)
+    pub keeper_gas_refund: u128,
+    /// Native VARA refunded to the liquidator out of the liquidated owner's native balance (0 = off)
+    pub liquidation_gas_refund: u128,
+    /// Native VARA per unit of callback gas, escrowed in full from the owner when an order with a
+    /// callback is saved and paid with the keeper gas refund (0 = callbacks are not charged)
+    pub callback_gas_price: u128,
+    /// Minimum age before a saved order may be swept as stale by a keeper (0 = sweeping off)
+    pub stale_order_min_age_seconds: u64,
+    /// Share of a swept order's escrowed native fee paid to the sweeping keeper (bps)
+    pub stale_sweep_reward_bps: u16,
+}
+
+/// Global ceilings on state growth (0 = unlimited)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct CapacityLimits {
+    pub max_open_positions: u64,
+    pub max_pending_orders: u64,
+}
+
+/// Lifetime USD total plus hourly buckets covering the recent window
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct WindowedUsd {
+    pub total: Usd,
+    /// (bucket start timestamp, sum), oldest first
+    pub buckets: Vec<(u64, Usd)>,
+}
+
+/// Funding settled per market, split by the paying side
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct FundingStats {
+    pub paid_by_longs: WindowedUsd,
+    pub paid_by_shorts: WindowedUsd,
+    /// Funding credited to receiving positions (both sides)
+    pub received: WindowedUsd,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct DiscountTier {
+    /// Minimum external balance to qualify
+    pub min_balance: U256,
+    pub discount_bps: u16,
+}
+
+/// Trading-fee discounts granted for holding an external token/NFT
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct FeeDiscountConfig {
+    /// Program queried for balances (None = discounts off)
+    pub program: Option<ActorId>,
+    /// Sails route of the balance query, e.g. ("Vft", "BalanceOf"); called with the account,
+    /// expected to reply with a U256
+    pub service: String,
+    pub method: String,
+    /// Sorted by `min_balance`; the highest qualifying tier applies
+    pub tiers: Vec<DiscountTier>,
+    /// How long a fetched balance stays valid
+    pub cache_ttl_seconds: u64,
+    /// Gas deposit for the reply of the balance query
+    pub reply_deposit: u64,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct CachedDiscount {
+    pub balance: U256,
+    pub discount_bps: u16,
+    pub fetched_at: u64,
+}
+
+/// Guardian set allowed to rotate an inactive admin
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct RecoveryConfig {
+    pub guardians: Vec<ActorId>,
+    /// Votes for the same new admin required to rotate
+    pub threshold: u32,
+    /// Admin inactivity after which guardians may vote (0 = recovery disabled)
+    pub inactivity_period_seconds: u64,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct RecoveryStatus {
+    pub config: RecoveryConfig,
+    pub admin: ActorId,
+    pub admin_last_active: u64,
+    /// Earliest time guardians may vote
+    pub recovery_available_at: Option<u64>,
+    /// (proposed admin, votes) in the current round
+    pub votes: Vec<(ActorId, u32)>,
+}
+
+/// Withdraw-only shutdown: proposed by the admin, active after a timelock or once a guardian
+/// threshold approves. Once active it is permanent.
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct WithdrawOnlyState {
+    pub proposed_at: u64,
+    pub activates_at: u64,
+    /// Proposed reference price per market; positions settle at the oracle mid held within a band of it
+    pub settlement_prices: BTreeMap<String, u128>,
+    /// Guardians that approved early activation
+    pub approvals: Vec<ActorId>,
+}
+
+/// Inputs of a position key derivation
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct PositionKeyParams {
+    pub account: ActorId,
+    pub market: String,
+    pub collateral_token: String,
+    pub is_long: bool,
+}
+
+/// A position key with the exact bytes it was hashed from (keccak-256)
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct PositionKeyDerivation {
+    pub params: PositionKeyParams,
+    /// account (32) || market (UTF-8) || collateral token (UTF-8) || is_long (1 byte)
+    pub preimage: Vec<u8>,
+    pub key: PositionKey,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Copy, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum FillKind {
+    Increase,
+    Decrease,
+    Liquidation,
+}
+
+/// An executed position change, kept in a per-account ring buffer for trade history
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct FillRecord {
+    pub position_key: PositionKey,
+    pub market: String,
+    pub is_long: bool,
+    pub kind: FillKind,
+    pub size_usd: Usd,
+    pub price: u128,
+    /// Order book maker/taker fee charged on this fill
+    pub trading_fee_usd: Usd,
+    /// Funding + borrowing settled on the position at fill time (negative = received)
+    pub settled_fees_usd: i128,
+    pub realized_pnl: i128,
+    pub timestamp: u64,
+    pub block: u32,
+    /// Strategy label of the order that produced the fill
+    pub tag: Option<u32>,
+}
+
+/// What happened when a saved order executed, persisted at execution time
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ExecutionDetails {
+    pub order_key: RequestKey,
+    pub position_key: PositionKey,
+    pub executor: ActorId,
+    pub size_delta_usd: Usd,
+    pub execution_price: u128,
+    /// Oracle mid at execution
+    pub mid_price: u128,
+    /// Positive = better for trader, negative = worse
+    pub price_impact_usd: i128,
+    /// Funding + borrowing settled on the resulting position (negative = received)
+    pub settled_fees_usd: i128,
+    pub realized_pnl: i128,
+    /// USD execution fee moved from the account to the executor
+    pub execution_fee_usd: Usd,
+    /// Native gas refund plus priority fee paid to the executor
+    pub keeper_native_fee: u128,
+    pub timestamp: u64,
+    pub block: u32,
+}
+
+/// What an inbox notice is about
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum NoticeKind {
+    MarginCall(MarginCall),
+    OrderExecuted {
+        order_key: RequestKey,
+        position_key: PositionKey,
+        size_delta_usd: Usd,
+        execution_price: u128,
+        tag: Option<u32>,
+    },
+    Liquidated(LiquidationRecord),
+    /// Funding paid in one settlement reached the account's alert threshold
+    FundingCharged {
+        position_key: PositionKey,
+        market: String,
+        amount_usd: Usd,
+    },
+    /// Realized profit sent out through the account's auto-withdraw token
+    ProfitWithdrawn {
+        token: ActorId,
+        amount_usd: Usd,
+        token_amount: u128,
+    },
+    /// Auto-top-up rule moved balance into a position's collateral
+    CollateralToppedUp(AutoTopUpApplied),
+    /// Missed heartbeat: resting orders were cancelled and the switch disarmed
+    DeadManSwitchTriggered(DeadManSwitchTrigger),
+    /// A position condition held and part of the position was closed
+    ConditionalCloseExecuted(ConditionalCloseExecuted),
+}
+
+/// Account-relevant event kept in the account's inbox until acknowledged
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct Notice {
+    /// Increasing per account; acknowledge up to an id to clear older notices
+    pub id: u64,
+    pub kind: NoticeKind,
+    pub timestamp: u64,
+    pub block: u32,
+}
+
+/// Point-in-time account equity, for PnL charts and drawdown metrics
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct EquitySample {
+    pub timestamp: u64,
+    pub block: u32,
+    /// Wallet balance held in the program
+    pub balance_usd: Usd,
+    /// Collateral + PnL at the oracle mid over all open positions
+    pub position_equity_usd: i128,
+    pub equity_usd: i128,
+}
+
+/// Per-account inbox state
+#[derive(Clone, Debug, Default)]
+pub struct NoticeInbox {
+    pub next_id: u64,
+    /// Unacknowledged notices, oldest first
+    pub notices: VecDeque<Notice>,
+}
+
+/// A liquidated position, kept in bounded per-market and per-account logs
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct LiquidationRecord {
+    pub position_key: PositionKey,
+    pub account: ActorId,
+    pub market: String,
+    pub is_long: bool,
+    pub size_usd: Usd,
+    /// Collateral after fee settlement, before the liquidation fee
+    pub collateral_usd: Usd,
+    pub price: u128,
+    pub pnl: i128,
+    pub liquidation_fee: Usd,
+    /// Loss in excess of the remaining collateral, absorbed by the pool
+    pub shortfall_usd: Usd,
+    pub liquidator: ActorId,
+    pub timestamp: u64,
+    pub block: u32,
+}
+
+/// Referral earnings of a code owner
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ReferralStats {
+    pub referred_accounts: u32,
+    /// Fee shares credited to the referrer's balance
+    pub total_earned_usd: Usd,
+}
+
+/// Dry run of a liquidation at the current oracle price, after virtual fee settlement
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct LiquidationSimulation {
+    pub position_key: PositionKey,
+    pub liquidatable: bool,
+    /// Adverse side of the spread plus the market's liquidation slippage
+    pub price: u128,
+    /// Unsettled funding + borrowing (negative = owed to the position)
+    pub pending_fees_usd: i128,
+    /// Collateral after fee settlement, before the liquidation fee
+    pub collateral_usd: Usd,
+    pub pnl: i128,
+    pub liquidation_fee: Usd,
+    pub owner_payout_usd: Usd,
+    /// Loss in excess of the remaining collateral
+    pub shortfall_usd: Usd,
+    /// Part of the shortfall the insurance fund would cover; the rest falls on the pool
+    pub insurance_draw_usd: Usd,
+}
+
+/// Result of one forced fee settlement pass over a market's positions
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketFeeSettlement {
+    pub market: String,
+    pub settled: u32,
+    /// Positions whose fees exceed their collateral (left for liquidation)
+    pub skipped: Vec<PositionKey>,
+    /// Net funding + borrowing settled (negative = credited to positions)
+    pub total_fees_usd: i128,
+    /// Where the next pass resumes; `None` once the market has been walked to the end
+    pub next_cursor: Option<PositionKey>,
+}
+
+/// Pool flow accumulated within the current block, used to aggregate price impact
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BlockFlow {
+    pub block: u32,
+    /// Pool OI before the block's first trade
+    pub long_oi_start: Usd,
+    pub short_oi_start: Usd,
+    /// Net OI flow this block, positive toward longs (long increases and short decreases)
+    pub net_flow_usd: i128,
+    /// Trades recorded this block, including book fills and liquidations
+    pub trades: u32,
+    /// Impact already applied to trades in this block (trader-centric sign)
+    pub charged_impact_usd: i128,
+}
+
+/// Realized execution quality of pool fills per market
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct SlippageStats {
+    pub executions: u64,
+    pub volume_usd: Usd,
+    /// Size-weighted cost of fills worse than the oracle mid (spread + impact)
+    pub adverse_slippage_usd: WindowedUsd,
+    /// Size-weighted gain of fills better than the oracle mid
+    pub favorable_slippage_usd: Usd,
+    pub max_slippage_bps: u32,
+    /// Positive price impact credited to traders for rebalancing fills
+    pub price_impact_rebated_usd: Usd,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct SlippageStatsView {
+    pub market: String,
+    pub executions: u64,
+    pub volume_usd: Usd,
+    /// Net slippage over volume, in bps (negative = fills better than mid on average)
+    pub avg_slippage_bps: i64,
+    pub max_slippage_bps: u32,
+    pub adverse_slippage_total: Usd,
+    pub adverse_slippage_recent: Usd,
+    pub favorable_slippage_total: Usd,
+    pub price_impact_paid_total: Usd,
+    pub price_impact_paid_recent: Usd,
+    pub price_impact_rebated_total: Usd,
+    pub window_seconds: u64,
+}
+
+/// Saved-order outcomes of a market, accumulated as orders execute or expire
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct OrderExecutionCounters {
+    pub executed: u64,
+    pub expired: u64,
+    /// Sum of creation-to-execution times of executed orders
+    pub total_wait_seconds: u64,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct OrderExecutionStats {
+    pub market: String,
+    /// Saved orders currently waiting for execution
+    pub pending: u32,
+    pub frozen: u32,
+    pub executed: u64,
+    pub expired: u64,
+    pub avg_time_to_execution_seconds: u64,
+}
+
+/// Fee inflows per market by source
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketFeeStats {
+    pub trading: WindowedUsd,
+    pub borrowing: WindowedUsd,
+    pub liquidation: WindowedUsd,
+    /// Adverse price impact paid by traders on pool executions
+    pub price_impact: WindowedUsd,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct FeeBreakdown {
+    pub trading: Usd,
+    pub borrowing: Usd,
+    /// Funding paid minus funding credited (retained by the pool)
+    pub funding_spread: Usd,
+    pub liquidation: Usd,
+    pub price_impact: Usd,
+    pub total: Usd,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketFeeRevenue {
+    pub market: String,
+    pub lifetime: FeeBreakdown,
+    pub recent: FeeBreakdown,
+    pub window_seconds: u64,
+}
+
+/// Protocol-wide totals across all markets
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ProtocolStats {
+    pub markets: u64,
+    /// Sum of pool liquidity (TVL)
+    pub total_liquidity_usd: Usd,
+    pub total_long_oi_usd: Usd,
+    pub total_short_oi_usd: Usd,
+    /// Lifetime fees of all kinds, funding spread included
+    pub total_fees_usd: Usd,
+    pub open_positions: u64,
+    pub open_orders: u64,
+    pub insurance_fund_usd: Usd,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct FundingStatsView {
+    pub market: String,
+    pub paid_by_longs_total: Usd,
+    pub paid_by_shorts_total: Usd,
+    pub paid_by_longs_recent: Usd,
+    pub paid_by_shorts_recent: Usd,
+    /// Length of the recent window in seconds
+    pub window_seconds: u64,
+}
+
+/// Accrued-but-unsettled position fees across a market
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarketPendingFees {
+    pub market: String,
+    /// Funding owed by positions on the paying side
+    pub funding_owed_usd: Usd,
+    /// Funding due to positions on the receiving side (owed less the funding spread)
+    pub funding_due_usd: Usd,
+    /// Borrowing fees owed to LPs
+    pub borrowing_usd: Usd,
+    pub open_positions: u32,
+    pub timestamp: u64,
+}
+
+/// Per-account lifetime trading accumulators, updated at execution and settlement
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct TraderStats {
+    /// PnL realized on decreases, closes and liquidations (before fees)
+    pub realized_pnl: i128,
+    /// Order book maker/taker fees
+    pub trading_fees: Usd,
+    pub borrowing_fees: Usd,
+    pub funding_paid: Usd,
+    pub funding_received: Usd,
+    /// Liquidation fees lost when liquidated
+    pub liquidation_fees: Usd,
+    /// Notional traded (increases + decreases)
+    pub volume_usd: Usd,
+    pub trade_count: u64,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct TraderStatsView {
+    pub account: ActorId,
+    pub stats: TraderStats,
+    /// Aggregate unrealized PnL of open positions at the oracle mid
+    pub unrealized_pnl: i128,
+    pub open_positions: u32,
+}
+
+/// Fungible token contract accepted as USD-denominated collateral
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct CollateralToken {
+    pub token: ActorId,
+    /// Token decimals; amounts are rescaled to micro-USD on credit
+    pub decimals: u8,
+}
+
+/// Program-driven keeper loop funded from gas reservations
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct SchedulerConfig {
+    pub enabled: bool,
+    /// Delay between scheduled runs, in blocks
+    pub interval_blocks: u32,
+    /// Saved orders older than this are expired on a run (0 = never expire)
+    pub order_ttl_seconds: u64,
+    /// Cap on liquidations per run to bound gas usage
+    pub max_liquidations_per_run: u32,
+    /// Cap on positions checked for liquidation per run; the next run resumes after the last one
+    pub max_liquidation_checks_per_run: u32,
+    /// Cap on request ids the order expiry walks per run; the next run resumes where it stopped
+    pub max_order_expiry_checks_per_run: u32,
+    /// Cap on accounts equity-sampled per run; the next run resumes after the last one
+    pub max_equity_samples_per_run: u32,
+    /// Cap on positions checked for margin calls per run; the next run resumes after the last one
+    pub max_margin_call_checks_per_run: u32,
+}
+
+/// Primary-keeper rotation. Each `interval_seconds` slot designates one keeper (round robin over
+/// the keeper list); the others act only after the primary has been silent for `backup_delay_seconds`.
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct KeeperRotationConfig {
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    pub backup_delay_seconds: u64,
+}
+
+/// Audit comparing each position's cached liquidation price with the live formula
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct LiquidationAuditConfig {
+    pub enabled: bool,
+    /// Deviation from the recomputed price (bps) reported as a mismatch
+    pub tolerance_bps: u16,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct LiquidationPriceMismatch {
+    pub position_key: PositionKey,
+    pub market: String,
+    pub cached_price: u128,
+    pub recomputed_price: u128,
+    pub deviation_bps: u32,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct LiquidationAuditReport {
+    pub checked: u32,
+    pub mismatches: Vec<LiquidationPriceMismatch>,
+    /// Where the next pass resumes; `None` once every position has been checked
+    pub next_cursor: Option<PositionKey>,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct KeeperActivity {
+    pub keeper: ActorId,
+    /// Last successful order execution, liquidation or order-expiring scheduled run (None = never seen)
+    pub last_active_at: Option<u64>,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct KeeperLiveness {
+    pub rotation: KeeperRotationConfig,
+    /// Current primary keeper (None when rotation is off or no keepers are registered)
+    pub primary: Option<ActorId>,
+    pub slot_ends_at: Option<u64>,
+    /// Whether backups may act now because the primary has been silent too long
+    pub backups_active: bool,
+    pub keepers: Vec<KeeperActivity>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_blocks: 100,
+            order_ttl_seconds: 0,
+            max_liquidations_per_run: 10,
+            max_liquidation_checks_per_run: 200,
+            max_order_expiry_checks_per_run: 200,
+            max_equity_samples_per_run: 50,
+            max_margin_call_checks_per_run: 200,
+        }
+    }
+}
+
+/// Gas reserved by the program to self-send a scheduled run
+#[derive(Clone, Debug)]
+pub struct GasReservation {
+    pub id: ReservationId,
+    pub amount: u64,
+    pub reserved_at_block: u32,
+    pub expires_at_block: u32,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct GasReservationInfo {
+    pub amount: u64,
+    pub reserved_at_block: u32,
+    pub expires_at_block: u32,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct SchedulerStatus {
+    pub config: SchedulerConfig,
+    pub reservations: Vec<GasReservationInfo>,
+    pub total_reserved_gas: u64,
+    /// Block at which the next self-sent run is due, if one is queued
+    pub next_run_block: Option<u32>,
+    pub last_run_block: Option<u32>,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ScheduledRunReport {
+    pub accrued_markets: u32,
+    pub expired_orders: u32,
+    pub liquidated_positions: u32,
+    /// Positions that entered the margin-call band during this run
+    pub margin_calls: Vec<MarginCall>,
+    /// Version of the state checkpoint published during this run, if one was due
+    pub checkpoint_version: Option<u64>,
+    /// Accounts whose equity was sampled during this run
+    pub equity_samples: u32,
+}
+
+/// Early warning for a position inside its market's margin-call band
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct MarginCall {
+    pub position_key: PositionKey,
+    pub account: ActorId,
+    pub market: String,
+    pub is_long: bool,
+    pub price: u128,
+    /// Collateral + PnL - pending fees
+    pub effective_collateral: i128,
+    pub liquidation_threshold_usd: Usd,
+    pub margin_call_threshold_usd: Usd,
+    pub timestamp: u64,
+}
+
+/// Opt-in heartbeat: if the account does not ping within `interval_seconds`, keepers cancel
+/// all of its resting orders
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct DeadManSwitch {
+    pub interval_seconds: u64,
+    pub last_heartbeat: u64,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct DeadManSwitchTrigger {
+    pub account: ActorId,
+    pub last_heartbeat: u64,
+    pub cancelled_orders: Vec<RequestKey>,
+    pub cancelled_book_orders: u32,
+}
+
+/// Keeper-applied collateral top-up of one isolated position, funded from the owner's balance
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct AutoTopUpRule {
+    /// Health factor (bps of the liquidation threshold) below which a top-up is applied
+    pub trigger_health_bps: u32,
+    /// Total collateral the rule may add over its lifetime
+    pub max_amount_usd: Usd,
+    pub used_usd: Usd,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct AutoTopUpApplied {
+    pub position_key: PositionKey,
+    pub account: ActorId,
+    pub amount_usd: Usd,
+    pub health_before_bps: u32,
+}
+
+/// Position metric watched by a conditional close, measured at the oracle mid after pending fees
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum PositionCondition {
+    /// Unrealized PnL net of pending fees at or above this USD amount
+    PnlAtLeast(i128),
+    /// Unrealized PnL net of pending fees at or below this USD amount
+    PnlAtMost(i128),
+    /// Size over effective collateral at or above this (bps, 10_000 = 1x)
+    LeverageAtLeast(u32),
+}
+
+/// Market close of part of a position once its condition holds, executed by keepers
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ConditionalClose {
+    pub key: RequestKey,
+    pub account: ActorId,
+    pub position_key: PositionKey,
+    pub condition: PositionCondition,
+    /// Share of the position closed when triggered (10_000 = all of it)
+    pub close_bps: u16,
+    /// Worst fill accepted, as a distance from the oracle mid at execution
+    pub max_slippage_bps: u16,
+    pub created_at: u64,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct ConditionalCloseExecuted {
+    pub key: RequestKey,
+    pub account: ActorId,
+    pub position_key: PositionKey,
+    pub condition: PositionCondition,
+    pub size_delta_usd: Usd,
+    pub execution_price: u128,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct OracleConfig {
+    pub max_age_seconds: u64,
 }
 
 #[derive(Encode, Decode, TypeInfo, Clone, Debug)]
@@ -293,6 +2114,72 @@ pub struct OracleState {
     pub timestamps: BTreeMap<String, u64>,
     pub last_signer: BTreeMap<String, ActorId>,
     pub config: OracleConfig,
+    /// Synthetic feeds priced as base / quote of two direct feeds
+    pub ratio_feeds: BTreeMap<String, RatioFeed>,
+    /// On-chain spot DEX TWAPs blended into direct feeds
+    pub spot_sources: BTreeMap<String, SpotTwapSource>,
+    pub spot_twaps: BTreeMap<String, CachedSpotTwap>,
+    /// Decimals publishers sign direct feed prices in; absent feeds are already micro-USD (6)
+    pub price_decimals: BTreeMap<String, u8>,
+}
+
+/// Spot DEX pool whose TWAP is blended into a direct feed's signed price
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct SpotTwapSource {
+    /// Pool program queried for the TWAP
+    pub program: ActorId,
+    /// Sails route of the TWAP query, called with `window_seconds`; expected to reply with the
+    /// price in USD per 1 token at the oracle scale (u128)
+    pub service: String,
+    pub method: String,
+    pub window_seconds: u64,
+    /// Share of the index price taken from the TWAP; the signed price keeps the rest
+    pub weight_bps: u16,
+    /// A fetched TWAP older than this is ignored and the signed price is used alone
+    pub max_age_seconds: u64,
+    /// A TWAP further than this from the signed mid is ignored and the signed price is used alone
+    pub max_deviation_bps: u16,
+    /// Gas deposit for the reply of the TWAP query
+    pub reply_deposit: u64,
+}
+
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct CachedSpotTwap {
+    pub price: u128,
+    pub fetched_at: u64,
+}
+
+/// Freshness of one oracle feed and the markets it is holding up
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct FeedStatus {
+    pub token: String,
+    /// Synthetic base / quote feed; its timestamp is the older leg's
+    pub is_ratio: bool,
+    /// `None` when no price was ever published
+    pub last_update: Option<u64>,
+    pub age_seconds: Option<u64>,
+    pub max_age_seconds: u64,
+    /// Not set for ratio feeds
+    pub last_signer: Option<ActorId>,
+    /// Older than `max_age_seconds`, or never published
+    pub stale: bool,
+    /// Markets indexed on this token; trading on them is blocked while the feed is stale
+    pub markets: Vec<String>,
+}
+
+/// Synthetic index price = price(base) / price(quote), scaled by USD_SCALE
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, PartialEq, Eq)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct RatioFeed {
+    pub base: String,
+    pub quote: String,
 }
 
 #[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
@@ -302,3 +2189,29 @@ pub struct MarketTokenInfo {
     pub total_supply: u128,
     pub balances: Vec<(ActorId, u128)>,
 }
+
+/// Junior LP tranche of a market. It pays trader profits only once the main pool's liquidity
+/// is exhausted, and earns `backstop_fee_share_bps` of trading fees in return.
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BackstopPool {
+    pub liquidity_usd: Usd,
+    pub total_shares: u128,
+    /// Fees earned per share, scaled by `BACKSTOP_FEE_SCALE`
+    pub fee_per_share: u128,
+    /// Profits paid out of the tranche since inception
+    pub absorbed_usd: Usd,
+}
+
+/// One provider's stake in a backstop tranche
+#[derive(Encode, Decode, TypeInfo, Clone, Debug, Default)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct BackstopStake {
+    pub shares: u128,
+    /// Pool `fee_per_share` at the last fee sync
+    pub fee_per_share_paid: u128,
+    /// Synced fees not yet claimed (USD)
+    pub pending_fee_usd: Usd,
+}
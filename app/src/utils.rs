@@ -1,6 +1,6 @@
-use sails_rs::prelude::{ActorId, H256, Vec, String};
+use sails_rs::prelude::{ActorId, Encode, H256, Vec, String};
 use sails_rs::gstd::exec;
-use crate::types::Price;
+use crate::types::{MAX_VIEW_REPLY_BYTES, Price, ViewLimits};
 
 /// Current block info
 #[inline]
@@ -8,6 +8,32 @@ pub fn now() -> (u32, u64) {
     (exec::block_height(), exec::block_timestamp())
 }
 
+/// Take items in order until `limits` are reached (at least one item is always taken).
+/// Returns the taken items and whether any were left over.
+pub fn take_within_limits<T: Encode>(items: impl IntoIterator<Item = T>, limits: &ViewLimits) -> (Vec<T>, bool) {
+    let max_items = limits.max_items.map_or(usize::MAX, |n| n.max(1) as usize);
+    let max_bytes = limits.max_bytes.unwrap_or(MAX_VIEW_REPLY_BYTES).min(MAX_VIEW_REPLY_BYTES) as usize;
+
+    let mut taken = Vec::new();
+    let mut bytes = 0usize;
+    for item in items {
+        let size = item.encoded_size();
+        if !taken.is_empty() && (taken.len() >= max_items || bytes.saturating_add(size) > max_bytes) {
+            return (taken, true);
+        }
+        bytes = bytes.saturating_add(size);
+        taken.push(item);
+    }
+    (taken, false)
+}
+
+/// Request key of the `id`-th generated request: the id little-endian in the first 8 bytes
+pub fn request_key(id: u64) -> H256 {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&id.to_le_bytes());
+    H256::from(bytes)
+}
+
 /// Canonical position key (keccak)
 pub fn position_key(
     account: ActorId,
@@ -16,12 +42,32 @@ pub fn position_key(
     is_long: bool,
 ) -> H256 {
     use sp_core::hashing::keccak_256;
-    let mut data = Vec::new();
+    H256::from(keccak_256(&position_key_preimage(account, market, collateral_token, is_long)))
+}
+
+/// Key of a sub-position: keccak(canonical key || nonce), unique per nonce
+pub fn sub_position_key(canonical: H256, nonce: u64) -> H256 {
+    use sp_core::hashing::keccak_256;
+    let mut data = [0u8; 40];
+    data[..32].copy_from_slice(canonical.as_bytes());
+    data[32..].copy_from_slice(&nonce.to_le_bytes());
+    H256::from(keccak_256(&data))
+}
+
+/// Bytes hashed into a position key:
+/// account (32) || market (UTF-8, unprefixed) || collateral token (UTF-8, unprefixed) || is_long (1)
+pub fn position_key_preimage(
+    account: ActorId,
+    market: &str,
+    collateral_token: &str,
+    is_long: bool,
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(33 + market.len() + collateral_token.len());
     data.extend_from_slice(account.as_ref());
     data.extend_from_slice(market.as_bytes());
     data.extend_from_slice(collateral_token.as_bytes());
     data.push(if is_long { 1 } else { 0 });
-    H256::from(keccak_256(&data))
+    data
 }
 
 pub fn verify_signature(
@@ -36,6 +82,103 @@ pub fn verify_signature(
     true
 }
 
+/// Domain prefixes of the solvency tree, so an internal node can never pass as a leaf
+const MERKLE_LEAF_PREFIX: u8 = 0x00;
+const MERKLE_NODE_PREFIX: u8 = 0x01;
+
+/// Solvency tree leaf: keccak(0x00 || account || balance || position equity)
+pub fn balance_leaf(account: ActorId, balance: u128, equity: i128) -> H256 {
+    use sp_core::hashing::keccak_256;
+    let mut data = Vec::with_capacity(65);
+    data.push(MERKLE_LEAF_PREFIX);
+    data.extend_from_slice(account.as_ref());
+    data.extend_from_slice(&balance.to_le_bytes());
+    data.extend_from_slice(&equity.to_le_bytes());
+    H256::from(keccak_256(&data))
+}
+
+/// State checkpoint commitment:
+/// keccak(version || block || published_at || liquidity || long OI || short OI || insurance fund || market count)
+pub fn checkpoint_hash(
+    version: u64,
+    block: u32,
+    published_at: u64,
+    totals: [u128; 4],
+    market_count: u32,
+) -> H256 {
+    use sp_core::hashing::keccak_256;
+    let mut data = Vec::with_capacity(88);
+    data.extend_from_slice(&version.to_le_bytes());
+    data.extend_from_slice(&block.to_le_bytes());
+    data.extend_from_slice(&published_at.to_le_bytes());
+    for total in totals {
+        data.extend_from_slice(&total.to_le_bytes());
+    }
+    data.extend_from_slice(&market_count.to_le_bytes());
+    H256::from(keccak_256(&data))
+}
+
+/// Internal node: keccak(0x01 || left || right)
+fn hash_pair(left: &H256, right: &H256) -> H256 {
+    use sp_core::hashing::keccak_256;
+    let mut data = [0u8; 65];
+    data[0] = MERKLE_NODE_PREFIX;
+    data[1..33].copy_from_slice(left.as_bytes());
+    data[33..].copy_from_slice(right.as_bytes());
+    H256::from(keccak_256(&data))
+}
+
+/// Binary Merkle root; an odd node at the end of a level is carried up unchanged
+pub fn merkle_root(leaves: &[H256]) -> H256 {
+    if leaves.is_empty() {
+        return H256::zero();
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| if pair.len() == 2 { hash_pair(&pair[0], &pair[1]) } else { pair[0] })
+            .collect();
+    }
+    level[0]
+}
+
+/// Sibling path from leaf `index` to the root (levels where the node is carried up are skipped)
+pub fn merkle_proof(leaves: &[H256], mut index: usize) -> Vec<H256> {
+    let mut proof = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            proof.push(level[sibling]);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| if pair.len() == 2 { hash_pair(&pair[0], &pair[1]) } else { pair[0] })
+            .collect();
+        index /= 2;
+    }
+    proof
+}
+
+/// Verify a proof produced by `merkle_proof` for a tree of `leaf_count` leaves
+pub fn verify_merkle_proof(leaf: H256, mut index: usize, mut leaf_count: usize, proof: &[H256], root: H256) -> bool {
+    let mut node = leaf;
+    let mut siblings = proof.iter();
+    while leaf_count > 1 {
+        let sibling = index ^ 1;
+        if sibling < leaf_count {
+            let Some(s) = siblings.next() else {
+                return false;
+            };
+            node = if index % 2 == 0 { hash_pair(&node, s) } else { hash_pair(s, &node) };
+        }
+        index /= 2;
+        leaf_count = leaf_count.div_ceil(2);
+    }
+    siblings.next().is_none() && node == root
+}
+
 /// Resolve market ID or token name to the correct oracle price key.
 /// If given a known market ID, returns its `index_token`
 pub fn price_key(id_or_token: &str) -> String {
@@ -45,4 +188,90 @@ pub fn price_key(id_or_token: &str) -> String {
     } else {
         String::from(id_or_token)
     }
-}
\ No newline at end of file
+}
+
+/// Rescale a token amount with `decimals` to micro-USD (6 decimals), rounding down.
+/// Returns `None` on overflow.
+pub fn token_amount_to_usd(amount: u128, decimals: u8) -> Option<u128> {
+    const USD_DECIMALS: u32 = 6;
+    let decimals = decimals as u32;
+    if decimals >= USD_DECIMALS {
+        Some(amount / 10u128.checked_pow(decimals - USD_DECIMALS)?)
+    } else {
+        amount.checked_mul(10u128.checked_pow(USD_DECIMALS - decimals)?)
+    }
+}
+
+/// Inverse of `token_amount_to_usd`, rounding down
+pub fn usd_to_token_amount(usd: u128, decimals: u8) -> Option<u128> {
+    const USD_DECIMALS: u32 = 6;
+    let decimals = decimals as u32;
+    if decimals >= USD_DECIMALS {
+        usd.checked_mul(10u128.checked_pow(decimals - USD_DECIMALS)?)
+    } else {
+        Some(usd / 10u128.checked_pow(USD_DECIMALS - decimals)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: u64) -> Vec<H256> {
+        (0..n).map(|i| balance_leaf(ActorId::from(i), i as u128 * 100, -(i as i128))).collect()
+    }
+
+    #[test]
+    fn test_merkle_proofs_verify_for_every_leaf() {
+        for n in 1..=9 {
+            let leaves = leaves(n);
+            let root = merkle_root(&leaves);
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = merkle_proof(&leaves, i);
+                assert!(verify_merkle_proof(*leaf, i, leaves.len(), &proof, root), "n={n} i={i}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_merkle_proof_rejects_wrong_leaf() {
+        let leaves = leaves(5);
+        let root = merkle_root(&leaves);
+        let proof = merkle_proof(&leaves, 2);
+        let forged = balance_leaf(ActorId::from(2u64), 1_000_000, 0);
+        assert!(!verify_merkle_proof(forged, 2, leaves.len(), &proof, root));
+    }
+
+    #[test]
+    fn test_token_amount_to_usd_rescales() {
+        assert_eq!(token_amount_to_usd(1_500_000, 6), Some(1_500_000));
+        assert_eq!(token_amount_to_usd(1_500_000_000_000_000_000, 18), Some(1_500_000));
+        assert_eq!(token_amount_to_usd(15, 1), Some(1_500_000));
+        assert_eq!(token_amount_to_usd(u128::MAX, 0), None);
+    }
+
+    #[test]
+    fn test_sub_position_keys_are_distinct() {
+        let canonical = position_key(ActorId::from(7u64), "ETH-USD", "USDC", true);
+        let first = sub_position_key(canonical, 1);
+        assert_eq!(first, sub_position_key(canonical, 1));
+        assert_ne!(first, sub_position_key(canonical, 2));
+        assert_ne!(first, canonical);
+        let other = position_key(ActorId::from(7u64), "ETH-USD", "USDC", false);
+        assert_ne!(first, sub_position_key(other, 1));
+    }
+
+    #[test]
+    fn test_position_key_preimage_layout() {
+        let account = ActorId::from(7u64);
+        let preimage = position_key_preimage(account, "ETH-USD", "USDC", true);
+        assert_eq!(preimage.len(), 32 + 7 + 4 + 1);
+        assert_eq!(&preimage[..32], account.as_ref());
+        assert_eq!(&preimage[32..39], b"ETH-USD");
+        assert_eq!(preimage.last(), Some(&1));
+        assert_eq!(
+            H256::from(sp_core::hashing::keccak_256(&preimage)),
+            position_key(account, "ETH-USD", "USDC", true)
+        );
+    }
+}